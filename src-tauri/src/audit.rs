@@ -0,0 +1,18 @@
+use crate::error::AppResult;
+use rusqlite::Connection;
+
+pub(crate) fn record_audit(
+    conn: &Connection,
+    entity: &str,
+    entity_id: i64,
+    action: &str,
+    actor: &str,
+    reason: Option<&str>,
+) -> AppResult<()> {
+    conn.execute(
+        "INSERT INTO audit_log (entity, entity_id, action, actor, reason)
+         VALUES (?1, ?2, ?3, ?4, ?5)",
+        (entity, entity_id, action, actor, reason),
+    )?;
+    Ok(())
+}
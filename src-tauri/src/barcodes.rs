@@ -0,0 +1,486 @@
+use crate::error::{AppError, AppResult};
+use crate::product_units::{find_unit_by_barcode, ProductUnitRecord};
+use crate::settings::{load_setting, store_setting};
+use crate::{
+    fetch_product_by_id, normalize_barcode, parse_product_row, Database, ProductRecord,
+    PRODUCT_COLUMNS,
+};
+use rusqlite::{Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+const EMBEDDED_RULES_KEY: &str = "embedded_barcode_rules";
+const EAN13_PAYLOAD_DIGITS: usize = 12;
+
+fn is_retail_code(code: &str) -> bool {
+    matches!(code.len(), 8 | 12 | 13) && code.bytes().all(|byte| byte.is_ascii_digit())
+}
+
+// GTIN check digit shared by EAN-8, UPC-A and EAN-13: digits are weighted 3, 1,
+// 3, ... starting from the one next to the check digit.
+pub(crate) fn has_valid_check_digit(code: &str) -> bool {
+    if !is_retail_code(code) {
+        return false;
+    }
+    let digits: Vec<u32> = code.bytes().map(|byte| u32::from(byte - b'0')).collect();
+    let (body, check) = digits.split_at(digits.len() - 1);
+    let sum: u32 = body
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(index, digit)| if index % 2 == 0 { digit * 3 } else { *digit })
+        .sum();
+    (10 - sum % 10) % 10 == check[0]
+}
+
+// Codes shaped like EAN-8, UPC-A or EAN-13 must carry a correct check digit
+// unless the caller marks them as internal. Other shapes are store codes and
+// are accepted as they are.
+pub(crate) fn validate_barcode(barcode: &str, internal: bool) -> AppResult<()> {
+    if internal || !is_retail_code(barcode) || has_valid_check_digit(barcode) {
+        return Ok(());
+    }
+    Err(AppError::validation(
+        "barcode_check_digit_invalid",
+        "barcode",
+        "Barcode check digit is wrong; mark it as an internal code to keep it",
+    ))
+}
+
+// Products and their selling units share one barcode space, so a scan can
+// only ever mean one thing. The error names which side holds the code.
+fn ensure_barcode_unused(
+    conn: &Connection,
+    barcode: &str,
+    product_id: Option<i64>,
+    unit_id: Option<i64>,
+) -> AppResult<()> {
+    let (on_product, on_unit): (bool, bool) = conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM products WHERE barcode = ?1 AND id IS NOT ?2),
+                EXISTS(SELECT 1 FROM product_units WHERE barcode = ?1 AND id IS NOT ?3)",
+        (barcode, product_id, unit_id),
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )?;
+    if on_product {
+        return Err(AppError::conflict(
+            "product_barcode_exists",
+            "Another product already uses this barcode",
+        )
+        .with_field("barcode"));
+    }
+    if on_unit {
+        return Err(AppError::conflict(
+            "unit_barcode_exists",
+            "A selling unit already uses this barcode",
+        )
+        .with_field("barcode"));
+    }
+    Ok(())
+}
+
+pub(crate) fn ensure_barcode_available(
+    conn: &Connection,
+    barcode: &str,
+    product_id: Option<i64>,
+) -> AppResult<()> {
+    ensure_barcode_unused(conn, barcode, product_id, None)
+}
+
+pub(crate) fn ensure_unit_barcode_available(
+    conn: &Connection,
+    barcode: &str,
+    unit_id: Option<i64>,
+) -> AppResult<()> {
+    ensure_barcode_unused(conn, barcode, None, unit_id)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum EmbeddedValueKind {
+    Weight,
+    Price,
+}
+
+// Scale labels: EAN-13 = prefix (2) + PLU + value + check digit. The value
+// takes whatever digits the PLU leaves free.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct EmbeddedBarcodeRule {
+    prefix: String,
+    plu_digits: usize,
+    kind: EmbeddedValueKind,
+    decimals: u32,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ResolveScanPayload {
+    barcode: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ScanResult {
+    product: ProductRecord,
+    embedded: bool,
+    quantity: Option<f64>,
+    line_price: Option<i64>,
+    unit: Option<ProductUnitRecord>,
+}
+
+impl EmbeddedBarcodeRule {
+    fn value_digits(&self) -> usize {
+        EAN13_PAYLOAD_DIGITS - self.prefix.len() - self.plu_digits
+    }
+
+    fn validate(&self) -> AppResult<()> {
+        let prefix_valid = self.prefix.len() == 2
+            && self.prefix.starts_with('2')
+            && self.prefix.bytes().all(|byte| byte.is_ascii_digit());
+        if !prefix_valid {
+            return Err(AppError::validation(
+                "embedded_rule_prefix_invalid",
+                "prefix",
+                "Prefix must be two digits from 20 to 29",
+            ));
+        }
+        if !(1..=6).contains(&self.plu_digits) {
+            return Err(AppError::validation(
+                "embedded_rule_plu_digits_invalid",
+                "pluDigits",
+                "PLU must use between 1 and 6 digits",
+            ));
+        }
+        let max_decimals = match self.kind {
+            EmbeddedValueKind::Weight => 3,
+            EmbeddedValueKind::Price => 2,
+        };
+        if self.decimals > max_decimals {
+            return Err(AppError::validation(
+                "embedded_rule_decimals_invalid",
+                "decimals",
+                format!("Use at most {} decimal places", max_decimals),
+            ));
+        }
+        Ok(())
+    }
+
+    // Splits a label into (PLU, value) when it carries this rule's prefix.
+    fn split<'a>(&self, code: &'a str) -> Option<(&'a str, f64)> {
+        let body = code.strip_prefix(self.prefix.as_str())?;
+        let (plu, rest) = body.split_at(self.plu_digits);
+        let raw: u64 = rest[..self.value_digits()].parse().ok()?;
+        Some((plu, raw as f64 / 10f64.powi(self.decimals as i32)))
+    }
+}
+
+fn load_rules(conn: &Connection) -> AppResult<Vec<EmbeddedBarcodeRule>> {
+    Ok(load_setting(conn, EMBEDDED_RULES_KEY)?.unwrap_or_default())
+}
+
+// The PLU on the label is matched against the product barcode as printed and
+// without its leading zeros, since stores key PLUs in both ways.
+fn find_by_plu(conn: &Connection, plu: &str) -> AppResult<Option<ProductRecord>> {
+    let trimmed = match plu.trim_start_matches('0') {
+        "" => "0",
+        value => value,
+    };
+    conn.query_row(
+        &format!(
+            "SELECT {}
+             FROM products
+             WHERE barcode IN (?1, ?2) AND archived_at IS NULL
+             ORDER BY barcode = ?1 DESC
+             LIMIT 1",
+            PRODUCT_COLUMNS
+        ),
+        (plu, trimmed),
+        parse_product_row,
+    )
+    .optional()
+    .map_err(AppError::from)
+}
+
+fn resolve_unit(conn: &Connection, code: &str) -> AppResult<Option<ScanResult>> {
+    let Some(unit) = find_unit_by_barcode(conn, code)? else {
+        return Ok(None);
+    };
+    let product = fetch_product_by_id(conn, unit.product_id)?;
+    if product.archived_at.is_some() {
+        return Ok(None);
+    }
+    Ok(Some(ScanResult {
+        product,
+        embedded: false,
+        quantity: None,
+        line_price: None,
+        unit: Some(unit),
+    }))
+}
+
+fn resolve_embedded(conn: &Connection, code: &str) -> AppResult<Option<ScanResult>> {
+    if code.len() != 13 || !has_valid_check_digit(code) {
+        return Ok(None);
+    }
+    let rules = load_rules(conn)?;
+    let Some((rule, plu, value)) = rules
+        .iter()
+        .find_map(|rule| rule.split(code).map(|(plu, value)| (rule, plu, value)))
+    else {
+        return Ok(None);
+    };
+    let Some(product) = find_by_plu(conn, plu)? else {
+        return Ok(None);
+    };
+    let (quantity, line_price) = match rule.kind {
+        EmbeddedValueKind::Weight => (Some(value), None),
+        EmbeddedValueKind::Price => (None, Some(value.floor() as i64)),
+    };
+    if quantity.is_some_and(|qty| qty <= 0.0) || line_price.is_some_and(|price| price <= 0) {
+        return Err(AppError::validation(
+            "embedded_value_invalid",
+            "barcode",
+            "The label carries no weight or price",
+        ));
+    }
+    Ok(Some(ScanResult {
+        product,
+        embedded: true,
+        quantity,
+        line_price,
+        unit: None,
+    }))
+}
+
+#[tauri::command]
+pub(crate) fn get_embedded_barcode_rules(
+    database: tauri::State<'_, Database>,
+) -> AppResult<Vec<EmbeddedBarcodeRule>> {
+    let conn = database.lock()?;
+    load_rules(&conn)
+}
+
+#[tauri::command]
+pub(crate) fn update_embedded_barcode_rules(
+    database: tauri::State<'_, Database>,
+    payload: Vec<EmbeddedBarcodeRule>,
+) -> AppResult<Vec<EmbeddedBarcodeRule>> {
+    let rules: Vec<EmbeddedBarcodeRule> = payload
+        .into_iter()
+        .map(|rule| EmbeddedBarcodeRule {
+            prefix: rule.prefix.trim().to_string(),
+            ..rule
+        })
+        .collect();
+    let mut prefixes = HashSet::new();
+    for rule in &rules {
+        rule.validate()?;
+        if !prefixes.insert(rule.prefix.as_str()) {
+            return Err(AppError::conflict(
+                "embedded_rule_prefix_duplicated",
+                "Each prefix can only have one rule",
+            )
+            .with_field("prefix"));
+        }
+    }
+    let conn = database.lock()?;
+    store_setting(&conn, EMBEDDED_RULES_KEY, &rules)?;
+    Ok(rules)
+}
+
+// An exact barcode match wins, then a selling unit's barcode; otherwise the
+// code is read as a scale label.
+#[tauri::command]
+pub(crate) fn resolve_scan(
+    database: tauri::State<'_, Database>,
+    payload: ResolveScanPayload,
+) -> AppResult<Option<ScanResult>> {
+    let Some(code) = normalize_barcode(Some(payload.barcode)) else {
+        return Ok(None);
+    };
+    let conn = database.lock()?;
+    crate::price_changes::apply_due_price_changes(&conn)?;
+    let exact = conn
+        .query_row(
+            &format!(
+                "SELECT {}
+                 FROM products
+                 WHERE barcode = ?1 AND archived_at IS NULL",
+                PRODUCT_COLUMNS
+            ),
+            [code.as_str()],
+            parse_product_row,
+        )
+        .optional()?;
+    if let Some(product) = exact {
+        return Ok(Some(ScanResult {
+            product,
+            embedded: false,
+            quantity: None,
+            line_price: None,
+            unit: None,
+        }));
+    }
+    if let Some(scan) = resolve_unit(&conn, &code)? {
+        return Ok(Some(scan));
+    }
+    resolve_embedded(&conn, &code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_digit_accepts_valid_retail_codes() {
+        for code in ["96385074", "036000291452", "4006381333931", "8934588012228"] {
+            assert!(has_valid_check_digit(code), "{}", code);
+            assert!(validate_barcode(code, false).is_ok(), "{}", code);
+        }
+    }
+
+    #[test]
+    fn check_digit_rejects_wrong_retail_codes() {
+        for code in ["96385075", "036000291453", "4006381333932"] {
+            assert!(!has_valid_check_digit(code), "{}", code);
+            assert_eq!(
+                validate_barcode(code, false).err().map(|err| err.code),
+                Some("barcode_check_digit_invalid"),
+                "{}",
+                code
+            );
+        }
+    }
+
+    #[test]
+    fn other_shapes_are_store_codes() {
+        for code in [
+            "1234567",
+            "12345678901",
+            "40063813339311",
+            "SP-001",
+            "4006381333A31",
+        ] {
+            assert!(!has_valid_check_digit(code), "{}", code);
+            assert!(validate_barcode(code, false).is_ok(), "{}", code);
+        }
+    }
+
+    #[test]
+    fn internal_codes_skip_the_check_digit() {
+        assert!(validate_barcode("4006381333932", true).is_ok());
+        assert!(validate_barcode("96385075", true).is_ok());
+    }
+
+    fn rule(
+        prefix: &str,
+        plu_digits: usize,
+        kind: EmbeddedValueKind,
+        decimals: u32,
+    ) -> EmbeddedBarcodeRule {
+        EmbeddedBarcodeRule {
+            prefix: prefix.into(),
+            plu_digits,
+            kind,
+            decimals,
+        }
+    }
+
+    // Completes a 12 digit label body with its check digit.
+    fn label(body: &str) -> String {
+        (0..10)
+            .map(|digit| format!("{}{}", body, digit))
+            .find(|code| has_valid_check_digit(code))
+            .unwrap()
+    }
+
+    fn scale_database(rules: &[EmbeddedBarcodeRule]) -> Connection {
+        let mut conn = Connection::open_in_memory().unwrap();
+        crate::initialize_schema(&mut conn).unwrap();
+        conn.execute_batch(
+            "INSERT INTO products (name, price, barcode) VALUES
+                ('Thịt heo', 120000, '123'),
+                ('Cá basa', 80000, '00456'),
+                ('Cá thu', 150000, '456'),
+                ('Bánh', 20000, '0789');",
+        )
+        .unwrap();
+        store_setting(&conn, EMBEDDED_RULES_KEY, &rules.to_vec()).unwrap();
+        conn
+    }
+
+    #[test]
+    fn split_reads_plu_and_value_by_width() {
+        let weight = rule("21", 5, EmbeddedValueKind::Weight, 3);
+        assert_eq!(weight.split("2100123012504"), Some(("00123", 1.25)));
+        assert_eq!(weight.split("2200123012504"), None);
+
+        let price = rule("22", 4, EmbeddedValueKind::Price, 0);
+        assert_eq!(price.value_digits(), 6);
+        assert_eq!(price.split("2207890120004"), Some(("0789", 12000.0)));
+
+        let cents = rule("23", 6, EmbeddedValueKind::Price, 2);
+        assert_eq!(cents.split("2300078912345"), Some(("000789", 12.34)));
+    }
+
+    #[test]
+    fn scale_labels_resolve_to_products() {
+        let conn = scale_database(&[
+            rule("21", 5, EmbeddedValueKind::Weight, 3),
+            rule("22", 4, EmbeddedValueKind::Price, 0),
+        ]);
+
+        // The PLU 00123 is keyed without its leading zeros.
+        let scan = resolve_embedded(&conn, &label("210012301250"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(scan.product.barcode.as_deref(), Some("123"));
+        assert!(scan.embedded);
+        assert_eq!(scan.quantity, Some(1.25));
+        assert_eq!(scan.line_price, None);
+
+        // A product keyed with the zeros wins over one keyed without.
+        let scan = resolve_embedded(&conn, &label("210045600500"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(scan.product.barcode.as_deref(), Some("00456"));
+        assert_eq!(scan.quantity, Some(0.5));
+
+        let scan = resolve_embedded(&conn, &label("220789012000"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(scan.product.barcode.as_deref(), Some("0789"));
+        assert_eq!(scan.line_price, Some(12000));
+        assert_eq!(scan.quantity, None);
+
+        // No rule for the prefix, an unknown PLU, or a bad check digit.
+        assert!(resolve_embedded(&conn, &label("230012301250"))
+            .unwrap()
+            .is_none());
+        assert!(resolve_embedded(&conn, &label("210099901250"))
+            .unwrap()
+            .is_none());
+        let mut broken = label("210012301250");
+        let last = broken.pop().unwrap();
+        broken.push(if last == '0' { '1' } else { '0' });
+        assert!(resolve_embedded(&conn, &broken).unwrap().is_none());
+    }
+
+    #[test]
+    fn scale_labels_without_a_value_are_rejected() {
+        let conn = scale_database(&[
+            rule("21", 5, EmbeddedValueKind::Weight, 3),
+            rule("22", 4, EmbeddedValueKind::Price, 0),
+        ]);
+        for body in ["210012300000", "220789000000"] {
+            assert_eq!(
+                resolve_embedded(&conn, &label(body))
+                    .err()
+                    .map(|err| err.code),
+                Some("embedded_value_invalid"),
+                "{}",
+                body
+            );
+        }
+    }
+}
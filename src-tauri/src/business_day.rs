@@ -0,0 +1,173 @@
+use crate::error::{AppError, AppResult};
+use crate::settings::{load_setting, store_setting};
+use crate::Database;
+use chrono::{DateTime, Duration, Utc};
+use chrono_tz::Tz;
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+
+const STORE_CLOCK_KEY: &str = "store_clock";
+const DEFAULT_TIMEZONE: &str = "Asia/Ho_Chi_Minh";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct StoreClock {
+    timezone: String,
+    cutover_hour: u32,
+}
+
+impl Default for StoreClock {
+    fn default() -> Self {
+        StoreClock {
+            timezone: DEFAULT_TIMEZONE.into(),
+            cutover_hour: 0,
+        }
+    }
+}
+
+pub(crate) struct StoreMoment {
+    pub timestamp: String,
+    pub business_date: String,
+}
+
+impl StoreMoment {
+    // The business date with the local time of day, so SQLite date functions
+    // applied to it land on the business day rather than the calendar day.
+    pub fn business_datetime(&self) -> String {
+        format!("{} {}", self.business_date, &self.timestamp[11..19])
+    }
+}
+
+impl StoreClock {
+    fn zone(&self) -> AppResult<Tz> {
+        self.timezone.parse::<Tz>().map_err(|_| {
+            AppError::validation(
+                "store_timezone_invalid",
+                "timezone",
+                "Timezone must be an IANA name such as Asia/Ho_Chi_Minh",
+            )
+        })
+    }
+
+    fn validate(&self) -> AppResult<()> {
+        self.zone()?;
+        if self.cutover_hour > 23 {
+            return Err(AppError::validation(
+                "business_day_cutover_invalid",
+                "cutoverHour",
+                "Cutover hour must be between 0 and 23",
+            ));
+        }
+        Ok(())
+    }
+
+    fn moment_at(&self, instant: DateTime<Utc>) -> AppResult<StoreMoment> {
+        let local = instant.with_timezone(&self.zone()?);
+        let business_date =
+            (local.naive_local() - Duration::hours(i64::from(self.cutover_hour))).date();
+        Ok(StoreMoment {
+            timestamp: local.format("%Y-%m-%d %H:%M:%S%:z").to_string(),
+            business_date: business_date.format("%Y-%m-%d").to_string(),
+        })
+    }
+}
+
+fn load_clock(conn: &Connection) -> AppResult<StoreClock> {
+    Ok(load_setting(conn, STORE_CLOCK_KEY)?.unwrap_or_default())
+}
+
+pub(crate) fn store_now(conn: &Connection) -> AppResult<StoreMoment> {
+    load_clock(conn)?.moment_at(Utc::now())
+}
+
+#[tauri::command]
+pub(crate) fn get_store_clock(database: tauri::State<'_, Database>) -> AppResult<StoreClock> {
+    let conn = database.lock()?;
+    load_clock(&conn)
+}
+
+#[tauri::command]
+pub(crate) fn update_store_clock(
+    database: tauri::State<'_, Database>,
+    payload: StoreClock,
+) -> AppResult<StoreClock> {
+    let clock = StoreClock {
+        timezone: payload.timezone.trim().to_string(),
+        ..payload
+    };
+    clock.validate()?;
+    let conn = database.lock()?;
+    store_setting(&conn, STORE_CLOCK_KEY, &clock)?;
+    Ok(clock)
+}
+
+#[tauri::command]
+pub(crate) fn current_business_date(database: tauri::State<'_, Database>) -> AppResult<String> {
+    let conn = database.lock()?;
+    Ok(store_now(&conn)?.business_date)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn clock(cutover_hour: u32) -> StoreClock {
+        StoreClock {
+            cutover_hour,
+            ..StoreClock::default()
+        }
+    }
+
+    // Hanoi is UTC+7, so 17:00 UTC is local midnight.
+    fn moment(clock: &StoreClock, utc: (i32, u32, u32, u32, u32)) -> StoreMoment {
+        let (year, month, day, hour, minute) = utc;
+        let instant = Utc
+            .with_ymd_and_hms(year, month, day, hour, minute, 0)
+            .unwrap();
+        clock.moment_at(instant).unwrap()
+    }
+
+    #[test]
+    fn midnight_cutover_follows_the_local_calendar() {
+        let clock = clock(0);
+        let before = moment(&clock, (2026, 1, 5, 16, 59));
+        assert_eq!(before.timestamp, "2026-01-05 23:59:00+07:00");
+        assert_eq!(before.business_date, "2026-01-05");
+        let after = moment(&clock, (2026, 1, 5, 17, 0));
+        assert_eq!(after.timestamp, "2026-01-06 00:00:00+07:00");
+        assert_eq!(after.business_date, "2026-01-06");
+    }
+
+    #[test]
+    fn late_cutover_keeps_the_night_on_the_previous_day() {
+        let clock = clock(4);
+        let after_midnight = moment(&clock, (2026, 1, 5, 20, 30));
+        assert_eq!(after_midnight.timestamp, "2026-01-06 03:30:00+07:00");
+        assert_eq!(after_midnight.business_date, "2026-01-05");
+        assert_eq!(after_midnight.business_datetime(), "2026-01-05 03:30:00");
+
+        let at_cutover = moment(&clock, (2026, 1, 5, 21, 0));
+        assert_eq!(at_cutover.business_date, "2026-01-06");
+        // The night of New Year's Eve still belongs to the old year.
+        let new_year = moment(&clock, (2025, 12, 31, 18, 0));
+        assert_eq!(new_year.business_date, "2025-12-31");
+    }
+
+    #[test]
+    fn clock_settings_are_validated() {
+        assert!(clock(23).validate().is_ok());
+        assert_eq!(
+            clock(24).validate().err().map(|err| err.code),
+            Some("business_day_cutover_invalid")
+        );
+        let unknown = StoreClock {
+            timezone: "Asia/Saigon Time".into(),
+            cutover_hour: 0,
+        };
+        assert_eq!(
+            unknown.validate().err().map(|err| err.code),
+            Some("store_timezone_invalid")
+        );
+    }
+}
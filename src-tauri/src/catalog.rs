@@ -0,0 +1,657 @@
+use crate::barcodes::{ensure_barcode_available, validate_barcode};
+use crate::error::{AppError, AppResult};
+use crate::price_changes::{record_price_change, PriceChangeSource};
+use crate::spreadsheet::{normalize_file_path, read_table, write_table, Cell, SheetFormat};
+use crate::{bool_to_sql, normalize_barcode, Database};
+use rusqlite::{Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+const CATALOG_COLUMNS: [&str; 6] = [
+    "name",
+    "price",
+    "barcode",
+    "visible",
+    "quick_display",
+    "display_order",
+];
+
+// Read on import but never exported: marks codes on the row as store codes
+// whose check digit is not verified.
+const IMPORT_ONLY_COLUMNS: [&str; 1] = ["internal_barcode"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum ImportMode {
+    CreateOnly,
+    UpdateByBarcode,
+    Upsert,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum ImportAction {
+    Create,
+    Update,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ImportProductsPayload {
+    path: String,
+    mode: ImportMode,
+    dry_run: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ExportProductsPayload {
+    path: String,
+    format: SheetFormat,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ImportRowError {
+    row: usize,
+    #[serde(flatten)]
+    error: AppError,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ImportRowPreview {
+    row: usize,
+    action: ImportAction,
+    product_id: Option<i64>,
+    name: String,
+    price: i64,
+    barcode: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ImportReport {
+    mode: ImportMode,
+    dry_run: bool,
+    applied: bool,
+    total_rows: usize,
+    created: usize,
+    updated: usize,
+    rows: Vec<ImportRowPreview>,
+    errors: Vec<ImportRowError>,
+}
+
+struct ImportRow {
+    row: usize,
+    name: String,
+    price: i64,
+    barcode: Option<String>,
+    internal_barcode: bool,
+    visible: Option<bool>,
+    quick_display: Option<bool>,
+    display_order: Option<i64>,
+}
+
+struct ColumnMap {
+    indexes: HashMap<&'static str, usize>,
+}
+
+impl ColumnMap {
+    fn from_header(header: &[String]) -> AppResult<ColumnMap> {
+        let mut indexes = HashMap::new();
+        for (index, title) in header.iter().enumerate() {
+            let key = title.trim().to_ascii_lowercase().replace(' ', "_");
+            if let Some(column) = CATALOG_COLUMNS
+                .iter()
+                .chain(IMPORT_ONLY_COLUMNS.iter())
+                .find(|column| **column == key)
+            {
+                indexes.entry(*column).or_insert(index);
+            }
+        }
+        for required in ["name", "price"] {
+            if !indexes.contains_key(required) {
+                return Err(AppError::validation(
+                    "import_column_missing",
+                    required,
+                    format!("The file needs a \"{}\" column", required),
+                ));
+            }
+        }
+        Ok(ColumnMap { indexes })
+    }
+
+    fn get<'a>(&self, cells: &'a [String], column: &str) -> &'a str {
+        self.indexes
+            .get(column)
+            .and_then(|index| cells.get(*index))
+            .map(|value| value.trim())
+            .unwrap_or("")
+    }
+}
+
+fn parse_flag(value: &str, column: &'static str) -> AppResult<Option<bool>> {
+    match value.to_lowercase().as_str() {
+        "" => Ok(None),
+        "1" | "true" | "yes" | "x" | "có" | "co" => Ok(Some(true)),
+        "0" | "false" | "no" | "không" | "khong" => Ok(Some(false)),
+        _ => Err(AppError::validation(
+            "import_flag_invalid",
+            column,
+            "Use 1/0, true/false or có/không",
+        )),
+    }
+}
+
+fn parse_row(columns: &ColumnMap, row: usize, cells: &[String]) -> AppResult<ImportRow> {
+    let name = columns.get(cells, "name");
+    if name.is_empty() {
+        return Err(AppError::validation(
+            "product_name_required",
+            "name",
+            "Product name is required",
+        ));
+    }
+    let price = columns
+        .get(cells, "price")
+        .parse::<i64>()
+        .ok()
+        .filter(|value| *value >= 0)
+        .ok_or_else(|| {
+            AppError::validation(
+                "import_price_invalid",
+                "price",
+                "Price must be a whole number of at least 0",
+            )
+        })?;
+    let display_order = match columns.get(cells, "display_order") {
+        "" => None,
+        value => Some(value.parse::<i64>().map_err(|_| {
+            AppError::validation(
+                "import_display_order_invalid",
+                "display_order",
+                "Display order must be a whole number",
+            )
+        })?),
+    };
+    Ok(ImportRow {
+        row,
+        name: name.to_string(),
+        price,
+        barcode: normalize_barcode(Some(columns.get(cells, "barcode").to_string())),
+        internal_barcode: parse_flag(columns.get(cells, "internal_barcode"), "internal_barcode")?
+            .unwrap_or(false),
+        visible: parse_flag(columns.get(cells, "visible"), "visible")?,
+        quick_display: parse_flag(columns.get(cells, "quick_display"), "quick_display")?,
+        display_order,
+    })
+}
+
+// Returns the product holding the barcode and whether it is archived.
+fn find_by_barcode(conn: &Connection, barcode: &str) -> AppResult<Option<(i64, bool)>> {
+    conn.query_row(
+        "SELECT id, archived_at IS NOT NULL
+         FROM products
+         WHERE barcode = ?1
+         ORDER BY id ASC
+         LIMIT 1",
+        [barcode],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )
+    .optional()
+    .map_err(AppError::from)
+}
+
+fn resolve_action(
+    conn: &Connection,
+    mode: ImportMode,
+    row: &ImportRow,
+) -> AppResult<(ImportAction, Option<i64>)> {
+    let existing = match row.barcode.as_deref() {
+        Some(barcode) => find_by_barcode(conn, barcode)?,
+        None => None,
+    };
+    match (mode, existing) {
+        // An archived product keeps its barcode but is not edited by imports;
+        // it has to be restored first.
+        (_, Some((_, true))) => Err(AppError::conflict(
+            "product_archived",
+            "This barcode belongs to an archived product",
+        )
+        .with_field("barcode")),
+        (ImportMode::CreateOnly, Some(_)) => Err(AppError::conflict(
+            "product_barcode_exists",
+            "A product with this barcode already exists",
+        )
+        .with_field("barcode")),
+        (ImportMode::UpdateByBarcode, None) if row.barcode.is_none() => Err(AppError::validation(
+            "import_barcode_required",
+            "barcode",
+            "Updating by barcode needs a barcode on every row",
+        )),
+        (ImportMode::UpdateByBarcode, None) => Err(AppError::not_found(
+            "product_not_found",
+            "No product has this barcode",
+        )
+        .with_field("barcode")),
+        (_, Some((id, _))) => Ok((ImportAction::Update, Some(id))),
+        (_, None) => Ok((ImportAction::Create, None)),
+    }
+}
+
+fn apply_row(
+    conn: &Connection,
+    row: &ImportRow,
+    action: ImportAction,
+    product_id: Option<i64>,
+    next_display_order: &mut i64,
+) -> AppResult<i64> {
+    match (action, product_id) {
+        (ImportAction::Update, Some(id)) => {
+            let old_price: i64 =
+                conn.query_row("SELECT price FROM products WHERE id = ?1", [id], |row| {
+                    row.get(0)
+                })?;
+            conn.execute(
+                "UPDATE products
+                 SET name = ?1,
+                     price = ?2,
+                     visible = COALESCE(?3, visible),
+                     quick_display = COALESCE(?4, quick_display),
+                     display_order = COALESCE(?5, display_order)
+                 WHERE id = ?6",
+                (
+                    row.name.as_str(),
+                    row.price,
+                    row.visible.map(bool_to_sql),
+                    row.quick_display.map(bool_to_sql),
+                    row.display_order,
+                    id,
+                ),
+            )?;
+            record_price_change(
+                conn,
+                id,
+                old_price,
+                row.price,
+                PriceChangeSource::Import,
+                None,
+            )?;
+            Ok(id)
+        }
+        _ => {
+            let display_order = row.display_order.unwrap_or(*next_display_order);
+            *next_display_order = display_order.max(*next_display_order) + 1;
+            conn.execute(
+                "INSERT INTO products (name, price, barcode, visible, quick_display, display_order)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                (
+                    row.name.as_str(),
+                    row.price,
+                    row.barcode.as_deref(),
+                    bool_to_sql(row.visible.unwrap_or(true)),
+                    bool_to_sql(row.quick_display.unwrap_or(false)),
+                    display_order,
+                ),
+            )?;
+            Ok(conn.last_insert_rowid())
+        }
+    }
+}
+
+// The whole file is validated and written in one transaction. A dry run, or a
+// file with any bad row, is rolled back so the preview matches what a real
+// import would do.
+#[tauri::command]
+pub(crate) fn import_products(
+    database: tauri::State<'_, Database>,
+    payload: ImportProductsPayload,
+) -> AppResult<ImportReport> {
+    let ImportProductsPayload {
+        path,
+        mode,
+        dry_run,
+    } = payload;
+    let path = normalize_file_path(&path)?;
+    let table = read_table(path)?;
+    let mut conn = database.lock()?;
+    import_table(&mut conn, table, mode, dry_run)
+}
+
+fn import_table(
+    conn: &mut Connection,
+    table: Vec<Vec<String>>,
+    mode: ImportMode,
+    dry_run: bool,
+) -> AppResult<ImportReport> {
+    let mut lines = table
+        .into_iter()
+        .enumerate()
+        .filter(|(_, cells)| cells.iter().any(|value| !value.trim().is_empty()));
+    let Some((_, header)) = lines.next() else {
+        return Err(AppError::validation(
+            "import_file_empty",
+            "path",
+            "The file has no rows",
+        ));
+    };
+    let columns = ColumnMap::from_header(&header)?;
+
+    let tx = conn.transaction()?;
+    let mut next_display_order: i64 = tx.query_row(
+        "SELECT COALESCE(MAX(display_order), 0) + 1 FROM products",
+        [],
+        |row| row.get(0),
+    )?;
+    let mut seen_barcodes: HashMap<String, usize> = HashMap::new();
+    let mut report = ImportReport {
+        mode,
+        dry_run,
+        applied: false,
+        total_rows: 0,
+        created: 0,
+        updated: 0,
+        rows: Vec::new(),
+        errors: Vec::new(),
+    };
+    for (index, cells) in lines {
+        let row_number = index + 1;
+        report.total_rows += 1;
+        let outcome = parse_row(&columns, row_number, &cells).and_then(|row| {
+            if let Some(barcode) = row.barcode.as_deref() {
+                if let Some(first) = seen_barcodes.insert(barcode.to_string(), row_number) {
+                    return Err(AppError::conflict(
+                        "import_barcode_duplicated",
+                        format!("Barcode is already used on row {}", first),
+                    )
+                    .with_field("barcode"));
+                }
+            }
+            let (action, product_id) = resolve_action(&tx, mode, &row)?;
+            if let Some(barcode) = row.barcode.as_deref() {
+                // An update matched the product's own code, which was checked
+                // when it was first stored.
+                if action == ImportAction::Create {
+                    validate_barcode(barcode, row.internal_barcode)?;
+                }
+                ensure_barcode_available(&tx, barcode, product_id)?;
+            }
+            let id = apply_row(&tx, &row, action, product_id, &mut next_display_order)?;
+            Ok(ImportRowPreview {
+                row: row.row,
+                action,
+                product_id: product_id.or(Some(id)),
+                name: row.name,
+                price: row.price,
+                barcode: row.barcode,
+            })
+        });
+        match outcome {
+            Ok(preview) => {
+                match preview.action {
+                    ImportAction::Create => report.created += 1,
+                    ImportAction::Update => report.updated += 1,
+                }
+                report.rows.push(preview);
+            }
+            Err(error) => report.errors.push(ImportRowError {
+                row: row_number,
+                error,
+            }),
+        }
+    }
+    if dry_run || !report.errors.is_empty() {
+        tx.rollback()?;
+    } else {
+        tx.commit()?;
+        report.applied = true;
+    }
+    Ok(report)
+}
+
+#[tauri::command]
+pub(crate) fn export_products(
+    database: tauri::State<'_, Database>,
+    payload: ExportProductsPayload,
+) -> AppResult<usize> {
+    let path = normalize_file_path(&payload.path)?;
+    let conn = database.lock()?;
+    let mut statement = conn.prepare(
+        "SELECT name, price, barcode, visible, quick_display, display_order
+         FROM products
+         WHERE archived_at IS NULL
+         ORDER BY display_order ASC, id ASC",
+    )?;
+    let rows = statement
+        .query_map([], |row| {
+            Ok(vec![
+                Cell::Text(row.get(0)?),
+                Cell::Integer(row.get(1)?),
+                Cell::from(row.get::<_, Option<String>>(2)?),
+                Cell::Integer(row.get(3)?),
+                Cell::Integer(row.get(4)?),
+                Cell::Integer(row.get(5)?),
+            ])
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    write_table(path, payload.format, "Sản phẩm", &CATALOG_COLUMNS, &rows)?;
+    Ok(rows.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn catalog() -> Connection {
+        let mut conn = Connection::open_in_memory().unwrap();
+        crate::initialize_schema(&mut conn).unwrap();
+        conn.execute_batch(
+            "INSERT INTO products (id, name, price, barcode, display_order)
+             VALUES (1, 'Rau muống', 15000, 'SP001', 1),
+                    (2, 'Rau cải', 12000, 'SP002', 2);
+             UPDATE products SET archived_at = '2026-01-01 08:00:00' WHERE id = 2;",
+        )
+        .unwrap();
+        conn
+    }
+
+    fn table(rows: &[&[&str]]) -> Vec<Vec<String>> {
+        rows.iter()
+            .map(|cells| cells.iter().map(|cell| cell.to_string()).collect())
+            .collect()
+    }
+
+    fn import(
+        conn: &mut Connection,
+        mode: ImportMode,
+        dry_run: bool,
+        rows: &[&[&str]],
+    ) -> ImportReport {
+        import_table(conn, table(rows), mode, dry_run).unwrap()
+    }
+
+    fn row_errors(report: &ImportReport) -> Vec<(usize, &'static str)> {
+        report
+            .errors
+            .iter()
+            .map(|error| (error.row, error.error.code))
+            .collect()
+    }
+
+    fn product_count(conn: &Connection) -> i64 {
+        conn.query_row("SELECT COUNT(*) FROM products", [], |row| row.get(0))
+            .unwrap()
+    }
+
+    #[test]
+    fn rows_are_parsed_against_the_header() {
+        let mut conn = catalog();
+        let report = import(
+            &mut conn,
+            ImportMode::Upsert,
+            false,
+            &[
+                &["", ""],
+                &["Price", " NAME ", "Quick Display", "Display Order"],
+                &["9000", "Cải thảo", "có", "7"],
+                &["5000", "Hành lá", "", ""],
+            ],
+        );
+        assert!(report.applied);
+        assert_eq!((report.total_rows, report.created), (2, 2));
+        // Row numbers count blank lines so they match the spreadsheet.
+        assert_eq!(report.rows[0].row, 3);
+        let (quick, order): (i64, i64) = conn
+            .query_row(
+                "SELECT quick_display, display_order FROM products WHERE name = 'Cải thảo'",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!((quick, order), (1, 7));
+        let order: i64 = conn
+            .query_row(
+                "SELECT display_order FROM products WHERE name = 'Hành lá'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(order, 8);
+
+        let missing = import_table(
+            &mut conn,
+            table(&[&["name"], &["Tỏi"]]),
+            ImportMode::Upsert,
+            false,
+        );
+        let missing = missing.err().unwrap();
+        assert_eq!(missing.code, "import_column_missing");
+        assert_eq!(missing.field.as_deref(), Some("price"));
+        let empty = import_table(&mut conn, table(&[&["", " "]]), ImportMode::Upsert, false);
+        assert_eq!(empty.err().map(|err| err.code), Some("import_file_empty"));
+    }
+
+    #[test]
+    fn any_bad_row_rolls_back_the_whole_file() {
+        let mut conn = catalog();
+        let report = import(
+            &mut conn,
+            ImportMode::Upsert,
+            false,
+            &[
+                &["name", "price", "visible", "display_order"],
+                &["Tỏi", "8000", "", ""],
+                &["", "8000", "", ""],
+                &["Gừng", "-1", "", ""],
+                &["Ớt", "abc", "", ""],
+                &["Chanh", "3000", "maybe", ""],
+                &["Sả", "3000", "", "2.5"],
+            ],
+        );
+        assert_eq!(
+            row_errors(&report),
+            vec![
+                (3, "product_name_required"),
+                (4, "import_price_invalid"),
+                (5, "import_price_invalid"),
+                (6, "import_flag_invalid"),
+                (7, "import_display_order_invalid"),
+            ]
+        );
+        assert_eq!(report.created, 1);
+        assert!(!report.applied);
+        assert_eq!(product_count(&conn), 2);
+    }
+
+    #[test]
+    fn dry_runs_preview_without_writing() {
+        let mut conn = catalog();
+        let report = import(
+            &mut conn,
+            ImportMode::Upsert,
+            true,
+            &[
+                &["name", "price", "barcode"],
+                &["Rau muống bó", "16000", "SP001"],
+                &["Tỏi", "8000", "SP003"],
+            ],
+        );
+        assert!(report.errors.is_empty());
+        assert!(!report.applied);
+        assert_eq!((report.created, report.updated), (1, 1));
+        assert_eq!(report.rows[0].product_id, Some(1));
+        assert_eq!(product_count(&conn), 2);
+        let price: i64 = conn
+            .query_row("SELECT price FROM products WHERE id = 1", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(price, 15000);
+    }
+
+    #[test]
+    fn modes_decide_between_create_and_update() {
+        let rows: &[&[&str]] = &[
+            &["name", "price", "barcode"],
+            &["Rau muống bó", "16000", "SP001"],
+            &["Tỏi", "8000", "SP003"],
+            &["Gừng", "9000", ""],
+        ];
+
+        let mut conn = catalog();
+        let report = import(&mut conn, ImportMode::CreateOnly, false, rows);
+        assert_eq!(row_errors(&report), vec![(2, "product_barcode_exists")]);
+
+        let report = import(&mut conn, ImportMode::UpdateByBarcode, false, rows);
+        assert_eq!(
+            row_errors(&report),
+            vec![(3, "product_not_found"), (4, "import_barcode_required")]
+        );
+
+        let report = import(&mut conn, ImportMode::Upsert, false, rows);
+        assert!(report.applied);
+        assert_eq!((report.created, report.updated), (2, 1));
+        let (name, price): (String, i64) = conn
+            .query_row("SELECT name, price FROM products WHERE id = 1", [], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })
+            .unwrap();
+        assert_eq!((name.as_str(), price), ("Rau muống bó", 16000));
+        let changes: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM price_changes WHERE product_id = 1",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(changes, 1);
+    }
+
+    #[test]
+    fn duplicate_and_archived_barcodes_are_row_errors() {
+        let mut conn = catalog();
+        let report = import(
+            &mut conn,
+            ImportMode::Upsert,
+            false,
+            &[
+                &["name", "price", "barcode"],
+                &["Tỏi", "8000", "SP003"],
+                &["Tỏi tím", "9000", "SP003"],
+                &["Rau cải", "13000", "SP002"],
+            ],
+        );
+        assert_eq!(
+            row_errors(&report),
+            vec![(3, "import_barcode_duplicated"), (4, "product_archived")]
+        );
+        assert!(!report.applied);
+        let price: i64 = conn
+            .query_row("SELECT price FROM products WHERE id = 2", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(price, 12000);
+    }
+}
@@ -0,0 +1,285 @@
+use crate::error::{AppError, AppResult};
+use crate::{parse_product_row, Database, ProductRecord, PRODUCT_COLUMNS};
+use rusqlite::{Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct CategoryRecord {
+    id: i64,
+    name: String,
+    parent_id: Option<i64>,
+    display_order: i64,
+    color: Option<String>,
+    product_count: i64,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct CreateCategoryPayload {
+    name: String,
+    parent_id: Option<i64>,
+    display_order: Option<i64>,
+    color: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct UpdateCategoryPayload {
+    id: i64,
+    name: String,
+    parent_id: Option<i64>,
+    display_order: i64,
+    color: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct DeleteCategoryPayload {
+    id: i64,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ListProductsByCategoryPayload {
+    category_id: Option<i64>,
+    include_subcategories: Option<bool>,
+}
+
+fn parse_category_row(row: &rusqlite::Row<'_>) -> Result<CategoryRecord, rusqlite::Error> {
+    Ok(CategoryRecord {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        parent_id: row.get(2)?,
+        display_order: row.get(3)?,
+        color: row.get(4)?,
+        product_count: row.get(5)?,
+    })
+}
+
+fn fetch_category(conn: &Connection, id: i64) -> AppResult<CategoryRecord> {
+    conn.query_row(
+        "SELECT c.id, c.name, c.parent_id, c.display_order, c.color,
+                (SELECT COUNT(*) FROM products p
+                 WHERE p.category_id = c.id AND p.archived_at IS NULL)
+         FROM categories c
+         WHERE c.id = ?1",
+        [id],
+        parse_category_row,
+    )
+    .optional()?
+    .ok_or_else(|| AppError::not_found("category_not_found", "Category not found"))
+}
+
+pub(crate) fn ensure_category_exists(conn: &Connection, id: i64) -> AppResult<()> {
+    fetch_category(conn, id)
+        .map(|_| ())
+        .map_err(|err| err.with_field("categoryId"))
+}
+
+fn clean_name(name: &str) -> AppResult<String> {
+    let cleaned = name.trim();
+    if cleaned.is_empty() {
+        return Err(AppError::validation(
+            "category_name_required",
+            "name",
+            "Category name is required",
+        ));
+    }
+    Ok(cleaned.to_string())
+}
+
+fn clean_color(color: Option<String>) -> AppResult<Option<String>> {
+    let Some(value) = color
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+    else {
+        return Ok(None);
+    };
+    let valid = value.len() == 7
+        && value.starts_with('#')
+        && value[1..].bytes().all(|byte| byte.is_ascii_hexdigit());
+    if !valid {
+        return Err(AppError::validation(
+            "category_color_invalid",
+            "color",
+            "Color must look like #1E88E5",
+        ));
+    }
+    Ok(Some(value.to_ascii_uppercase()))
+}
+
+// Categories nest one level: a parent must itself be top level, and a category
+// that already has children cannot be moved under another one.
+fn validate_parent(conn: &Connection, id: Option<i64>, parent_id: Option<i64>) -> AppResult<()> {
+    let Some(parent_id) = parent_id else {
+        return Ok(());
+    };
+    if id == Some(parent_id) {
+        return Err(AppError::validation(
+            "category_parent_invalid",
+            "parentId",
+            "A category cannot be its own parent",
+        ));
+    }
+    let parent = fetch_category(conn, parent_id).map_err(|err| err.with_field("parentId"))?;
+    let has_children = match id {
+        Some(id) => conn.query_row(
+            "SELECT EXISTS(SELECT 1 FROM categories WHERE parent_id = ?1)",
+            [id],
+            |row| row.get(0),
+        )?,
+        None => false,
+    };
+    if parent.parent_id.is_some() || has_children {
+        return Err(AppError::validation(
+            "category_nesting_too_deep",
+            "parentId",
+            "Categories can only be nested one level deep",
+        ));
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub(crate) fn list_categories(
+    database: tauri::State<'_, Database>,
+) -> AppResult<Vec<CategoryRecord>> {
+    let conn = database.lock()?;
+    let mut statement = conn.prepare(
+        "SELECT c.id, c.name, c.parent_id, c.display_order, c.color,
+                (SELECT COUNT(*) FROM products p
+                 WHERE p.category_id = c.id AND p.archived_at IS NULL)
+         FROM categories c
+         LEFT JOIN categories parent ON parent.id = c.parent_id
+         ORDER BY COALESCE(parent.display_order, c.display_order) ASC,
+                  COALESCE(c.parent_id, c.id) ASC,
+                  c.parent_id IS NOT NULL ASC,
+                  c.display_order ASC,
+                  c.id ASC",
+    )?;
+    let records = statement
+        .query_map([], parse_category_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(records)
+}
+
+#[tauri::command]
+pub(crate) fn create_category(
+    database: tauri::State<'_, Database>,
+    payload: CreateCategoryPayload,
+) -> AppResult<CategoryRecord> {
+    let name = clean_name(&payload.name)?;
+    let color = clean_color(payload.color)?;
+    let conn = database.lock()?;
+    validate_parent(&conn, None, payload.parent_id)?;
+    let display_order = match payload.display_order {
+        Some(value) => value,
+        None => conn.query_row(
+            "SELECT COALESCE(MAX(display_order), 0) + 1 FROM categories WHERE parent_id IS ?1",
+            [payload.parent_id],
+            |row| row.get(0),
+        )?,
+    };
+    conn.execute(
+        "INSERT INTO categories (name, parent_id, display_order, color)
+         VALUES (?1, ?2, ?3, ?4)",
+        (
+            name.as_str(),
+            payload.parent_id,
+            display_order,
+            color.as_deref(),
+        ),
+    )?;
+    fetch_category(&conn, conn.last_insert_rowid())
+}
+
+#[tauri::command]
+pub(crate) fn update_category(
+    database: tauri::State<'_, Database>,
+    payload: UpdateCategoryPayload,
+) -> AppResult<CategoryRecord> {
+    let name = clean_name(&payload.name)?;
+    let color = clean_color(payload.color)?;
+    let conn = database.lock()?;
+    fetch_category(&conn, payload.id)?;
+    validate_parent(&conn, Some(payload.id), payload.parent_id)?;
+    conn.execute(
+        "UPDATE categories
+         SET name = ?1,
+             parent_id = ?2,
+             display_order = ?3,
+             color = ?4
+         WHERE id = ?5",
+        (
+            name.as_str(),
+            payload.parent_id,
+            payload.display_order,
+            color.as_deref(),
+            payload.id,
+        ),
+    )?;
+    fetch_category(&conn, payload.id)
+}
+
+// Products in a deleted category become uncategorized; categories that still
+// have children must be emptied first.
+#[tauri::command]
+pub(crate) fn delete_category(
+    database: tauri::State<'_, Database>,
+    payload: DeleteCategoryPayload,
+) -> AppResult<()> {
+    let mut conn = database.lock()?;
+    let tx = conn.transaction()?;
+    fetch_category(&tx, payload.id)?;
+    let has_children: bool = tx.query_row(
+        "SELECT EXISTS(SELECT 1 FROM categories WHERE parent_id = ?1)",
+        [payload.id],
+        |row| row.get(0),
+    )?;
+    if has_children {
+        return Err(AppError::conflict(
+            "category_has_children",
+            "Move or delete the subcategories first",
+        ));
+    }
+    tx.execute(
+        "UPDATE products SET category_id = NULL WHERE category_id = ?1",
+        [payload.id],
+    )?;
+    tx.execute("DELETE FROM categories WHERE id = ?1", [payload.id])?;
+    tx.commit()?;
+    Ok(())
+}
+
+// Without a category id this lists uncategorized products. A top-level
+// category includes its subcategories unless the caller opts out.
+#[tauri::command]
+pub(crate) fn list_products_by_category(
+    database: tauri::State<'_, Database>,
+    payload: ListProductsByCategoryPayload,
+) -> AppResult<Vec<ProductRecord>> {
+    let conn = database.lock()?;
+    crate::price_changes::apply_due_price_changes(&conn)?;
+    if let Some(category_id) = payload.category_id {
+        fetch_category(&conn, category_id).map_err(|err| err.with_field("categoryId"))?;
+    }
+    let include_subcategories = payload.include_subcategories.unwrap_or(true);
+    let mut statement = conn.prepare(&format!(
+        "SELECT {}
+         FROM products
+         WHERE archived_at IS NULL
+           AND (category_id IS ?1
+                OR (?2 AND ?1 IS NOT NULL
+                    AND category_id IN (SELECT id FROM categories WHERE parent_id = ?1)))
+         ORDER BY display_order ASC, id ASC",
+        PRODUCT_COLUMNS
+    ))?;
+    let records = statement
+        .query_map(
+            (payload.category_id, include_subcategories),
+            parse_product_row,
+        )?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(records)
+}
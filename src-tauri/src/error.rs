@@ -0,0 +1,104 @@
+use rusqlite::ErrorCode;
+use serde::Serialize;
+use std::fmt;
+
+pub(crate) type AppResult<T> = Result<T, AppError>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum ErrorCategory {
+    Validation,
+    NotFound,
+    Conflict,
+    Storage,
+}
+
+// Serialized to the frontend as `{ code, category, field, messageKey, message }`.
+// `code` is stable across releases, `messageKey` is what the UI translates and
+// `message` is an English fallback meant for logs.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct AppError {
+    pub code: &'static str,
+    pub category: ErrorCategory,
+    pub field: Option<String>,
+    pub message_key: String,
+    pub message: String,
+}
+
+impl AppError {
+    pub fn new(category: ErrorCategory, code: &'static str, message: impl Into<String>) -> Self {
+        AppError {
+            code,
+            category,
+            field: None,
+            message_key: format!("errors.{}", code),
+            message: message.into(),
+        }
+    }
+
+    pub fn validation(
+        code: &'static str,
+        field: impl Into<String>,
+        message: impl Into<String>,
+    ) -> Self {
+        AppError::new(ErrorCategory::Validation, code, message).with_field(field)
+    }
+
+    pub fn not_found(code: &'static str, message: &str) -> Self {
+        AppError::new(ErrorCategory::NotFound, code, message)
+    }
+
+    pub fn conflict(code: &'static str, message: impl Into<String>) -> Self {
+        AppError::new(ErrorCategory::Conflict, code, message)
+    }
+
+    pub fn storage(code: &'static str, message: impl Into<String>) -> Self {
+        AppError::new(ErrorCategory::Storage, code, message)
+    }
+
+    pub fn with_field(mut self, field: impl Into<String>) -> Self {
+        self.field = Some(field.into());
+        self
+    }
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.code, self.message)
+    }
+}
+
+impl std::error::Error for AppError {}
+
+impl From<rusqlite::Error> for AppError {
+    fn from(err: rusqlite::Error) -> Self {
+        match &err {
+            rusqlite::Error::QueryReturnedNoRows => {
+                AppError::not_found("record_not_found", "Record not found")
+            }
+            rusqlite::Error::SqliteFailure(failure, _) => match failure.code {
+                ErrorCode::ConstraintViolation => {
+                    AppError::conflict("constraint_violation", err.to_string())
+                }
+                ErrorCode::DatabaseBusy | ErrorCode::DatabaseLocked => {
+                    AppError::storage("database_locked", err.to_string())
+                }
+                _ => AppError::storage("database_error", err.to_string()),
+            },
+            _ => AppError::storage("database_error", err.to_string()),
+        }
+    }
+}
+
+impl From<std::io::Error> for AppError {
+    fn from(err: std::io::Error) -> Self {
+        AppError::storage("io_error", err.to_string())
+    }
+}
+
+impl From<tauri::Error> for AppError {
+    fn from(err: tauri::Error) -> Self {
+        AppError::storage("app_path_unavailable", err.to_string())
+    }
+}
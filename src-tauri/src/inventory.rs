@@ -0,0 +1,588 @@
+use crate::business_day::{store_now, StoreMoment};
+use crate::error::{AppError, AppResult};
+use crate::pricing::NormalizedPaymentItem;
+use crate::settings::{load_setting, store_setting};
+use crate::Database;
+use rusqlite::{params_from_iter, types::Value, Connection};
+use serde::{Deserialize, Serialize};
+
+const STOCK_POLICY_KEY: &str = "stock_policy";
+const QUANTITY_SCALE: f64 = 1000.0;
+const DEFAULT_MOVEMENT_LIMIT: i64 = 100;
+const MAX_MOVEMENT_LIMIT: i64 = 500;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum NegativeStockPolicy {
+    #[default]
+    Allow,
+    Warn,
+    Block,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct StockPolicy {
+    negative_stock: NegativeStockPolicy,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum MovementReason {
+    Sale,
+    Refund,
+    Void,
+    Adjustment,
+    Receipt,
+    Stocktake,
+}
+
+impl MovementReason {
+    fn as_str(self) -> &'static str {
+        match self {
+            MovementReason::Sale => "sale",
+            MovementReason::Refund => "refund",
+            MovementReason::Void => "void",
+            MovementReason::Adjustment => "adjustment",
+            MovementReason::Receipt => "receipt",
+            MovementReason::Stocktake => "stocktake",
+        }
+    }
+}
+
+// Where a batch of movements comes from: the reason plus the payment, refund
+// or other document id it belongs to.
+pub(crate) struct MovementSource<'a> {
+    pub reason: MovementReason,
+    pub source_id: Option<i64>,
+    pub actor: Option<&'a str>,
+    pub note: Option<&'a str>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct StockShortage {
+    product_id: i64,
+    name: String,
+    on_hand: f64,
+    requested: f64,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct StockLevel {
+    product_id: i64,
+    name: String,
+    barcode: Option<String>,
+    category_id: Option<i64>,
+    on_hand: f64,
+    last_movement_at: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct StockMovementRecord {
+    id: i64,
+    product_id: i64,
+    quantity: f64,
+    reason: String,
+    source_id: Option<i64>,
+    actor: Option<String>,
+    note: Option<String>,
+    created_at: String,
+    business_date: String,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct StockLevelsPayload {
+    product_ids: Option<Vec<i64>>,
+    category_id: Option<i64>,
+    include_archived: Option<bool>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct StockMovementsPayload {
+    product_id: i64,
+    limit: Option<i64>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct AdjustStockPayload {
+    product_id: i64,
+    quantity: f64,
+    actor: String,
+    note: Option<String>,
+}
+
+pub(crate) fn round_quantity(quantity: f64) -> f64 {
+    (quantity * QUANTITY_SCALE).round() / QUANTITY_SCALE
+}
+
+pub(crate) fn load_stock_policy(conn: &Connection) -> AppResult<StockPolicy> {
+    Ok(load_setting(conn, STOCK_POLICY_KEY)?.unwrap_or_default())
+}
+
+pub(crate) fn on_hand(conn: &Connection, product_id: i64) -> AppResult<f64> {
+    let total: f64 = conn.query_row(
+        "SELECT COALESCE(SUM(quantity), 0) FROM stock_movements WHERE product_id = ?1",
+        [product_id],
+        |row| row.get(0),
+    )?;
+    Ok(round_quantity(total))
+}
+
+// Blends received goods into the product's weighted average cost. Stock at or
+// below zero carries no value, so the new cost is simply the receipt's.
+pub(crate) fn apply_receipt_cost(
+    conn: &Connection,
+    product_id: i64,
+    quantity: f64,
+    unit_cost: i64,
+) -> AppResult<()> {
+    let current: Option<i64> = conn.query_row(
+        "SELECT cost_price FROM products WHERE id = ?1",
+        [product_id],
+        |row| row.get(0),
+    )?;
+    let stock = on_hand(conn, product_id)?;
+    let average = match current {
+        Some(cost) if stock > 0.0 && stock + quantity > 0.0 => {
+            ((stock * cost as f64 + quantity * unit_cost as f64) / (stock + quantity)).round()
+                as i64
+        }
+        _ => unit_cost,
+    };
+    conn.execute(
+        "UPDATE products SET cost_price = ?1 WHERE id = ?2",
+        (average, product_id),
+    )?;
+    Ok(())
+}
+
+pub(crate) fn record_movements(
+    conn: &Connection,
+    moment: &StoreMoment,
+    source: &MovementSource<'_>,
+    lines: impl IntoIterator<Item = (i64, f64)>,
+) -> AppResult<()> {
+    let mut statement = conn.prepare(
+        "INSERT INTO stock_movements (
+            product_id, quantity, reason, source_id, actor, note, created_at, business_date
+        )
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+    )?;
+    for (product_id, quantity) in lines {
+        let quantity = round_quantity(quantity);
+        if quantity == 0.0 {
+            continue;
+        }
+        statement.execute((
+            product_id,
+            quantity,
+            source.reason.as_str(),
+            source.source_id,
+            source.actor,
+            source.note,
+            moment.timestamp.as_str(),
+            moment.business_date.as_str(),
+        ))?;
+    }
+    Ok(())
+}
+
+fn sold_quantities(items: &[NormalizedPaymentItem]) -> Vec<(i64, String, f64)> {
+    let mut totals: Vec<(i64, String, f64)> = Vec::new();
+    for item in items {
+        let Some(product_id) = item.product_id else {
+            continue;
+        };
+        match totals.iter_mut().find(|(id, _, _)| *id == product_id) {
+            Some(entry) => entry.2 += item.base_quantity(),
+            None => totals.push((product_id, item.name.clone(), item.base_quantity())),
+        }
+    }
+    totals
+}
+
+// Applies the negative-stock policy to a cart before it is saved. Blocking
+// stops the sale; warning lets it through and hands the shortages back.
+pub(crate) fn check_stock(
+    conn: &Connection,
+    items: &[NormalizedPaymentItem],
+) -> AppResult<Vec<StockShortage>> {
+    let policy = load_stock_policy(conn)?.negative_stock;
+    if policy == NegativeStockPolicy::Allow {
+        return Ok(Vec::new());
+    }
+    let mut shortages = Vec::new();
+    for (product_id, name, requested) in sold_quantities(items) {
+        let available = on_hand(conn, product_id)?;
+        if round_quantity(available - requested) < 0.0 {
+            shortages.push(StockShortage {
+                product_id,
+                name,
+                on_hand: available,
+                requested: round_quantity(requested),
+            });
+        }
+    }
+    if policy == NegativeStockPolicy::Block {
+        if let Some(shortage) = shortages.first() {
+            return Err(AppError::conflict(
+                "stock_insufficient",
+                format!(
+                    "Only {} of {} left in stock",
+                    shortage.on_hand, shortage.name
+                ),
+            )
+            .with_field("items"));
+        }
+    }
+    Ok(shortages)
+}
+
+pub(crate) fn record_sale(
+    conn: &Connection,
+    moment: &StoreMoment,
+    payment_id: i64,
+    items: &[NormalizedPaymentItem],
+) -> AppResult<()> {
+    let source = MovementSource {
+        reason: MovementReason::Sale,
+        source_id: Some(payment_id),
+        actor: None,
+        note: None,
+    };
+    let lines = sold_quantities(items)
+        .into_iter()
+        .map(|(product_id, _, quantity)| (product_id, -quantity));
+    record_movements(conn, moment, &source, lines)
+}
+
+// A void puts back whatever the sale and its refunds left out of stock, so
+// sales recorded before stock tracking existed are not reversed.
+pub(crate) fn reverse_payment(
+    conn: &Connection,
+    moment: &StoreMoment,
+    payment_id: i64,
+    actor: &str,
+) -> AppResult<()> {
+    let mut statement = conn.prepare(
+        "SELECT product_id, -SUM(quantity)
+         FROM stock_movements
+         WHERE (reason = 'sale' AND source_id = ?1)
+            OR (reason = 'refund'
+                AND source_id IN (SELECT id FROM refunds WHERE payment_id = ?1))
+         GROUP BY product_id",
+    )?;
+    let lines = statement
+        .query_map([payment_id], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<Result<Vec<(i64, f64)>, _>>()?;
+    let source = MovementSource {
+        reason: MovementReason::Void,
+        source_id: Some(payment_id),
+        actor: Some(actor),
+        note: None,
+    };
+    record_movements(conn, moment, &source, lines)
+}
+
+#[tauri::command]
+pub(crate) fn get_stock_policy(database: tauri::State<'_, Database>) -> AppResult<StockPolicy> {
+    let conn = database.lock()?;
+    load_stock_policy(&conn)
+}
+
+#[tauri::command]
+pub(crate) fn update_stock_policy(
+    database: tauri::State<'_, Database>,
+    payload: StockPolicy,
+) -> AppResult<StockPolicy> {
+    let conn = database.lock()?;
+    store_setting(&conn, STOCK_POLICY_KEY, &payload)?;
+    Ok(payload)
+}
+
+#[tauri::command]
+pub(crate) fn stock_levels(
+    database: tauri::State<'_, Database>,
+    payload: Option<StockLevelsPayload>,
+) -> AppResult<Vec<StockLevel>> {
+    let StockLevelsPayload {
+        product_ids,
+        category_id,
+        include_archived,
+    } = payload.unwrap_or_default();
+    let mut clauses: Vec<String> = Vec::new();
+    let mut params: Vec<Value> = Vec::new();
+    if !include_archived.unwrap_or(false) {
+        clauses.push("p.archived_at IS NULL".into());
+    }
+    if let Some(ids) = product_ids.filter(|ids| !ids.is_empty()) {
+        let start = params.len() + 1;
+        let placeholders: Vec<String> = (start..start + ids.len())
+            .map(|index| format!("?{}", index))
+            .collect();
+        clauses.push(format!("p.id IN ({})", placeholders.join(", ")));
+        params.extend(ids.into_iter().map(Value::Integer));
+    }
+    if let Some(category_id) = category_id {
+        params.push(Value::Integer(category_id));
+        clauses.push(format!(
+            "(p.category_id = ?{0}
+              OR p.category_id IN (SELECT id FROM categories WHERE parent_id = ?{0}))",
+            params.len()
+        ));
+    }
+    let where_sql = if clauses.is_empty() {
+        String::new()
+    } else {
+        format!("WHERE {}", clauses.join(" AND "))
+    };
+    let conn = database.lock()?;
+    let mut statement = conn.prepare(&format!(
+        "SELECT p.id, p.name, p.barcode, p.category_id,
+                COALESCE(m.on_hand, 0), m.last_movement_at
+         FROM products p
+         LEFT JOIN (
+             SELECT product_id, SUM(quantity) AS on_hand, MAX(created_at) AS last_movement_at
+             FROM stock_movements
+             GROUP BY product_id
+         ) m ON m.product_id = p.id
+         {}
+         ORDER BY p.display_order ASC, p.id ASC",
+        where_sql
+    ))?;
+    let rows = statement.query_map(params_from_iter(params), |row| {
+        Ok(StockLevel {
+            product_id: row.get(0)?,
+            name: row.get(1)?,
+            barcode: row.get(2)?,
+            category_id: row.get(3)?,
+            on_hand: round_quantity(row.get(4)?),
+            last_movement_at: row.get(5)?,
+        })
+    })?;
+    rows.collect::<Result<Vec<_>, _>>().map_err(AppError::from)
+}
+
+#[tauri::command]
+pub(crate) fn list_stock_movements(
+    database: tauri::State<'_, Database>,
+    payload: StockMovementsPayload,
+) -> AppResult<Vec<StockMovementRecord>> {
+    let limit = payload
+        .limit
+        .unwrap_or(DEFAULT_MOVEMENT_LIMIT)
+        .clamp(1, MAX_MOVEMENT_LIMIT);
+    let conn = database.lock()?;
+    let mut statement = conn.prepare(
+        "SELECT id, product_id, quantity, reason, source_id, actor, note, created_at,
+                business_date
+         FROM stock_movements
+         WHERE product_id = ?1
+         ORDER BY id DESC
+         LIMIT ?2",
+    )?;
+    let rows = statement.query_map((payload.product_id, limit), |row| {
+        Ok(StockMovementRecord {
+            id: row.get(0)?,
+            product_id: row.get(1)?,
+            quantity: row.get(2)?,
+            reason: row.get(3)?,
+            source_id: row.get(4)?,
+            actor: row.get(5)?,
+            note: row.get(6)?,
+            created_at: row.get(7)?,
+            business_date: row.get(8)?,
+        })
+    })?;
+    rows.collect::<Result<Vec<_>, _>>().map_err(AppError::from)
+}
+
+// Manual corrections, e.g. opening stock or breakage. The quantity is the
+// change to apply, not the new level.
+#[tauri::command]
+pub(crate) fn adjust_stock(
+    database: tauri::State<'_, Database>,
+    payload: AdjustStockPayload,
+) -> AppResult<f64> {
+    let actor = payload.actor.trim();
+    if actor.is_empty() {
+        return Err(AppError::validation(
+            "stock_actor_required",
+            "actor",
+            "The person adjusting stock is required",
+        ));
+    }
+    let quantity = round_quantity(payload.quantity);
+    if !quantity.is_finite() || quantity == 0.0 {
+        return Err(AppError::validation(
+            "stock_adjustment_invalid",
+            "quantity",
+            "Adjustment must be a non-zero quantity",
+        ));
+    }
+    let note = payload
+        .note
+        .as_deref()
+        .map(str::trim)
+        .filter(|value| !value.is_empty());
+    let mut conn = database.lock()?;
+    let tx = conn.transaction()?;
+    crate::fetch_product_by_id(&tx, payload.product_id)
+        .map_err(|err| err.with_field("productId"))?;
+    let moment = store_now(&tx)?;
+    let source = MovementSource {
+        reason: MovementReason::Adjustment,
+        source_id: None,
+        actor: Some(actor),
+        note,
+    };
+    record_movements(&tx, &moment, &source, [(payload.product_id, quantity)])?;
+    let level = on_hand(&tx, payload.product_id)?;
+    tx.commit()?;
+    Ok(level)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stocked_database(policy: NegativeStockPolicy) -> Connection {
+        let mut conn = Connection::open_in_memory().unwrap();
+        crate::initialize_schema(&mut conn).unwrap();
+        conn.execute_batch(
+            "INSERT INTO products (id, name, price) VALUES (1, 'Gạo', 20000), (2, 'Muối', 5000);",
+        )
+        .unwrap();
+        store_setting(
+            &conn,
+            STOCK_POLICY_KEY,
+            &StockPolicy {
+                negative_stock: policy,
+            },
+        )
+        .unwrap();
+        let moment = moment();
+        record_movements(
+            &conn,
+            &moment,
+            &source(MovementReason::Adjustment, None),
+            [(1, 10.0), (2, 1.0)],
+        )
+        .unwrap();
+        conn
+    }
+
+    fn moment() -> StoreMoment {
+        StoreMoment {
+            timestamp: "2026-01-05 09:00:00+07:00".into(),
+            business_date: "2026-01-05".into(),
+        }
+    }
+
+    fn source(reason: MovementReason, source_id: Option<i64>) -> MovementSource<'static> {
+        MovementSource {
+            reason,
+            source_id,
+            actor: Some("Linh"),
+            note: None,
+        }
+    }
+
+    fn sold(product_id: i64, quantity: f64, unit_factor: f64) -> NormalizedPaymentItem {
+        NormalizedPaymentItem {
+            product_id: Some(product_id),
+            name: format!("Sản phẩm {}", product_id),
+            quantity_decimal: quantity,
+            legacy_quantity: quantity.round() as i64,
+            base_unit_price: 1000,
+            edited_unit_price: None,
+            effective_unit_price: 1000,
+            line_subtotal: 1000,
+            line_discount: 0,
+            line_tax: 0,
+            unit_id: None,
+            unit_name: None,
+            unit_factor,
+        }
+    }
+
+    // Two lines of the same product add up before they are checked.
+    fn cart() -> Vec<NormalizedPaymentItem> {
+        vec![sold(1, 4.0, 1.0), sold(2, 1.0, 1.0), sold(1, 2.0, 5.0)]
+    }
+
+    #[test]
+    fn allow_policy_skips_the_check() {
+        let conn = stocked_database(NegativeStockPolicy::Allow);
+        assert!(check_stock(&conn, &cart()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn warn_policy_returns_the_shortages() {
+        let conn = stocked_database(NegativeStockPolicy::Warn);
+        let shortages = check_stock(&conn, &cart()).unwrap();
+        assert_eq!(shortages.len(), 1);
+        assert_eq!(shortages[0].product_id, 1);
+        assert_eq!(shortages[0].on_hand, 10.0);
+        assert_eq!(shortages[0].requested, 14.0);
+        assert!(check_stock(&conn, &[sold(1, 10.0, 1.0)])
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn block_policy_refuses_the_sale() {
+        let conn = stocked_database(NegativeStockPolicy::Block);
+        let err = check_stock(&conn, &cart()).err().unwrap();
+        assert_eq!(err.code, "stock_insufficient");
+        assert_eq!(err.field.as_deref(), Some("items"));
+        assert!(check_stock(&conn, &[sold(2, 1.0, 1.0)]).is_ok());
+    }
+
+    #[test]
+    fn voiding_puts_back_only_what_refunds_left_out() {
+        let conn = stocked_database(NegativeStockPolicy::Allow);
+        conn.execute_batch(
+            "INSERT INTO payments (id, invoice_number, cashier_name, subtotal, tax, total,
+                                   discount, paid_cash, change_due)
+             VALUES (1, 'HD0001', 'Linh', 0, 0, 0, 0, 0, 0),
+                    (2, 'HD0002', 'Linh', 0, 0, 0, 0, 0, 0),
+                    (3, 'HD0003', 'Linh', 0, 0, 0, 0, 0, 0);
+             INSERT INTO refunds (id, payment_id, refund_number, cashier_name, total)
+             VALUES (1, 1, 'HD0001-TH1', 'Linh', 0);",
+        )
+        .unwrap();
+        let moment = moment();
+        record_sale(&conn, &moment, 1, &[sold(1, 3.0, 1.0), sold(2, 1.0, 1.0)]).unwrap();
+        record_sale(&conn, &moment, 2, &[sold(1, 1.0, 1.0)]).unwrap();
+        record_movements(
+            &conn,
+            &moment,
+            &source(MovementReason::Refund, Some(1)),
+            [(1, 1.0)],
+        )
+        .unwrap();
+        assert_eq!(on_hand(&conn, 1).unwrap(), 7.0);
+
+        reverse_payment(&conn, &moment, 1, "Linh").unwrap();
+        // Payment 2 stays sold; payment 1 is back in full.
+        assert_eq!(on_hand(&conn, 1).unwrap(), 9.0);
+        assert_eq!(on_hand(&conn, 2).unwrap(), 1.0);
+
+        // A sale from before stock tracking has nothing to reverse.
+        reverse_payment(&conn, &moment, 3, "Linh").unwrap();
+        let voids: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM stock_movements WHERE reason = 'void'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(voids, 2);
+    }
+}
@@ -0,0 +1,323 @@
+use crate::business_day::{store_now, StoreMoment};
+use crate::error::{AppError, AppResult};
+use crate::settings::{load_setting, store_setting};
+use crate::Database;
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+
+const INVOICE_FORMAT_KEY: &str = "invoice_number_format";
+const MAX_COUNTER_WIDTH: usize = 12;
+const MAX_CODE_LENGTH: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum SequenceReset {
+    Never,
+    Daily,
+    Yearly,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct InvoiceNumberFormat {
+    prefix: String,
+    terminal_code: String,
+    date_pattern: Option<String>,
+    separator: String,
+    counter_width: usize,
+    reset: SequenceReset,
+}
+
+impl Default for InvoiceNumberFormat {
+    fn default() -> Self {
+        InvoiceNumberFormat {
+            prefix: "HD".into(),
+            terminal_code: String::new(),
+            date_pattern: Some("%Y%m%d".into()),
+            separator: String::new(),
+            counter_width: 4,
+            reset: SequenceReset::Daily,
+        }
+    }
+}
+
+impl InvoiceNumberFormat {
+    fn validate(&self) -> AppResult<()> {
+        for (field, value) in [
+            ("prefix", &self.prefix),
+            ("terminalCode", &self.terminal_code),
+            ("separator", &self.separator),
+        ] {
+            if value.len() > MAX_CODE_LENGTH || value.chars().any(char::is_whitespace) {
+                return Err(AppError::validation(
+                    "invoice_format_code_invalid",
+                    field,
+                    "Invoice number parts must be short and contain no spaces",
+                ));
+            }
+        }
+        if self.counter_width == 0 || self.counter_width > MAX_COUNTER_WIDTH {
+            return Err(AppError::validation(
+                "invoice_format_counter_width_invalid",
+                "counterWidth",
+                format!("Counter width must be between 1 and {}", MAX_COUNTER_WIDTH),
+            ));
+        }
+        let pattern = self.date_pattern.as_deref().unwrap_or("");
+        let has = |token: &str| pattern.contains(token);
+        let has_year = has("%Y") || has("%F");
+        let has_day = has("%F") || has("%j") || (has("%m") && has("%d"));
+        let resets_ok = match self.reset {
+            SequenceReset::Never => true,
+            SequenceReset::Daily => has_year && has_day,
+            SequenceReset::Yearly => has_year,
+        };
+        if !resets_ok {
+            return Err(AppError::validation(
+                "invoice_format_reset_without_date",
+                "datePattern",
+                "The date part must include the period the counter resets on",
+            ));
+        }
+        Ok(())
+    }
+}
+
+fn load_format(conn: &Connection) -> AppResult<InvoiceNumberFormat> {
+    Ok(load_setting(conn, INVOICE_FORMAT_KEY)?.unwrap_or_default())
+}
+
+fn format_moment(conn: &Connection, pattern: &str, moment: &StoreMoment) -> AppResult<String> {
+    conn.query_row(
+        "SELECT strftime(?1, ?2)",
+        [pattern, moment.business_datetime().as_str()],
+        |row| row.get(0),
+    )
+    .map_err(AppError::from)
+}
+
+fn sequence_scope(format: &InvoiceNumberFormat, moment: &StoreMoment) -> String {
+    let period = match format.reset {
+        SequenceReset::Never => "",
+        SequenceReset::Daily => moment.business_date.as_str(),
+        SequenceReset::Yearly => &moment.business_date[..4],
+    };
+    format!("{}|{}", format.terminal_code, period)
+}
+
+// Everything in front of the counter, separator included.
+fn render_stem(
+    conn: &Connection,
+    format: &InvoiceNumberFormat,
+    moment: &StoreMoment,
+) -> AppResult<String> {
+    let mut stem = String::new();
+    let head = format!("{}{}", format.prefix, format.terminal_code);
+    if !head.is_empty() {
+        stem.push_str(&head);
+        stem.push_str(&format.separator);
+    }
+    if let Some(pattern) = format
+        .date_pattern
+        .as_deref()
+        .filter(|value| !value.is_empty())
+    {
+        stem.push_str(&format_moment(conn, pattern, moment)?);
+        stem.push_str(&format.separator);
+    }
+    Ok(stem)
+}
+
+// Must run inside the transaction that inserts the payment so a rolled back
+// sale also rolls back its counter and the sequence stays gapless.
+pub(crate) fn allocate_invoice_number(
+    conn: &Connection,
+    moment: &StoreMoment,
+) -> AppResult<String> {
+    let format = load_format(conn)?;
+    let scope = sequence_scope(&format, moment);
+    let counter: i64 = conn.query_row(
+        "INSERT INTO invoice_sequences (scope, last_value) VALUES (?1, 1)
+         ON CONFLICT(scope) DO UPDATE SET last_value = last_value + 1
+         RETURNING last_value",
+        [scope.as_str()],
+        |row| row.get(0),
+    )?;
+    let stem = render_stem(conn, &format, moment)?;
+    Ok(format!(
+        "{}{:0width$}",
+        stem,
+        counter,
+        width = format.counter_width
+    ))
+}
+
+// A new format can land on a scope that starts again at 1 while still
+// rendering numbers already issued today (say Daily to Never with the same
+// date part). The current scope is moved past the highest number issued with
+// the new stem and counter width so the next sale cannot collide.
+fn seed_current_sequence(conn: &Connection, format: &InvoiceNumberFormat) -> AppResult<()> {
+    let moment = store_now(conn)?;
+    let stem = render_stem(conn, format, &moment)?;
+    let counter_start = stem.chars().count() as i64 + 1;
+    let issued: i64 = conn.query_row(
+        "SELECT COALESCE(MAX(CAST(substr(invoice_number, ?2) AS INTEGER)), 0)
+         FROM payments
+         WHERE invoice_number >= ?1 AND invoice_number < ?1 || char(1114111)
+           AND length(invoice_number) = ?2 - 1 + ?3
+           AND substr(invoice_number, ?2) NOT GLOB '*[^0-9]*'",
+        (stem.as_str(), counter_start, format.counter_width as i64),
+        |row| row.get(0),
+    )?;
+    if issued == 0 {
+        return Ok(());
+    }
+    conn.execute(
+        "INSERT INTO invoice_sequences (scope, last_value) VALUES (?1, ?2)
+         ON CONFLICT(scope) DO UPDATE SET last_value = MAX(last_value, excluded.last_value)",
+        (sequence_scope(format, &moment).as_str(), issued),
+    )?;
+    Ok(())
+}
+
+#[tauri::command]
+pub(crate) fn get_invoice_number_format(
+    database: tauri::State<'_, Database>,
+) -> AppResult<InvoiceNumberFormat> {
+    let conn = database.lock()?;
+    load_format(&conn)
+}
+
+#[tauri::command]
+pub(crate) fn update_invoice_number_format(
+    database: tauri::State<'_, Database>,
+    payload: InvoiceNumberFormat,
+) -> AppResult<InvoiceNumberFormat> {
+    let format = InvoiceNumberFormat {
+        prefix: payload.prefix.trim().to_string(),
+        terminal_code: payload.terminal_code.trim().to_string(),
+        date_pattern: payload
+            .date_pattern
+            .map(|value| value.trim().to_string())
+            .filter(|value| !value.is_empty()),
+        ..payload
+    };
+    format.validate()?;
+    let conn = database.lock()?;
+    if let Some(pattern) = format.date_pattern.as_deref() {
+        let supported: bool =
+            conn.query_row("SELECT strftime(?1, 'now') IS NOT NULL", [pattern], |row| {
+                row.get(0)
+            })?;
+        if !supported {
+            return Err(AppError::validation(
+                "invoice_format_date_pattern_invalid",
+                "datePattern",
+                "The date part uses an unsupported format code",
+            ));
+        }
+    }
+    let tx = conn.unchecked_transaction()?;
+    store_setting(&tx, INVOICE_FORMAT_KEY, &format)?;
+    seed_current_sequence(&tx, &format)?;
+    tx.commit()?;
+    Ok(format)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn numbering(format: InvoiceNumberFormat) -> Connection {
+        let mut conn = Connection::open_in_memory().unwrap();
+        crate::initialize_schema(&mut conn).unwrap();
+        format.validate().unwrap();
+        store_setting(&conn, INVOICE_FORMAT_KEY, &format).unwrap();
+        conn
+    }
+
+    fn moment(business_date: &str, time: &str) -> StoreMoment {
+        StoreMoment {
+            timestamp: format!("{} {}+07:00", business_date, time),
+            business_date: business_date.into(),
+        }
+    }
+
+    fn next(conn: &Connection, business_date: &str) -> String {
+        allocate_invoice_number(conn, &moment(business_date, "09:00:00")).unwrap()
+    }
+
+    #[test]
+    fn rolled_back_sales_leave_no_gap() {
+        let mut conn = numbering(InvoiceNumberFormat::default());
+        assert_eq!(next(&conn, "2026-01-05"), "HD202601050001");
+
+        let tx = conn.transaction().unwrap();
+        assert_eq!(next(&tx, "2026-01-05"), "HD202601050002");
+        tx.rollback().unwrap();
+
+        assert_eq!(next(&conn, "2026-01-05"), "HD202601050002");
+        assert_eq!(next(&conn, "2026-01-05"), "HD202601050003");
+    }
+
+    #[test]
+    fn counters_restart_with_their_period() {
+        let conn = numbering(InvoiceNumberFormat::default());
+        assert_eq!(next(&conn, "2026-01-05"), "HD202601050001");
+        assert_eq!(next(&conn, "2026-01-05"), "HD202601050002");
+        assert_eq!(next(&conn, "2026-01-06"), "HD202601060001");
+
+        let conn = numbering(InvoiceNumberFormat {
+            terminal_code: "Q1".into(),
+            date_pattern: Some("%Y".into()),
+            separator: "-".into(),
+            reset: SequenceReset::Yearly,
+            ..InvoiceNumberFormat::default()
+        });
+        assert_eq!(next(&conn, "2025-12-31"), "HDQ1-2025-0001");
+        assert_eq!(next(&conn, "2026-01-01"), "HDQ1-2026-0001");
+        assert_eq!(next(&conn, "2026-06-30"), "HDQ1-2026-0002");
+
+        let conn = numbering(InvoiceNumberFormat {
+            date_pattern: None,
+            counter_width: 6,
+            reset: SequenceReset::Never,
+            ..InvoiceNumberFormat::default()
+        });
+        assert_eq!(next(&conn, "2025-12-31"), "HD000001");
+        assert_eq!(next(&conn, "2026-01-01"), "HD000002");
+    }
+
+    // Sales rung up after midnight but before the cutover carry the
+    // previous business day in their number.
+    #[test]
+    fn numbers_follow_the_business_day() {
+        let conn = numbering(InvoiceNumberFormat::default());
+        let late = StoreMoment {
+            timestamp: "2026-01-06 01:30:00+07:00".into(),
+            business_date: "2026-01-05".into(),
+        };
+        assert_eq!(
+            allocate_invoice_number(&conn, &late).unwrap(),
+            "HD202601050001"
+        );
+    }
+
+    #[test]
+    fn resets_need_the_period_in_the_date_part() {
+        let daily_without_day = InvoiceNumberFormat {
+            date_pattern: Some("%Y%m".into()),
+            ..InvoiceNumberFormat::default()
+        };
+        assert_eq!(
+            daily_without_day.validate().err().map(|err| err.code),
+            Some("invoice_format_reset_without_date")
+        );
+        let never = InvoiceNumberFormat {
+            date_pattern: None,
+            reset: SequenceReset::Never,
+            ..InvoiceNumberFormat::default()
+        };
+        assert!(never.validate().is_ok());
+    }
+}
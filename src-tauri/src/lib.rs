@@ -1,9 +1,51 @@
-use rusqlite::Connection;
+mod audit;
+mod barcodes;
+mod business_day;
+mod catalog;
+mod categories;
+mod error;
+mod inventory;
+mod invoicing;
+mod migrations;
+mod payment_search;
+mod price_changes;
+mod pricing;
+mod product_archive;
+mod product_units;
+mod purchasing;
+mod refunds;
+mod reports;
+mod settings;
+mod spreadsheet;
+mod stocktake;
+
+use error::{AppError, AppResult};
+use pricing::{CartTotals, NormalizedPaymentItem, PaymentItemInput, PricedCart};
+use rusqlite::{params_from_iter, Connection, OptionalExtension, TransactionBehavior};
 use serde::{Deserialize, Serialize};
-use std::{env, fs, path::PathBuf};
+use std::{
+    collections::HashMap,
+    env, fs,
+    path::PathBuf,
+    sync::{Mutex, MutexGuard},
+    time::Duration,
+};
 use tauri::{path::BaseDirectory, Manager};
 
-const MONEY_ROUNDING_MODE: &str = "floor";
+const DB_BUSY_TIMEOUT_MS: u64 = 5_000;
+const MAX_IDEMPOTENCY_KEY_LENGTH: usize = 128;
+
+struct Database {
+    conn: Mutex<Connection>,
+}
+
+impl Database {
+    fn lock(&self) -> AppResult<MutexGuard<'_, Connection>> {
+        self.conn.lock().map_err(|_| {
+            AppError::storage("database_unavailable", "Database connection is unavailable")
+        })
+    }
+}
 
 #[derive(Serialize)]
 #[serde(rename_all = "camelCase")]
@@ -15,6 +57,10 @@ struct ProductRecord {
     visible: bool,
     quick_display: bool,
     display_order: i64,
+    archived_at: Option<String>,
+    category_id: Option<i64>,
+    cost_price: Option<i64>,
+    unit_name: Option<String>,
 }
 
 #[derive(Deserialize)]
@@ -26,6 +72,10 @@ struct CreateProductPayload {
     visible: bool,
     quick_display: bool,
     display_order: i64,
+    category_id: Option<i64>,
+    cost_price: Option<i64>,
+    unit_name: Option<String>,
+    internal_barcode: Option<bool>,
 }
 
 #[derive(Deserialize)]
@@ -38,6 +88,17 @@ struct UpdateProductPayload {
     visible: bool,
     quick_display: bool,
     display_order: i64,
+    category_id: Option<i64>,
+    cost_price: Option<i64>,
+    unit_name: Option<String>,
+    internal_barcode: Option<bool>,
+    changed_by: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct FindProductByBarcodePayload {
+    barcode: String,
 }
 
 #[derive(Serialize)]
@@ -54,6 +115,8 @@ struct PaymentItemRecord {
     effective_unit_price: i64,
     line_subtotal: i64,
     line_discount: i64,
+    unit_name: Option<String>,
+    unit_factor: f64,
 }
 
 #[derive(Serialize)]
@@ -84,39 +147,19 @@ struct PaymentRecord {
     change_due: i64,
     note: Option<String>,
     created_at: String,
+    business_date: Option<String>,
+    voided: bool,
+    voided_at: Option<String>,
+    voided_by: Option<String>,
+    void_reason: Option<String>,
     items: Vec<PaymentItemRecord>,
-}
-
-#[derive(Deserialize)]
-#[serde(rename_all = "camelCase")]
-struct PaymentItemInput {
-    product_id: Option<i64>,
-    name: String,
-    quantity: f64,
-    base_unit_price: i64,
-    edited_unit_price: Option<i64>,
-    effective_unit_price: Option<i64>,
-    price: Option<i64>,
-    line_subtotal: Option<i64>,
-    line_discount: Option<i64>,
-}
-
-struct NormalizedPaymentItem {
-    product_id: Option<i64>,
-    name: String,
-    quantity_decimal: f64,
-    legacy_quantity: i64,
-    base_unit_price: i64,
-    edited_unit_price: Option<i64>,
-    effective_unit_price: i64,
-    line_subtotal: i64,
-    line_discount: i64,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    stock_warnings: Vec<inventory::StockShortage>,
 }
 
 #[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
 struct CreatePaymentPayload {
-    invoice_number: String,
     cashier_name: String,
     subtotal: i64,
     tax: i64,
@@ -125,6 +168,7 @@ struct CreatePaymentPayload {
     paid_cash: i64,
     change_due: i64,
     note: Option<String>,
+    idempotency_key: Option<String>,
     items: Vec<PaymentItemInput>,
 }
 
@@ -144,151 +188,48 @@ fn locate_seed_database(app_handle: &tauri::AppHandle) -> Option<PathBuf> {
     candidates.into_iter().find(|path| path.exists())
 }
 
-fn ensure_database(app_handle: &tauri::AppHandle) -> Result<PathBuf, String> {
+fn ensure_database(app_handle: &tauri::AppHandle) -> AppResult<PathBuf> {
     let db_path = app_handle
         .path()
-        .resolve("products.sqlite", BaseDirectory::AppData)
-        .map_err(|err| err.to_string())?;
+        .resolve("products.sqlite", BaseDirectory::AppData)?;
     if !db_path.exists() {
         if let Some(resource_path) = locate_seed_database(app_handle) {
             if let Some(parent) = db_path.parent() {
-                fs::create_dir_all(parent).map_err(|err| err.to_string())?;
+                fs::create_dir_all(parent)?;
             }
-            fs::copy(resource_path, &db_path).map_err(|err| err.to_string())?;
+            fs::copy(resource_path, &db_path)?;
         } else {
             if let Some(parent) = db_path.parent() {
-                fs::create_dir_all(parent).map_err(|err| err.to_string())?;
+                fs::create_dir_all(parent)?;
             }
-            fs::File::create(&db_path).map_err(|err| err.to_string())?;
+            fs::File::create(&db_path)?;
         }
     }
     Ok(db_path)
 }
 
-fn column_exists(conn: &Connection, table: &str, column: &str) -> Result<bool, String> {
-    let sql = format!("PRAGMA table_info({})", table);
-    let mut statement = conn.prepare(&sql).map_err(|err| err.to_string())?;
-    let mut rows = statement.query([]).map_err(|err| err.to_string())?;
-    while let Some(row) = rows.next().map_err(|err| err.to_string())? {
-        let name: String = row.get(1).map_err(|err| err.to_string())?;
-        if name.eq_ignore_ascii_case(column) {
-            return Ok(true);
-        }
-    }
-    Ok(false)
-}
-
-fn add_column_if_missing(
-    conn: &Connection,
-    table: &str,
-    column: &str,
-    definition: &str,
-) -> Result<(), String> {
-    if column_exists(conn, table, column)? {
-        return Ok(());
-    }
-    let sql = format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition);
-    conn.execute(sql.as_str(), [])
-        .map_err(|err| err.to_string())
-        .map(|_| ())
-}
-
-fn ensure_payment_item_columns(conn: &Connection) -> Result<(), String> {
-    add_column_if_missing(conn, "payment_items", "quantity_decimal", "REAL")?;
-    add_column_if_missing(conn, "payment_items", "base_unit_price", "INTEGER")?;
-    add_column_if_missing(conn, "payment_items", "edited_unit_price", "INTEGER")?;
-    add_column_if_missing(conn, "payment_items", "line_subtotal", "INTEGER")?;
-    add_column_if_missing(
-        conn,
-        "payment_items",
-        "line_discount",
-        "INTEGER NOT NULL DEFAULT 0",
-    )?;
+fn initialize_schema(conn: &mut Connection) -> AppResult<()> {
+    migrations::run_migrations(conn)?;
+    seed_cashiers_if_empty(conn)?;
     Ok(())
 }
 
-fn round_money(value: f64) -> i64 {
-    if MONEY_ROUNDING_MODE == "round" {
-        return value.round().max(0.0) as i64;
-    }
-    value.floor().max(0.0) as i64
-}
-
-fn initialize_schema(conn: &Connection) -> Result<(), String> {
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS products (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            name TEXT NOT NULL,
-            price INTEGER NOT NULL,
-            barcode TEXT,
-            visible INTEGER NOT NULL DEFAULT 1,
-            quick_display INTEGER NOT NULL DEFAULT 0,
-            display_order INTEGER NOT NULL DEFAULT 1,
-            created_at TEXT DEFAULT CURRENT_TIMESTAMP
-        )",
-        [],
-    )
-    .map_err(|err| err.to_string())?;
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS payments (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            invoice_number TEXT NOT NULL,
-            cashier_name TEXT NOT NULL,
-            subtotal INTEGER NOT NULL,
-            tax INTEGER NOT NULL,
-            total INTEGER NOT NULL,
-            discount INTEGER NOT NULL DEFAULT 0,
-            paid_cash INTEGER NOT NULL,
-            change_due INTEGER NOT NULL,
-            note TEXT,
-            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
-        )",
-        [],
-    )
-    .map_err(|err| err.to_string())?;
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS payment_items (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            payment_id INTEGER NOT NULL REFERENCES payments(id) ON DELETE CASCADE,
-            product_id INTEGER,
-            name TEXT NOT NULL,
-            quantity INTEGER NOT NULL,
-            price INTEGER NOT NULL,
-            quantity_decimal REAL,
-            base_unit_price INTEGER,
-            edited_unit_price INTEGER,
-            line_subtotal INTEGER,
-            line_discount INTEGER NOT NULL DEFAULT 0
-        )",
-        [],
-    )
-    .map_err(|err| err.to_string())?;
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS cashiers (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            code TEXT NOT NULL UNIQUE,
-            name TEXT NOT NULL,
-            role TEXT NOT NULL,
-            last_active TEXT,
-            require_pin INTEGER NOT NULL DEFAULT 0,
-            pin TEXT,
-            display_order INTEGER NOT NULL DEFAULT 1,
-            is_active INTEGER NOT NULL DEFAULT 1,
-            created_at TEXT DEFAULT CURRENT_TIMESTAMP
-        )",
-        [],
-    )
-    .map_err(|err| err.to_string())?;
-    ensure_payment_item_columns(conn)?;
-    seed_cashiers_if_empty(conn)?;
+fn configure_connection(conn: &Connection) -> AppResult<()> {
+    conn.busy_timeout(Duration::from_millis(DB_BUSY_TIMEOUT_MS))?;
+    conn.pragma_update_and_check(None, "journal_mode", "WAL", |row| row.get::<_, String>(0))?;
+    conn.pragma_update(None, "synchronous", "NORMAL")?;
+    conn.pragma_update(None, "foreign_keys", "ON")?;
     Ok(())
 }
 
-fn open_connection(app_handle: &tauri::AppHandle) -> Result<Connection, String> {
+fn open_database(app_handle: &tauri::AppHandle) -> AppResult<Database> {
     let db_path = ensure_database(app_handle)?;
-    let conn = Connection::open(db_path).map_err(|err| err.to_string())?;
-    initialize_schema(&conn)?;
-    Ok(conn)
+    let mut conn = Connection::open(db_path)?;
+    configure_connection(&conn)?;
+    initialize_schema(&mut conn)?;
+    Ok(Database {
+        conn: Mutex::new(conn),
+    })
 }
 
 fn bool_to_sql(value: bool) -> i64 {
@@ -299,17 +240,24 @@ fn bool_to_sql(value: bool) -> i64 {
     }
 }
 
-const DEFAULT_CASHIER_SEED: &[(&str, &str, &str, &str, bool, Option<&str>)] = &[
+type CashierSeed = (
+    &'static str,
+    &'static str,
+    &'static str,
+    &'static str,
+    bool,
+    Option<&'static str>,
+);
+
+const DEFAULT_CASHIER_SEED: &[CashierSeed] = &[
     ("Duo Ngoc", "Đức Ngọc", "Trưởng ca", "08:05", true, Some("1508")),
     ("hoang", "Hoàng", "Thu ngân", "08:10", false, None),
     ("an", "An", "Thu ngân", "Đang nghỉ", true, Some("5678")),
     ("vi", "Vi", "Thu ngân", "Hôm qua", false, None),
 ];
 
-fn seed_cashiers_if_empty(conn: &Connection) -> Result<(), String> {
-    let count: i64 = conn
-        .query_row("SELECT COUNT(*) FROM cashiers", [], |row| row.get(0))
-        .map_err(|err| err.to_string())?;
+fn seed_cashiers_if_empty(conn: &Connection) -> AppResult<()> {
+    let count: i64 = conn.query_row("SELECT COUNT(*) FROM cashiers", [], |row| row.get(0))?;
     if count > 0 {
         return Ok(());
     }
@@ -328,8 +276,7 @@ fn seed_cashiers_if_empty(conn: &Connection) -> Result<(), String> {
                 *pin,
                 (index as i64) + 1,
             ),
-        )
-        .map_err(|err| err.to_string())?;
+        )?;
     }
     Ok(())
 }
@@ -359,6 +306,9 @@ fn parse_cashier_row(row: &rusqlite::Row<'_>) -> Result<CashierRecord, rusqlite:
     })
 }
 
+const PRODUCT_COLUMNS: &str = "id, name, price, barcode, visible, quick_display, display_order, \
+     archived_at, category_id, cost_price, unit_name";
+
 fn parse_product_row(row: &rusqlite::Row<'_>) -> Result<ProductRecord, rusqlite::Error> {
     Ok(ProductRecord {
         id: row.get(0)?,
@@ -368,64 +318,124 @@ fn parse_product_row(row: &rusqlite::Row<'_>) -> Result<ProductRecord, rusqlite:
         visible: row.get::<_, i64>(4)? != 0,
         quick_display: row.get::<_, i64>(5)? != 0,
         display_order: row.get(6)?,
+        archived_at: row.get(7)?,
+        category_id: row.get(8)?,
+        cost_price: row.get(9)?,
+        unit_name: row.get(10)?,
     })
 }
 
-fn fetch_product_by_id(conn: &Connection, id: i64) -> Result<ProductRecord, String> {
+fn fetch_product_by_id(conn: &Connection, id: i64) -> AppResult<ProductRecord> {
     conn.query_row(
-        "SELECT id, name, price, barcode, visible, quick_display, display_order
-         FROM products
-         WHERE id = ?1",
+        &format!(
+            "SELECT {}
+             FROM products
+             WHERE id = ?1",
+            PRODUCT_COLUMNS
+        ),
         [id],
-        |row| parse_product_row(row),
+        parse_product_row,
     )
-    .map_err(|err| err.to_string())
+    .optional()?
+    .ok_or_else(|| AppError::not_found("product_not_found", "Product not found"))
 }
 
 #[tauri::command]
-fn list_products(app_handle: tauri::AppHandle) -> Result<Vec<ProductRecord>, String> {
-    let conn = open_connection(&app_handle)?;
-    let mut statement = conn
-        .prepare(
-            "SELECT id, name, price, barcode, visible, quick_display, display_order
-             FROM products
-             ORDER BY display_order ASC",
-        )
-        .map_err(|err| err.to_string())?;
+fn list_products(database: tauri::State<'_, Database>) -> AppResult<Vec<ProductRecord>> {
+    let conn = database.lock()?;
+    price_changes::apply_due_price_changes(&conn)?;
+    let mut statement = conn.prepare(&format!(
+        "SELECT {}
+         FROM products
+         WHERE archived_at IS NULL
+         ORDER BY display_order ASC",
+        PRODUCT_COLUMNS
+    ))?;
     let records = statement
-        .query_map([], |row| parse_product_row(row))
-        .map_err(|err| err.to_string())?
-        .collect::<Result<Vec<_>, _>>()
-        .map_err(|err| err.to_string())?;
+        .query_map([], parse_product_row)?
+        .collect::<Result<Vec<_>, _>>()?;
 
     Ok(records)
 }
 
 #[tauri::command]
-fn list_cashiers(app_handle: tauri::AppHandle) -> Result<Vec<CashierRecord>, String> {
-    let conn = open_connection(&app_handle)?;
-    let mut statement = conn
-        .prepare(
-            "SELECT id, code, name, role, last_active, require_pin, pin, display_order, is_active
+fn find_product_by_barcode(
+    database: tauri::State<'_, Database>,
+    payload: FindProductByBarcodePayload,
+) -> AppResult<Option<ProductRecord>> {
+    let conn = database.lock()?;
+    price_changes::apply_due_price_changes(&conn)?;
+    let Some(barcode) = normalize_barcode(Some(payload.barcode)) else {
+        return Ok(None);
+    };
+    conn.query_row(
+        &format!(
+            "SELECT {}
+             FROM products
+             WHERE barcode = ?1 AND archived_at IS NULL",
+            PRODUCT_COLUMNS
+        ),
+        [barcode],
+        parse_product_row,
+    )
+    .optional()
+    .map_err(AppError::from)
+}
+
+#[tauri::command]
+fn list_cashiers(database: tauri::State<'_, Database>) -> AppResult<Vec<CashierRecord>> {
+    let conn = database.lock()?;
+    let mut statement = conn.prepare(
+        "SELECT id, code, name, role, last_active, require_pin, pin, display_order, is_active
              FROM cashiers
              WHERE is_active != 0
              ORDER BY display_order ASC, name ASC",
-        )
-        .map_err(|err| err.to_string())?;
+    )?;
     let records = statement
-        .query_map([], |row| parse_cashier_row(row))
-        .map_err(|err| err.to_string())?
-        .collect::<Result<Vec<_>, _>>()
-        .map_err(|err| err.to_string())?;
+        .query_map([], parse_cashier_row)?
+        .collect::<Result<Vec<_>, _>>()?;
     Ok(records)
 }
 
+// Cost is optional on the form; leaving it out on an update keeps the average
+// cost that goods receipts have built up.
+fn validate_cost_price(cost_price: Option<i64>) -> AppResult<()> {
+    if cost_price.is_some_and(|value| value < 0) {
+        return Err(AppError::validation(
+            "product_cost_invalid",
+            "costPrice",
+            "Cost price cannot be negative",
+        ));
+    }
+    Ok(())
+}
+
+// Name and selling price rules shared by the create and update commands.
+fn validate_name_and_price(name: &str, price: i64) -> AppResult<String> {
+    let cleaned = name.trim();
+    if cleaned.is_empty() {
+        return Err(AppError::validation(
+            "product_name_required",
+            "name",
+            "Product name is required",
+        ));
+    }
+    if price < 0 {
+        return Err(AppError::validation(
+            "product_price_invalid",
+            "price",
+            "Price cannot be negative",
+        ));
+    }
+    Ok(cleaned.to_string())
+}
+
 #[tauri::command]
 fn create_product(
-    app_handle: tauri::AppHandle,
+    database: tauri::State<'_, Database>,
     payload: CreateProductPayload,
-) -> Result<ProductRecord, String> {
-    let conn = open_connection(&app_handle)?;
+) -> AppResult<ProductRecord> {
+    let conn = database.lock()?;
     let CreateProductPayload {
         name,
         price,
@@ -433,12 +443,28 @@ fn create_product(
         visible,
         quick_display,
         display_order,
+        internal_barcode,
+        category_id,
+        cost_price,
+        unit_name,
     } = payload;
-    let cleaned_name = name.trim().to_string();
+    let cleaned_name = validate_name_and_price(&name, price)?;
     let normalized_barcode = normalize_barcode(barcode);
+    if let Some(code) = normalized_barcode.as_deref() {
+        barcodes::validate_barcode(code, internal_barcode.unwrap_or(false))?;
+        barcodes::ensure_barcode_available(&conn, code, None)?;
+    }
+    if let Some(category_id) = category_id {
+        categories::ensure_category_exists(&conn, category_id)?;
+    }
+    validate_cost_price(cost_price)?;
+    let unit_name = normalize_note(unit_name);
     conn.execute(
-        "INSERT INTO products (name, price, barcode, visible, quick_display, display_order)
-         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        "INSERT INTO products (
+            name, price, barcode, visible, quick_display, display_order, category_id, cost_price,
+            unit_name
+        )
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
         (
             cleaned_name.as_str(),
             price,
@@ -446,19 +472,21 @@ fn create_product(
             bool_to_sql(visible),
             bool_to_sql(quick_display),
             display_order,
+            category_id,
+            cost_price,
+            unit_name.as_deref(),
         ),
-    )
-    .map_err(|err| err.to_string())?;
+    )?;
     let id = conn.last_insert_rowid();
     fetch_product_by_id(&conn, id)
 }
 
 #[tauri::command]
 fn update_product(
-    app_handle: tauri::AppHandle,
+    database: tauri::State<'_, Database>,
     payload: UpdateProductPayload,
-) -> Result<ProductRecord, String> {
-    let conn = open_connection(&app_handle)?;
+) -> AppResult<ProductRecord> {
+    let mut conn = database.lock()?;
     let UpdateProductPayload {
         id,
         name,
@@ -467,33 +495,64 @@ fn update_product(
         visible,
         quick_display,
         display_order,
+        internal_barcode,
+        category_id,
+        cost_price,
+        unit_name,
+        changed_by,
     } = payload;
-    let cleaned_name = name.trim().to_string();
+    let cleaned_name = validate_name_and_price(&name, price)?;
     let normalized_barcode = normalize_barcode(barcode);
-    let affected = conn
-        .execute(
-            "UPDATE products
+    let current = fetch_product_by_id(&conn, id)?;
+    if let Some(code) = normalized_barcode.as_deref() {
+        // A code that is already stored on this product is not re-checked, so
+        // toggling visibility never trips over an older internal code.
+        if current.barcode.as_deref() != Some(code) {
+            barcodes::validate_barcode(code, internal_barcode.unwrap_or(false))?;
+        }
+        barcodes::ensure_barcode_available(&conn, code, Some(id))?;
+    }
+    if let Some(category_id) = category_id {
+        categories::ensure_category_exists(&conn, category_id)?;
+    }
+    validate_cost_price(cost_price)?;
+    let unit_name = normalize_note(unit_name);
+    let changed_by = normalize_note(changed_by);
+    let tx = conn.transaction()?;
+    tx.execute(
+        "UPDATE products
              SET name = ?1,
                  price = ?2,
                  barcode = ?3,
                  visible = ?4,
                  quick_display = ?5,
-                 display_order = ?6
-             WHERE id = ?7",
-            (
-                cleaned_name.as_str(),
-                price,
-                normalized_barcode.as_deref(),
-                bool_to_sql(visible),
-                bool_to_sql(quick_display),
-                display_order,
-                id,
-            ),
-        )
-        .map_err(|err| err.to_string())?;
-    if affected == 0 {
-        return Err("Product not found".into());
-    }
+                 display_order = ?6,
+                 category_id = ?7,
+                 cost_price = COALESCE(?8, cost_price),
+                 unit_name = ?9
+             WHERE id = ?10",
+        (
+            cleaned_name.as_str(),
+            price,
+            normalized_barcode.as_deref(),
+            bool_to_sql(visible),
+            bool_to_sql(quick_display),
+            display_order,
+            category_id,
+            cost_price,
+            unit_name.as_deref(),
+            id,
+        ),
+    )?;
+    price_changes::record_price_change(
+        &tx,
+        id,
+        current.price,
+        price,
+        price_changes::PriceChangeSource::Manual,
+        changed_by.as_deref(),
+    )?;
+    tx.commit()?;
     fetch_product_by_id(&conn, id)
 }
 
@@ -509,81 +568,130 @@ struct PaymentRow {
     change_due: i64,
     note: Option<String>,
     created_at: String,
+    voided_at: Option<String>,
+    voided_by: Option<String>,
+    void_reason: Option<String>,
+    business_date: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct VoidPaymentPayload {
+    payment_id: i64,
+    voided_by: String,
+    reason: String,
+}
+
+fn parse_payment_row(row: &rusqlite::Row<'_>) -> Result<PaymentRow, rusqlite::Error> {
+    Ok(PaymentRow {
+        id: row.get(0)?,
+        invoice_number: row.get(1)?,
+        cashier_name: row.get(2)?,
+        subtotal: row.get(3)?,
+        tax: row.get(4)?,
+        total: row.get(5)?,
+        discount: row.get(6)?,
+        paid_cash: row.get(7)?,
+        change_due: row.get(8)?,
+        note: row.get(9)?,
+        created_at: row.get(10)?,
+        voided_at: row.get(11)?,
+        voided_by: row.get(12)?,
+        void_reason: row.get(13)?,
+        business_date: row.get(14)?,
+    })
 }
 
-fn fetch_payment_row(conn: &Connection, id: i64) -> Result<PaymentRow, String> {
+fn fetch_payment_row(conn: &Connection, id: i64) -> AppResult<PaymentRow> {
     conn.query_row(
         "SELECT id, invoice_number, cashier_name, subtotal, tax, total, discount,
-                paid_cash, change_due, note, created_at
+                paid_cash, change_due, note, created_at,
+                voided_at, voided_by, void_reason, business_date
          FROM payments
          WHERE id = ?1",
         [id],
-        |row| {
-            Ok(PaymentRow {
-                id: row.get(0)?,
-                invoice_number: row.get(1)?,
-                cashier_name: row.get(2)?,
-                subtotal: row.get(3)?,
-                tax: row.get(4)?,
-                total: row.get(5)?,
-                discount: row.get(6)?,
-                paid_cash: row.get(7)?,
-                change_due: row.get(8)?,
-                note: row.get(9)?,
-                created_at: row.get(10)?,
-            })
-        },
+        parse_payment_row,
     )
-    .map_err(|err| err.to_string())
-}
-
-fn fetch_payment_items(conn: &Connection, payment_id: i64) -> Result<Vec<PaymentItemRecord>, String> {
-    let mut statement = conn
-        .prepare(
-            "SELECT id, product_id, name, quantity, price,
-                    quantity_decimal, base_unit_price, edited_unit_price,
-                    line_subtotal, line_discount
-             FROM payment_items
-             WHERE payment_id = ?1
-             ORDER BY id ASC",
-        )
-        .map_err(|err| err.to_string())?;
-    let mapped_rows = statement
-        .query_map([payment_id], |row| {
-            let legacy_quantity: i64 = row.get(3)?;
-            let price: i64 = row.get(4)?;
-            let quantity_decimal: Option<f64> = row.get(5)?;
-            let base_unit_price: Option<i64> = row.get(6)?;
-            let edited_unit_price: Option<i64> = row.get(7)?;
-            let line_subtotal: Option<i64> = row.get(8)?;
-            let line_discount: Option<i64> = row.get(9)?;
-            let normalized_quantity = quantity_decimal.unwrap_or(legacy_quantity as f64);
-            let resolved_base_price = base_unit_price.unwrap_or(price);
-            let subtotal_value =
-                line_subtotal.unwrap_or_else(|| round_money(price as f64 * normalized_quantity));
-            Ok(PaymentItemRecord {
-                id: row.get(0)?,
-                product_id: row.get(1)?,
-                name: row.get(2)?,
-                quantity: legacy_quantity,
-                price,
-                quantity_decimal: Some(normalized_quantity),
-                base_unit_price: resolved_base_price,
-                edited_unit_price,
-                effective_unit_price: price,
-                line_subtotal: subtotal_value,
-                line_discount: line_discount.unwrap_or(0),
-            })
+    .optional()?
+    .ok_or_else(|| AppError::not_found("payment_not_found", "Payment not found"))
+}
+
+fn parse_payment_item_row(
+    row: &rusqlite::Row<'_>,
+) -> Result<(i64, PaymentItemRecord), rusqlite::Error> {
+    let legacy_quantity: i64 = row.get(4)?;
+    let price: i64 = row.get(5)?;
+    let quantity_decimal: Option<f64> = row.get(6)?;
+    let base_unit_price: Option<i64> = row.get(7)?;
+    let edited_unit_price: Option<i64> = row.get(8)?;
+    let line_subtotal: Option<i64> = row.get(9)?;
+    let line_discount: Option<i64> = row.get(10)?;
+    let normalized_quantity = quantity_decimal.unwrap_or(legacy_quantity as f64);
+    let resolved_base_price = base_unit_price.unwrap_or(price);
+    let subtotal_value =
+        line_subtotal.unwrap_or_else(|| pricing::compute_line_subtotal(price, normalized_quantity));
+    let item = PaymentItemRecord {
+        id: row.get(1)?,
+        product_id: row.get(2)?,
+        name: row.get(3)?,
+        quantity: legacy_quantity,
+        price,
+        quantity_decimal: Some(normalized_quantity),
+        base_unit_price: resolved_base_price,
+        edited_unit_price,
+        effective_unit_price: price,
+        line_subtotal: subtotal_value,
+        line_discount: line_discount.unwrap_or(0),
+        unit_name: row.get(11)?,
+        unit_factor: row.get(12)?,
+    };
+    Ok((row.get(0)?, item))
+}
+
+fn fetch_items_for_payments(
+    conn: &Connection,
+    payment_ids: &[i64],
+) -> AppResult<HashMap<i64, Vec<PaymentItemRecord>>> {
+    let mut grouped: HashMap<i64, Vec<PaymentItemRecord>> = HashMap::new();
+    if payment_ids.is_empty() {
+        return Ok(grouped);
+    }
+    let placeholders = vec!["?"; payment_ids.len()].join(", ");
+    let mut statement = conn.prepare(&format!(
+        "SELECT payment_id, id, product_id, name, quantity, price,
+                quantity_decimal, base_unit_price, edited_unit_price,
+                line_subtotal, line_discount, unit_name, unit_factor
+         FROM payment_items
+         WHERE payment_id IN ({})
+         ORDER BY payment_id ASC, id ASC",
+        placeholders
+    ))?;
+    let mut rows = statement.query(params_from_iter(payment_ids))?;
+    while let Some(row) = rows.next()? {
+        let (payment_id, item) = parse_payment_item_row(row)?;
+        grouped.entry(payment_id).or_default().push(item);
+    }
+    Ok(grouped)
+}
+
+// Loads the items of every row with a single query instead of one per payment.
+fn hydrate_payment_records(
+    conn: &Connection,
+    rows: Vec<PaymentRow>,
+) -> AppResult<Vec<PaymentRecord>> {
+    let ids: Vec<i64> = rows.iter().map(|row| row.id).collect();
+    let mut items = fetch_items_for_payments(conn, &ids)?;
+    Ok(rows
+        .into_iter()
+        .map(|row| {
+            let row_items = items.remove(&row.id).unwrap_or_default();
+            build_payment_record(row, row_items)
         })
-        .map_err(|err| err.to_string())?;
-    mapped_rows
-        .collect::<Result<Vec<_>, _>>()
-        .map_err(|err| err.to_string())
+        .collect())
 }
 
-fn hydrate_payment_record(conn: &Connection, row: PaymentRow) -> Result<PaymentRecord, String> {
-    let items = fetch_payment_items(conn, row.id)?;
-    Ok(PaymentRecord {
+fn build_payment_record(row: PaymentRow, items: Vec<PaymentItemRecord>) -> PaymentRecord {
+    PaymentRecord {
         id: row.id,
         invoice_number: row.invoice_number,
         cashier_name: row.cashier_name,
@@ -595,39 +703,27 @@ fn hydrate_payment_record(conn: &Connection, row: PaymentRow) -> Result<PaymentR
         change_due: row.change_due,
         note: row.note,
         created_at: row.created_at,
+        business_date: row.business_date,
+        voided: row.voided_at.is_some(),
+        voided_at: row.voided_at,
+        voided_by: row.voided_by,
+        void_reason: row.void_reason,
         items,
-    })
+        stock_warnings: Vec::new(),
+    }
 }
 
-fn list_payment_rows(conn: &Connection) -> Result<Vec<PaymentRow>, String> {
-    let mut statement = conn
-        .prepare(
-            "SELECT id, invoice_number, cashier_name, subtotal, tax, total, discount,
-                    paid_cash, change_due, note, created_at
-             FROM payments
-             ORDER BY datetime(created_at) DESC
-             LIMIT 200",
-        )
-        .map_err(|err| err.to_string())?;
-    let rows = statement
-        .query_map([], |row| {
-            Ok(PaymentRow {
-                id: row.get(0)?,
-                invoice_number: row.get(1)?,
-                cashier_name: row.get(2)?,
-                subtotal: row.get(3)?,
-                tax: row.get(4)?,
-                total: row.get(5)?,
-                discount: row.get(6)?,
-                paid_cash: row.get(7)?,
-                change_due: row.get(8)?,
-                note: row.get(9)?,
-                created_at: row.get(10)?,
-            })
-        })
-        .map_err(|err| err.to_string())?;
-    rows.collect::<Result<Vec<_>, _>>()
-        .map_err(|err| err.to_string())
+fn list_payment_rows(conn: &Connection) -> AppResult<Vec<PaymentRow>> {
+    let mut statement = conn.prepare(
+        "SELECT id, invoice_number, cashier_name, subtotal, tax, total, discount,
+                paid_cash, change_due, note, created_at,
+                voided_at, voided_by, void_reason, business_date
+         FROM payments
+         ORDER BY created_at DESC, id DESC
+         LIMIT 200",
+    )?;
+    let rows = statement.query_map([], parse_payment_row)?;
+    rows.collect::<Result<Vec<_>, _>>().map_err(AppError::from)
 }
 
 fn normalize_note(note: Option<String>) -> Option<String> {
@@ -641,79 +737,105 @@ fn normalize_note(note: Option<String>) -> Option<String> {
     })
 }
 
-fn normalize_payment_items(items: Vec<PaymentItemInput>) -> Result<Vec<NormalizedPaymentItem>, String> {
-    if items.is_empty() {
-        return Err("Payment must contain at least one item".into());
-    }
-    let mut normalized = Vec::with_capacity(items.len());
-    for item in items {
-        if !item.quantity.is_finite() || item.quantity <= 0.0 {
-            return Err("Item quantity must be greater than 0".into());
-        }
-        let cleaned_name = item.name.trim();
-        if cleaned_name.is_empty() {
-            return Err("Item name cannot be empty".into());
-        }
-        if item.base_unit_price < 0 {
-            return Err("Base unit price cannot be negative".into());
-        }
-        let resolved_effective_price = item
-            .effective_unit_price
-            .or(item.price)
-            .unwrap_or(item.base_unit_price);
-        if resolved_effective_price < 0 {
-            return Err("Effective unit price cannot be negative".into());
-        }
-        let edited_price = item.edited_unit_price.filter(|value| *value >= 0);
-        let computed_subtotal =
-            round_money((resolved_effective_price as f64) * item.quantity);
-        let line_subtotal = item.line_subtotal.unwrap_or(computed_subtotal);
-        if line_subtotal < 0 {
-            return Err("Line subtotal cannot be negative".into());
-        }
-        let line_discount = item.line_discount.unwrap_or(0);
-        if line_discount < 0 {
-            return Err("Line discount cannot be negative".into());
+fn ensure_products_exist(conn: &Connection, items: &[NormalizedPaymentItem]) -> AppResult<()> {
+    let mut statement =
+        conn.prepare("SELECT archived_at IS NOT NULL FROM products WHERE id = ?1")?;
+    for (index, item) in items.iter().enumerate() {
+        let Some(product_id) = item.product_id else {
+            continue;
+        };
+        let archived: Option<bool> = statement
+            .query_row([product_id], |row| row.get(0))
+            .optional()?;
+        match archived {
+            None => {
+                return Err(AppError::not_found(
+                    "item_product_not_found",
+                    "Referenced product does not exist",
+                )
+                .with_field(format!("items[{}].productId", index)))
+            }
+            Some(true) => {
+                return Err(AppError::conflict(
+                    "item_product_archived",
+                    "Referenced product has been archived",
+                )
+                .with_field(format!("items[{}].productId", index)))
+            }
+            Some(false) => {}
         }
-        let rounded_qty = item.quantity.round() as i64;
-        let legacy_quantity = if rounded_qty <= 0 { 1 } else { rounded_qty };
-        normalized.push(NormalizedPaymentItem {
-            product_id: item.product_id,
-            name: cleaned_name.to_string(),
-            quantity_decimal: item.quantity,
-            legacy_quantity,
-            base_unit_price: item.base_unit_price,
-            edited_unit_price: edited_price,
-            effective_unit_price: resolved_effective_price,
-            line_subtotal,
-            line_discount,
-        });
-    }
-    Ok(normalized)
-}
-
-fn load_payment_by_id(conn: &Connection, id: i64) -> Result<PaymentRecord, String> {
+    }
+    Ok(())
+}
+
+fn normalize_idempotency_key(key: Option<String>) -> AppResult<Option<String>> {
+    let Some(cleaned) = key
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+    else {
+        return Ok(None);
+    };
+    if cleaned.len() > MAX_IDEMPOTENCY_KEY_LENGTH {
+        return Err(AppError::validation(
+            "idempotency_key_invalid",
+            "idempotencyKey",
+            "Idempotency key is too long",
+        ));
+    }
+    Ok(Some(cleaned))
+}
+
+fn find_payment_by_idempotency_key(conn: &Connection, key: &str) -> AppResult<Option<i64>> {
+    conn.query_row(
+        "SELECT id FROM payments WHERE idempotency_key = ?1",
+        [key],
+        |row| row.get(0),
+    )
+    .optional()
+    .map_err(AppError::from)
+}
+
+fn replay_payment(
+    conn: &Connection,
+    payment_id: i64,
+    totals: &CartTotals,
+    paid_cash: i64,
+) -> AppResult<PaymentRecord> {
+    let existing = load_payment_by_id(conn, payment_id)?;
+    if existing.total != totals.total || existing.paid_cash != paid_cash {
+        return Err(AppError::conflict(
+            "idempotency_key_reused",
+            "Idempotency key was already used for a different payment",
+        )
+        .with_field("idempotencyKey"));
+    }
+    Ok(existing)
+}
+
+fn load_payment_by_id(conn: &Connection, id: i64) -> AppResult<PaymentRecord> {
     let row = fetch_payment_row(conn, id)?;
-    hydrate_payment_record(conn, row)
+    let mut records = hydrate_payment_records(conn, vec![row])?;
+    Ok(records.remove(0))
 }
 
 #[tauri::command]
-fn list_payments(app_handle: tauri::AppHandle) -> Result<Vec<PaymentRecord>, String> {
-    let conn = open_connection(&app_handle)?;
+fn list_payments(database: tauri::State<'_, Database>) -> AppResult<Vec<PaymentRecord>> {
+    let conn = database.lock()?;
     let rows = list_payment_rows(&conn)?;
-    rows.into_iter()
-        .map(|row| hydrate_payment_record(&conn, row))
-        .collect::<Result<Vec<_>, _>>()
+    hydrate_payment_records(&conn, rows)
 }
 
 #[tauri::command]
 fn create_payment(
-    app_handle: tauri::AppHandle,
+    database: tauri::State<'_, Database>,
     payload: CreatePaymentPayload,
-) -> Result<PaymentRecord, String> {
-    let mut conn = open_connection(&app_handle)?;
+) -> AppResult<PaymentRecord> {
+    save_payment(&database, payload)
+}
+
+fn save_payment(database: &Database, payload: CreatePaymentPayload) -> AppResult<PaymentRecord> {
+    let mut conn = database.lock()?;
     let CreatePaymentPayload {
-        invoice_number,
         cashier_name,
         subtotal,
         tax,
@@ -722,47 +844,97 @@ fn create_payment(
         paid_cash,
         change_due,
         note,
-        items,
+        idempotency_key,
+        mut items,
     } = payload;
-    let normalized_items = normalize_payment_items(items)?;
-    let cleaned_invoice = invoice_number.trim().to_string();
-    if cleaned_invoice.is_empty() {
-        return Err("Invoice number is required".into());
+    let idempotency_key = normalize_idempotency_key(idempotency_key)?;
+    if let Some(key) = idempotency_key.as_deref() {
+        if let Some(existing_id) = find_payment_by_idempotency_key(&conn, key)? {
+            // A retry carries the prices the sale was recorded at, even if the
+            // catalog has moved on since.
+            let PricedCart { totals, .. } = pricing::price_cart(items)?;
+            return replay_payment(&conn, existing_id, &totals, paid_cash);
+        }
     }
+    pricing::apply_catalog_prices(&conn, &mut items)?;
+    let PricedCart {
+        items: mut normalized_items,
+        totals,
+    } = pricing::price_cart(items)?;
     let cleaned_cashier = cashier_name.trim().to_string();
     if cleaned_cashier.is_empty() {
-        return Err("Cashier name is required".into());
+        return Err(AppError::validation(
+            "cashier_name_required",
+            "cashierName",
+            "Cashier name is required",
+        ));
     }
+    pricing::reconcile_figure("subtotal", subtotal, totals.subtotal)?;
+    pricing::reconcile_figure("discount", discount, totals.discount)?;
+    pricing::reconcile_figure("tax", tax, totals.tax)?;
+    pricing::reconcile_figure("total", total, totals.total)?;
+    if paid_cash < totals.total {
+        return Err(AppError::validation(
+            "paid_cash_insufficient",
+            "paidCash",
+            "Paid cash is less than the payment total",
+        ));
+    }
+    let expected_change = paid_cash - totals.total;
+    pricing::reconcile_figure("changeDue", change_due, expected_change)?;
+    ensure_products_exist(&conn, &normalized_items)?;
+    product_units::resolve_item_units(&conn, &mut normalized_items)?;
+    let stock_warnings = inventory::check_stock(&conn, &normalized_items)?;
     let normalized_note = normalize_note(note);
-    let tx = conn.transaction().map_err(|err| err.to_string())?;
-    tx.execute(
+    // Take the write lock up front: a deferred transaction that reads first
+    // cannot be upgraded once another connection has committed a sale.
+    let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+    let moment = business_day::store_now(&tx)?;
+    let invoice_number = invoicing::allocate_invoice_number(&tx, &moment)?;
+    let inserted = tx.execute(
         "INSERT INTO payments (
             invoice_number, cashier_name, subtotal, tax, total, discount,
-            paid_cash, change_due, note
+            paid_cash, change_due, note, idempotency_key, created_at, business_date
         )
-        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
         (
-            cleaned_invoice.as_str(),
+            invoice_number.as_str(),
             cleaned_cashier.as_str(),
-            subtotal,
-            tax,
-            total,
-            discount,
+            totals.subtotal,
+            totals.tax,
+            totals.total,
+            totals.discount,
             paid_cash,
-            change_due,
+            expected_change,
             normalized_note.as_deref(),
+            idempotency_key.as_deref(),
+            moment.timestamp.as_str(),
+            moment.business_date.as_str(),
         ),
-    )
-    .map_err(|err| err.to_string())?;
+    );
+    if let Err(err) = inserted {
+        drop(tx);
+        // Another process may have committed the same submission between our
+        // lookup and this insert; the unique index turns that into a replay.
+        if let Some(key) = idempotency_key.as_deref() {
+            if let Some(existing_id) = find_payment_by_idempotency_key(&conn, key)? {
+                return replay_payment(&conn, existing_id, &totals, paid_cash);
+            }
+        }
+        return Err(err.into());
+    }
     let payment_id = tx.last_insert_rowid();
+    inventory::record_sale(&tx, &moment, payment_id, &normalized_items)?;
     for item in normalized_items {
         tx.execute(
             "INSERT INTO payment_items (
                 payment_id, product_id, name, quantity, price,
                 quantity_decimal, base_unit_price, edited_unit_price,
-                line_subtotal, line_discount
+                line_subtotal, line_discount, unit_id, unit_name, unit_factor, unit_cost
             )
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13,
+                     (SELECT CAST(ROUND(cost_price * ?13) AS INTEGER)
+                      FROM products WHERE id = ?2))",
             (
                 payment_id,
                 item.product_id,
@@ -774,11 +946,77 @@ fn create_payment(
                 item.edited_unit_price,
                 item.line_subtotal,
                 item.line_discount,
+                item.unit_id,
+                item.unit_name.as_deref(),
+                item.unit_factor,
             ),
-        )
-        .map_err(|err| err.to_string())?;
+        )?;
+    }
+    tx.commit()?;
+    let mut record = load_payment_by_id(&conn, payment_id)?;
+    record.stock_warnings = stock_warnings;
+    Ok(record)
+}
+
+#[tauri::command]
+fn void_payment(
+    database: tauri::State<'_, Database>,
+    payload: VoidPaymentPayload,
+) -> AppResult<PaymentRecord> {
+    let mut conn = database.lock()?;
+    let VoidPaymentPayload {
+        payment_id,
+        voided_by,
+        reason,
+    } = payload;
+    let cleaned_actor = voided_by.trim().to_string();
+    if cleaned_actor.is_empty() {
+        return Err(AppError::validation(
+            "voided_by_required",
+            "voidedBy",
+            "The person voiding the payment is required",
+        ));
+    }
+    let cleaned_reason = reason.trim().to_string();
+    if cleaned_reason.is_empty() {
+        return Err(AppError::validation(
+            "void_reason_required",
+            "reason",
+            "A reason is required to void a payment",
+        ));
     }
-    tx.commit().map_err(|err| err.to_string())?;
+    let tx = conn.transaction()?;
+    let existing = fetch_payment_row(&tx, payment_id)?;
+    if existing.voided_at.is_some() {
+        return Err(AppError::conflict(
+            "payment_already_voided",
+            "Payment has already been voided",
+        ));
+    }
+    let moment = business_day::store_now(&tx)?;
+    tx.execute(
+        "UPDATE payments
+         SET voided_at = ?1,
+             voided_by = ?2,
+             void_reason = ?3
+         WHERE id = ?4 AND voided_at IS NULL",
+        (
+            moment.timestamp.as_str(),
+            cleaned_actor.as_str(),
+            cleaned_reason.as_str(),
+            payment_id,
+        ),
+    )?;
+    audit::record_audit(
+        &tx,
+        "payment",
+        payment_id,
+        "void",
+        &cleaned_actor,
+        Some(&cleaned_reason),
+    )?;
+    inventory::reverse_payment(&tx, &moment, payment_id, &cleaned_actor)?;
+    tx.commit()?;
     load_payment_by_id(&conn, payment_id)
 }
 
@@ -786,14 +1024,272 @@ fn create_payment(
 pub fn run() {
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
+        .plugin(tauri_plugin_dialog::init())
+        .setup(|app| {
+            let database = open_database(app.handle())?;
+            app.manage(database);
+            Ok(())
+        })
         .invoke_handler(tauri::generate_handler![
             list_products,
+            find_product_by_barcode,
+            barcodes::resolve_scan,
+            barcodes::get_embedded_barcode_rules,
+            barcodes::update_embedded_barcode_rules,
             list_cashiers,
             create_product,
             update_product,
+            categories::list_categories,
+            categories::create_category,
+            categories::update_category,
+            categories::delete_category,
+            categories::list_products_by_category,
+            product_archive::list_archived_products,
+            product_archive::archive_product,
+            product_archive::restore_product,
+            product_archive::delete_product,
+            catalog::import_products,
+            catalog::export_products,
             list_payments,
-            create_payment
+            create_payment,
+            void_payment,
+            pricing::quote_cart,
+            payment_search::search_payments,
+            invoicing::get_invoice_number_format,
+            invoicing::update_invoice_number_format,
+            business_day::get_store_clock,
+            business_day::update_store_clock,
+            business_day::current_business_date,
+            refunds::create_refund,
+            refunds::list_refunds,
+            reports::revenue_report,
+            reports::export_sales,
+            reports::margin_report,
+            inventory::get_stock_policy,
+            inventory::update_stock_policy,
+            inventory::stock_levels,
+            inventory::list_stock_movements,
+            inventory::adjust_stock,
+            purchasing::list_suppliers,
+            purchasing::create_supplier,
+            purchasing::update_supplier,
+            purchasing::create_purchase_order,
+            purchasing::list_purchase_orders,
+            purchasing::get_purchase_order,
+            purchasing::close_purchase_order,
+            purchasing::receive_goods,
+            stocktake::start_stocktake,
+            stocktake::record_count,
+            stocktake::get_stocktake,
+            stocktake::list_stocktakes,
+            stocktake::approve_stocktake,
+            stocktake::cancel_stocktake,
+            price_changes::list_price_history,
+            price_changes::schedule_price_changes,
+            price_changes::list_scheduled_prices,
+            price_changes::cancel_scheduled_price,
+            product_units::list_product_units,
+            product_units::save_product_unit,
+            product_units::delete_product_unit
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Barrier;
+    use std::thread;
+
+    fn prepare_connection(mut conn: Connection) -> Database {
+        configure_connection(&conn).unwrap();
+        initialize_schema(&mut conn).unwrap();
+        conn.execute(
+            "INSERT INTO products (name, price)
+             SELECT 'Rau muống', 15000 WHERE NOT EXISTS (SELECT 1 FROM products)",
+            [],
+        )
+        .unwrap();
+        Database {
+            conn: Mutex::new(conn),
+        }
+    }
+
+    fn payment_payload(key: &str, paid_cash: i64) -> CreatePaymentPayload {
+        priced_payload(key, 15000, paid_cash)
+    }
+
+    fn priced_payload(key: &str, unit_price: i64, paid_cash: i64) -> CreatePaymentPayload {
+        let total = unit_price * 2;
+        serde_json::from_value(serde_json::json!({
+            "cashierName": "Linh",
+            "subtotal": total,
+            "tax": 0,
+            "total": total,
+            "discount": 0,
+            "paidCash": paid_cash,
+            "changeDue": paid_cash - total,
+            "note": null,
+            "idempotencyKey": key,
+            "items": [{
+                "productId": 1,
+                "name": "Rau muống",
+                "quantity": 2.0,
+                "baseUnitPrice": unit_price
+            }]
+        }))
+        .unwrap()
+    }
+
+    fn submit_concurrently(databases: [&Database; 2], key: &str) -> Vec<PaymentRecord> {
+        let barrier = Barrier::new(databases.len());
+        thread::scope(|scope| {
+            let handles: Vec<_> = databases
+                .into_iter()
+                .map(|database| {
+                    let barrier = &barrier;
+                    scope.spawn(move || {
+                        barrier.wait();
+                        save_payment(database, payment_payload(key, 50000))
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .map(|handle| handle.join().unwrap().unwrap())
+                .collect()
+        })
+    }
+
+    fn count(database: &Database, sql: &str) -> i64 {
+        database
+            .lock()
+            .unwrap()
+            .query_row(sql, [], |row| row.get(0))
+            .unwrap()
+    }
+
+    fn assert_single_sale(database: &Database, records: &[PaymentRecord]) {
+        let first = &records[0];
+        for record in records {
+            assert_eq!(record.id, first.id);
+            assert_eq!(record.invoice_number, first.invoice_number);
+            assert_eq!(record.created_at, first.created_at);
+            assert_eq!(record.items.len(), 1);
+        }
+        assert_eq!(count(database, "SELECT COUNT(*) FROM payments"), 1);
+        assert_eq!(
+            count(
+                database,
+                "SELECT COUNT(DISTINCT invoice_number) FROM payments"
+            ),
+            1
+        );
+        assert_eq!(count(database, "SELECT COUNT(*) FROM payment_items"), 1);
+        assert_eq!(
+            count(
+                database,
+                "SELECT COUNT(*) FROM stock_movements WHERE reason = 'sale'"
+            ),
+            1
+        );
+        assert_eq!(
+            count(database, "SELECT last_value FROM invoice_sequences"),
+            1
+        );
+    }
+
+    // A retry with the same key replays the recorded sale; the race between
+    // two connections is covered below.
+    #[test]
+    fn repeated_submissions_replay_one_payment() {
+        let database = prepare_connection(Connection::open_in_memory().unwrap());
+        let records = [
+            save_payment(&database, payment_payload("checkout-1", 50000)).unwrap(),
+            save_payment(&database, payment_payload("checkout-1", 50000)).unwrap(),
+        ];
+        assert_single_sale(&database, &records);
+
+        let reused = save_payment(&database, payment_payload("checkout-1", 100000));
+        assert_eq!(
+            reused.err().map(|err| err.code),
+            Some("idempotency_key_reused")
+        );
+        let other = save_payment(&database, payment_payload("checkout-2", 50000)).unwrap();
+        assert_ne!(other.invoice_number, records[0].invoice_number);
+    }
+
+    // The register may still show the old price when a schedule falls due;
+    // the sale is charged the new one, while a retry of an earlier sale
+    // replays at the price it was recorded with.
+    #[test]
+    fn due_price_schedules_reach_the_sale() {
+        let database = prepare_connection(Connection::open_in_memory().unwrap());
+        let earlier = save_payment(&database, payment_payload("checkout-1", 50000)).unwrap();
+        database
+            .lock()
+            .unwrap()
+            .execute(
+                "INSERT INTO scheduled_prices (product_id, new_price, effective_at, created_by, created_at)
+                 VALUES (1, 18000, '2000-01-01 00:00', 'Linh', '2000-01-01 00:00:00')",
+                [],
+            )
+            .unwrap();
+
+        let stale = save_payment(&database, payment_payload("checkout-2", 50000));
+        assert_eq!(
+            stale.err().map(|err| err.code),
+            Some("payment_figure_mismatch")
+        );
+        assert_eq!(
+            count(&database, "SELECT price FROM products WHERE id = 1"),
+            18000
+        );
+
+        let replayed = save_payment(&database, payment_payload("checkout-1", 50000)).unwrap();
+        assert_eq!(replayed.id, earlier.id);
+        let current = save_payment(&database, priced_payload("checkout-2", 18000, 50000)).unwrap();
+        assert_eq!(current.total, 36000);
+    }
+
+    // Removes the database file and its WAL companions before and after use.
+    struct ScratchFile(PathBuf);
+
+    impl ScratchFile {
+        fn new(name: &str) -> Self {
+            let file = ScratchFile(std::env::temp_dir().join(format!(
+                "htx_pos_{}_{}.sqlite",
+                name,
+                std::process::id()
+            )));
+            file.remove();
+            file
+        }
+
+        fn remove(&self) {
+            for suffix in ["", "-wal", "-shm"] {
+                let mut path = self.0.clone().into_os_string();
+                path.push(suffix);
+                let _ = fs::remove_file(path);
+            }
+        }
+    }
+
+    impl Drop for ScratchFile {
+        fn drop(&mut self) {
+            self.remove();
+        }
+    }
+
+    // Two connections to one file, as when a retried IPC call races a second
+    // window: the loser of the insert falls back to replaying the winner.
+    #[test]
+    fn concurrent_duplicate_submissions_across_connections() {
+        let file = ScratchFile::new("idempotency");
+        let first = prepare_connection(Connection::open(&file.0).unwrap());
+        let second = prepare_connection(Connection::open(&file.0).unwrap());
+        let records = submit_concurrently([&first, &second], "checkout-1");
+        assert_single_sale(&first, &records);
+    }
+}
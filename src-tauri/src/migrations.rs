@@ -0,0 +1,751 @@
+use crate::error::{AppError, AppResult};
+use rusqlite::Connection;
+
+struct Migration {
+    version: i64,
+    description: &'static str,
+    apply: fn(&Connection) -> AppResult<()>,
+}
+
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "baseline schema",
+        apply: migrate_baseline,
+    },
+    Migration {
+        version: 2,
+        description: "settings and invoice number sequences",
+        apply: migrate_invoice_sequences,
+    },
+    Migration {
+        version: 3,
+        description: "payment idempotency keys",
+        apply: migrate_payment_idempotency,
+    },
+    Migration {
+        version: 4,
+        description: "payment voiding and audit log",
+        apply: migrate_payment_voids,
+    },
+    Migration {
+        version: 5,
+        description: "refunds against payments",
+        apply: migrate_refunds,
+    },
+    Migration {
+        version: 6,
+        description: "payment search indexes",
+        apply: migrate_payment_search_indexes,
+    },
+    Migration {
+        version: 7,
+        description: "local timestamps and business dates",
+        apply: migrate_business_dates,
+    },
+    Migration {
+        version: 8,
+        description: "unique product barcodes",
+        apply: migrate_unique_barcodes,
+    },
+    Migration {
+        version: 9,
+        description: "product archiving",
+        apply: migrate_product_archive,
+    },
+    Migration {
+        version: 10,
+        description: "product categories",
+        apply: migrate_categories,
+    },
+    Migration {
+        version: 11,
+        description: "stock ledger",
+        apply: migrate_stock_ledger,
+    },
+    Migration {
+        version: 12,
+        description: "suppliers, purchase orders and goods receipts",
+        apply: migrate_purchasing,
+    },
+    Migration {
+        version: 13,
+        description: "stocktake sessions and count sheets",
+        apply: migrate_stocktakes,
+    },
+    Migration {
+        version: 14,
+        description: "product cost price and sale line cost snapshot",
+        apply: migrate_cost_price,
+    },
+    Migration {
+        version: 15,
+        description: "price history and scheduled price changes",
+        apply: migrate_price_changes,
+    },
+    Migration {
+        version: 16,
+        description: "selling units with conversion factors",
+        apply: migrate_product_units,
+    },
+];
+
+pub(crate) fn latest_version() -> i64 {
+    MIGRATIONS
+        .last()
+        .map(|migration| migration.version)
+        .unwrap_or(0)
+}
+
+fn current_version(conn: &Connection) -> AppResult<i64> {
+    conn.pragma_query_value(None, "user_version", |row| row.get(0))
+        .map_err(AppError::from)
+}
+
+pub(crate) fn run_migrations(conn: &mut Connection) -> AppResult<()> {
+    let current = current_version(conn)?;
+    let latest = latest_version();
+    if current > latest {
+        return Err(AppError::storage(
+            "schema_too_new",
+            format!(
+                "Database schema version {} is newer than this application supports ({})",
+                current, latest
+            ),
+        ));
+    }
+    for migration in MIGRATIONS
+        .iter()
+        .filter(|migration| migration.version > current)
+    {
+        let tx = conn.transaction()?;
+        (migration.apply)(&tx).map_err(|err| {
+            AppError::storage(
+                "migration_failed",
+                format!(
+                    "Migration {} ({}) failed: {}",
+                    migration.version, migration.description, err.message
+                ),
+            )
+        })?;
+        tx.pragma_update(None, "user_version", migration.version)?;
+        tx.commit()?;
+    }
+    Ok(())
+}
+
+fn column_exists(conn: &Connection, table: &str, column: &str) -> AppResult<bool> {
+    let sql = format!("PRAGMA table_info({})", table);
+    let mut statement = conn.prepare(&sql)?;
+    let mut rows = statement.query([])?;
+    while let Some(row) = rows.next()? {
+        let name: String = row.get(1)?;
+        if name.eq_ignore_ascii_case(column) {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+fn add_column_if_missing(
+    conn: &Connection,
+    table: &str,
+    column: &str,
+    definition: &str,
+) -> AppResult<()> {
+    if column_exists(conn, table, column)? {
+        return Ok(());
+    }
+    let sql = format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition);
+    conn.execute(sql.as_str(), [])?;
+    Ok(())
+}
+
+fn ensure_payment_item_columns(conn: &Connection) -> AppResult<()> {
+    add_column_if_missing(conn, "payment_items", "quantity_decimal", "REAL")?;
+    add_column_if_missing(conn, "payment_items", "base_unit_price", "INTEGER")?;
+    add_column_if_missing(conn, "payment_items", "edited_unit_price", "INTEGER")?;
+    add_column_if_missing(conn, "payment_items", "line_subtotal", "INTEGER")?;
+    add_column_if_missing(
+        conn,
+        "payment_items",
+        "line_discount",
+        "INTEGER NOT NULL DEFAULT 0",
+    )?;
+    Ok(())
+}
+
+/// Creates the original tables and adopts databases written before schema
+/// versioning existed, whose `payment_items` may lack the pricing columns.
+fn migrate_baseline(conn: &Connection) -> AppResult<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS products (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL,
+            price INTEGER NOT NULL,
+            barcode TEXT,
+            visible INTEGER NOT NULL DEFAULT 1,
+            quick_display INTEGER NOT NULL DEFAULT 0,
+            display_order INTEGER NOT NULL DEFAULT 1,
+            created_at TEXT DEFAULT CURRENT_TIMESTAMP
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS payments (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            invoice_number TEXT NOT NULL,
+            cashier_name TEXT NOT NULL,
+            subtotal INTEGER NOT NULL,
+            tax INTEGER NOT NULL,
+            total INTEGER NOT NULL,
+            discount INTEGER NOT NULL DEFAULT 0,
+            paid_cash INTEGER NOT NULL,
+            change_due INTEGER NOT NULL,
+            note TEXT,
+            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS payment_items (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            payment_id INTEGER NOT NULL REFERENCES payments(id) ON DELETE CASCADE,
+            product_id INTEGER,
+            name TEXT NOT NULL,
+            quantity INTEGER NOT NULL,
+            price INTEGER NOT NULL,
+            quantity_decimal REAL,
+            base_unit_price INTEGER,
+            edited_unit_price INTEGER,
+            line_subtotal INTEGER,
+            line_discount INTEGER NOT NULL DEFAULT 0
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS cashiers (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            code TEXT NOT NULL UNIQUE,
+            name TEXT NOT NULL,
+            role TEXT NOT NULL,
+            last_active TEXT,
+            require_pin INTEGER NOT NULL DEFAULT 0,
+            pin TEXT,
+            display_order INTEGER NOT NULL DEFAULT 1,
+            is_active INTEGER NOT NULL DEFAULT 1,
+            created_at TEXT DEFAULT CURRENT_TIMESTAMP
+        )",
+        [],
+    )?;
+    ensure_payment_item_columns(conn)
+}
+
+fn migrate_invoice_sequences(conn: &Connection) -> AppResult<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS settings (
+            key TEXT PRIMARY KEY,
+            value TEXT NOT NULL,
+            updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+        );
+        CREATE TABLE IF NOT EXISTS invoice_sequences (
+            scope TEXT PRIMARY KEY,
+            last_value INTEGER NOT NULL
+        );
+        UPDATE payments
+        SET invoice_number = invoice_number || '-' || id
+        WHERE id NOT IN (
+            SELECT MIN(id) FROM payments GROUP BY invoice_number
+        );
+        CREATE UNIQUE INDEX IF NOT EXISTS idx_payments_invoice_number
+            ON payments(invoice_number);",
+    )?;
+    Ok(())
+}
+
+fn migrate_payment_idempotency(conn: &Connection) -> AppResult<()> {
+    conn.execute_batch(
+        "ALTER TABLE payments ADD COLUMN idempotency_key TEXT;
+        CREATE UNIQUE INDEX IF NOT EXISTS idx_payments_idempotency_key
+            ON payments(idempotency_key)
+            WHERE idempotency_key IS NOT NULL;",
+    )?;
+    Ok(())
+}
+
+fn migrate_payment_voids(conn: &Connection) -> AppResult<()> {
+    conn.execute_batch(
+        "ALTER TABLE payments ADD COLUMN voided_at TEXT;
+        ALTER TABLE payments ADD COLUMN voided_by TEXT;
+        ALTER TABLE payments ADD COLUMN void_reason TEXT;
+        CREATE TABLE IF NOT EXISTS audit_log (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            entity TEXT NOT NULL,
+            entity_id INTEGER NOT NULL,
+            action TEXT NOT NULL,
+            actor TEXT NOT NULL,
+            reason TEXT,
+            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+        );
+        CREATE INDEX IF NOT EXISTS idx_audit_log_entity
+            ON audit_log(entity, entity_id);",
+    )?;
+    Ok(())
+}
+
+fn migrate_refunds(conn: &Connection) -> AppResult<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS refunds (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            payment_id INTEGER NOT NULL REFERENCES payments(id),
+            refund_number TEXT NOT NULL UNIQUE,
+            cashier_name TEXT NOT NULL,
+            reason TEXT,
+            total INTEGER NOT NULL,
+            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+        );
+        CREATE TABLE IF NOT EXISTS refund_items (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            refund_id INTEGER NOT NULL REFERENCES refunds(id) ON DELETE CASCADE,
+            payment_item_id INTEGER NOT NULL REFERENCES payment_items(id),
+            product_id INTEGER,
+            name TEXT NOT NULL,
+            quantity REAL NOT NULL,
+            unit_price INTEGER NOT NULL,
+            amount INTEGER NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS idx_refunds_payment_id ON refunds(payment_id);
+        CREATE INDEX IF NOT EXISTS idx_refund_items_payment_item_id
+            ON refund_items(payment_item_id);",
+    )?;
+    Ok(())
+}
+
+fn migrate_payment_search_indexes(conn: &Connection) -> AppResult<()> {
+    conn.execute_batch(
+        "CREATE INDEX IF NOT EXISTS idx_payments_created_at ON payments(created_at, id);
+        CREATE INDEX IF NOT EXISTS idx_payments_cashier_created_at
+            ON payments(cashier_name, created_at);
+        CREATE INDEX IF NOT EXISTS idx_payment_items_payment_id ON payment_items(payment_id);
+        CREATE INDEX IF NOT EXISTS idx_payment_items_product_id ON payment_items(product_id);",
+    )?;
+    Ok(())
+}
+
+// Older rows were written with SQLite's CURRENT_TIMESTAMP, which is UTC without
+// an offset. They are rewritten in the default store timezone (UTC+7, no DST)
+// and given a business date with the default midnight cutover.
+fn migrate_business_dates(conn: &Connection) -> AppResult<()> {
+    conn.execute_batch(
+        "ALTER TABLE payments ADD COLUMN business_date TEXT;
+        ALTER TABLE refunds ADD COLUMN business_date TEXT;
+        UPDATE payments
+        SET created_at = datetime(created_at, '+7 hours') || '+07:00'
+        WHERE length(created_at) = 19;
+        UPDATE payments
+        SET voided_at = datetime(voided_at, '+7 hours') || '+07:00'
+        WHERE length(voided_at) = 19;
+        UPDATE refunds
+        SET created_at = datetime(created_at, '+7 hours') || '+07:00'
+        WHERE length(created_at) = 19;
+        UPDATE payments SET business_date = substr(created_at, 1, 10);
+        UPDATE refunds SET business_date = substr(created_at, 1, 10);
+        CREATE INDEX IF NOT EXISTS idx_payments_business_date
+            ON payments(business_date, created_at);
+        CREATE INDEX IF NOT EXISTS idx_refunds_business_date ON refunds(business_date);",
+    )?;
+    Ok(())
+}
+
+// Products that shared a barcode keep it on the oldest row; the others lose it
+// and the removed code is kept in the audit log so it can be reassigned.
+fn migrate_unique_barcodes(conn: &Connection) -> AppResult<()> {
+    conn.execute_batch(
+        "UPDATE products SET barcode = NULL WHERE trim(barcode) = '';
+        INSERT INTO audit_log (entity, entity_id, action, actor, reason)
+        SELECT 'product', id, 'barcode_cleared', 'system', 'Duplicate barcode ' || barcode
+        FROM products
+        WHERE barcode IS NOT NULL
+          AND id NOT IN (
+              SELECT MIN(id) FROM products WHERE barcode IS NOT NULL GROUP BY barcode
+          );
+        UPDATE products
+        SET barcode = NULL
+        WHERE barcode IS NOT NULL
+          AND id NOT IN (
+              SELECT MIN(id) FROM products WHERE barcode IS NOT NULL GROUP BY barcode
+          );
+        CREATE UNIQUE INDEX IF NOT EXISTS idx_products_barcode
+            ON products(barcode)
+            WHERE barcode IS NOT NULL;",
+    )?;
+    Ok(())
+}
+
+fn migrate_product_archive(conn: &Connection) -> AppResult<()> {
+    conn.execute_batch("ALTER TABLE products ADD COLUMN archived_at TEXT;")?;
+    Ok(())
+}
+
+fn migrate_categories(conn: &Connection) -> AppResult<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS categories (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL,
+            parent_id INTEGER REFERENCES categories(id),
+            display_order INTEGER NOT NULL DEFAULT 1,
+            color TEXT,
+            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+        );
+        ALTER TABLE products ADD COLUMN category_id INTEGER REFERENCES categories(id);
+        CREATE INDEX IF NOT EXISTS idx_categories_parent_id ON categories(parent_id);
+        CREATE INDEX IF NOT EXISTS idx_products_category_id ON products(category_id);",
+    )?;
+    Ok(())
+}
+
+fn migrate_stock_ledger(conn: &Connection) -> AppResult<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS stock_movements (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            product_id INTEGER NOT NULL REFERENCES products(id),
+            quantity REAL NOT NULL,
+            reason TEXT NOT NULL,
+            source_id INTEGER,
+            actor TEXT,
+            note TEXT,
+            created_at TEXT NOT NULL,
+            business_date TEXT NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS idx_stock_movements_product_id
+            ON stock_movements(product_id, id);
+        CREATE INDEX IF NOT EXISTS idx_stock_movements_source
+            ON stock_movements(reason, source_id);",
+    )?;
+    Ok(())
+}
+
+fn migrate_purchasing(conn: &Connection) -> AppResult<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS suppliers (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL,
+            phone TEXT,
+            address TEXT,
+            note TEXT,
+            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+        );
+        CREATE TABLE IF NOT EXISTS purchase_orders (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            order_number TEXT NOT NULL UNIQUE,
+            supplier_id INTEGER NOT NULL REFERENCES suppliers(id),
+            status TEXT NOT NULL DEFAULT 'open',
+            note TEXT,
+            created_by TEXT NOT NULL,
+            created_at TEXT NOT NULL,
+            business_date TEXT NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS purchase_order_lines (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            order_id INTEGER NOT NULL REFERENCES purchase_orders(id) ON DELETE CASCADE,
+            product_id INTEGER NOT NULL REFERENCES products(id),
+            quantity_ordered REAL NOT NULL,
+            quantity_received REAL NOT NULL DEFAULT 0,
+            unit_cost INTEGER NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS goods_receipts (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            order_id INTEGER REFERENCES purchase_orders(id),
+            supplier_id INTEGER REFERENCES suppliers(id),
+            received_by TEXT NOT NULL,
+            note TEXT,
+            total_cost INTEGER NOT NULL,
+            created_at TEXT NOT NULL,
+            business_date TEXT NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS goods_receipt_lines (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            receipt_id INTEGER NOT NULL REFERENCES goods_receipts(id) ON DELETE CASCADE,
+            order_line_id INTEGER REFERENCES purchase_order_lines(id),
+            product_id INTEGER NOT NULL REFERENCES products(id),
+            quantity REAL NOT NULL,
+            unit_cost INTEGER NOT NULL,
+            line_cost INTEGER NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS idx_purchase_orders_status
+            ON purchase_orders(status, created_at);
+        CREATE INDEX IF NOT EXISTS idx_purchase_order_lines_order_id
+            ON purchase_order_lines(order_id);
+        CREATE INDEX IF NOT EXISTS idx_goods_receipt_lines_receipt_id
+            ON goods_receipt_lines(receipt_id);",
+    )?;
+    Ok(())
+}
+
+fn migrate_stocktakes(conn: &Connection) -> AppResult<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS stocktakes (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            status TEXT NOT NULL DEFAULT 'open',
+            note TEXT,
+            category_id INTEGER REFERENCES categories(id) ON DELETE SET NULL,
+            created_by TEXT NOT NULL,
+            created_at TEXT NOT NULL,
+            business_date TEXT NOT NULL,
+            closed_by TEXT,
+            closed_at TEXT
+        );
+        CREATE TABLE IF NOT EXISTS stocktake_lines (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            stocktake_id INTEGER NOT NULL REFERENCES stocktakes(id) ON DELETE CASCADE,
+            product_id INTEGER NOT NULL REFERENCES products(id),
+            expected_quantity REAL NOT NULL,
+            counted_quantity REAL,
+            unit_value INTEGER NOT NULL,
+            counted_by TEXT,
+            counted_at TEXT,
+            UNIQUE (stocktake_id, product_id)
+        );
+        CREATE INDEX IF NOT EXISTS idx_stocktakes_status ON stocktakes(status);",
+    )?;
+    Ok(())
+}
+
+fn migrate_cost_price(conn: &Connection) -> AppResult<()> {
+    conn.execute_batch(
+        "ALTER TABLE products ADD COLUMN cost_price INTEGER;
+        ALTER TABLE payment_items ADD COLUMN unit_cost INTEGER;",
+    )?;
+    Ok(())
+}
+
+fn migrate_price_changes(conn: &Connection) -> AppResult<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS price_changes (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            product_id INTEGER NOT NULL REFERENCES products(id) ON DELETE CASCADE,
+            old_price INTEGER NOT NULL,
+            new_price INTEGER NOT NULL,
+            source TEXT NOT NULL,
+            changed_by TEXT,
+            changed_at TEXT NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS scheduled_prices (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            product_id INTEGER NOT NULL REFERENCES products(id) ON DELETE CASCADE,
+            new_price INTEGER NOT NULL,
+            effective_at TEXT NOT NULL,
+            note TEXT,
+            created_by TEXT NOT NULL,
+            created_at TEXT NOT NULL,
+            applied_at TEXT,
+            cancelled_by TEXT,
+            cancelled_at TEXT
+        );
+        CREATE INDEX IF NOT EXISTS idx_price_changes_product_id
+            ON price_changes(product_id, id);
+        CREATE INDEX IF NOT EXISTS idx_scheduled_prices_pending
+            ON scheduled_prices(effective_at)
+            WHERE applied_at IS NULL AND cancelled_at IS NULL;",
+    )?;
+    Ok(())
+}
+
+fn migrate_product_units(conn: &Connection) -> AppResult<()> {
+    conn.execute_batch(
+        "ALTER TABLE products ADD COLUMN unit_name TEXT;
+        CREATE TABLE IF NOT EXISTS product_units (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            product_id INTEGER NOT NULL REFERENCES products(id) ON DELETE CASCADE,
+            name TEXT NOT NULL,
+            factor REAL NOT NULL,
+            price INTEGER NOT NULL,
+            barcode TEXT,
+            display_order INTEGER NOT NULL DEFAULT 1,
+            UNIQUE (product_id, name)
+        );
+        CREATE UNIQUE INDEX IF NOT EXISTS idx_product_units_barcode
+            ON product_units(barcode) WHERE barcode IS NOT NULL;
+        ALTER TABLE payment_items ADD COLUMN unit_id INTEGER;
+        ALTER TABLE payment_items ADD COLUMN unit_name TEXT;
+        ALTER TABLE payment_items ADD COLUMN unit_factor REAL NOT NULL DEFAULT 1;",
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SEED_DATABASE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../data/products.sqlite");
+
+    // Copies the shipped seed into memory table by table so the file on disk
+    // is never touched.
+    fn seed_in_memory() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute("ATTACH DATABASE ?1 AS seed", [SEED_DATABASE])
+            .unwrap();
+        let tables: Vec<(String, String)> = conn
+            .prepare(
+                "SELECT name, sql FROM seed.sqlite_master
+                 WHERE type = 'table' AND name NOT LIKE 'sqlite_%'",
+            )
+            .unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        for (name, sql) in tables {
+            conn.execute_batch(&sql).unwrap();
+            conn.execute_batch(&format!(
+                "INSERT INTO main.{name} SELECT * FROM seed.{name}"
+            ))
+            .unwrap();
+        }
+        conn.execute_batch("DETACH DATABASE seed").unwrap();
+        conn
+    }
+
+    fn legacy_in_memory() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE products (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                name TEXT NOT NULL,
+                price INTEGER NOT NULL,
+                barcode TEXT,
+                visible INTEGER NOT NULL DEFAULT 1,
+                quick_display INTEGER NOT NULL DEFAULT 0,
+                display_order INTEGER NOT NULL DEFAULT 1,
+                created_at TEXT DEFAULT CURRENT_TIMESTAMP
+            );
+            CREATE TABLE payments (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                invoice_number TEXT NOT NULL,
+                cashier_name TEXT NOT NULL,
+                subtotal INTEGER NOT NULL,
+                tax INTEGER NOT NULL,
+                total INTEGER NOT NULL,
+                discount INTEGER NOT NULL DEFAULT 0,
+                paid_cash INTEGER NOT NULL,
+                change_due INTEGER NOT NULL,
+                note TEXT,
+                created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+            );
+            CREATE TABLE payment_items (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                payment_id INTEGER NOT NULL,
+                product_id INTEGER,
+                name TEXT NOT NULL,
+                quantity INTEGER NOT NULL,
+                price INTEGER NOT NULL
+            );
+            INSERT INTO products (name, price, barcode) VALUES ('Rau muống', 15000, '111');
+            INSERT INTO products (name, price, barcode) VALUES ('Rau cải', 12000, '111');
+            INSERT INTO payments (
+                invoice_number, cashier_name, subtotal, tax, total, paid_cash, change_due,
+                created_at
+            )
+            VALUES ('HD1', 'Linh', 30000, 0, 30000, 50000, 20000, '2024-03-01 18:30:00'),
+                   ('HD1', 'Vi', 12000, 0, 12000, 12000, 0, '2024-03-02 02:00:00');
+            INSERT INTO payment_items (payment_id, product_id, name, quantity, price)
+            VALUES (1, 1, 'Rau muống', 2, 15000), (2, 2, 'Rau cải', 1, 12000);",
+        )
+        .unwrap();
+        conn
+    }
+
+    fn payment_dates(conn: &Connection) -> Vec<(String, String)> {
+        conn.prepare("SELECT created_at, business_date FROM payments ORDER BY id")
+            .unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn migrates_shipped_seed_to_latest() {
+        let mut conn = seed_in_memory();
+        let payments_before: i64 = conn
+            .query_row("SELECT COUNT(*) FROM payments", [], |row| row.get(0))
+            .unwrap();
+        run_migrations(&mut conn).unwrap();
+        assert_eq!(current_version(&conn).unwrap(), latest_version());
+        let payments_after: i64 = conn
+            .query_row("SELECT COUNT(*) FROM payments", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(payments_after, payments_before);
+        for (created_at, business_date) in payment_dates(&conn) {
+            assert!(created_at.ends_with("+07:00"), "{}", created_at);
+            assert_eq!(business_date, created_at[..10]);
+        }
+        run_migrations(&mut conn).unwrap();
+        assert_eq!(current_version(&conn).unwrap(), latest_version());
+    }
+
+    #[test]
+    fn migrates_legacy_payment_items_layout() {
+        let mut conn = legacy_in_memory();
+        run_migrations(&mut conn).unwrap();
+        assert_eq!(current_version(&conn).unwrap(), latest_version());
+        for column in [
+            "quantity_decimal",
+            "base_unit_price",
+            "edited_unit_price",
+            "line_subtotal",
+            "line_discount",
+            "unit_cost",
+            "unit_factor",
+        ] {
+            assert!(column_exists(&conn, "payment_items", column).unwrap());
+        }
+        assert_eq!(
+            payment_dates(&conn),
+            vec![
+                (
+                    "2024-03-02 01:30:00+07:00".to_string(),
+                    "2024-03-02".to_string()
+                ),
+                (
+                    "2024-03-02 09:00:00+07:00".to_string(),
+                    "2024-03-02".to_string()
+                ),
+            ]
+        );
+        let invoice_numbers: Vec<String> = conn
+            .prepare("SELECT invoice_number FROM payments ORDER BY id")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(invoice_numbers, ["HD1", "HD1-2"]);
+        let barcodes: Vec<Option<String>> = conn
+            .prepare("SELECT barcode FROM products ORDER BY id")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(barcodes, [Some("111".to_string()), None]);
+        let (line_discount, unit_factor): (i64, f64) = conn
+            .query_row(
+                "SELECT line_discount, unit_factor FROM payment_items WHERE id = 1",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!((line_discount, unit_factor), (0, 1.0));
+    }
+
+    #[test]
+    fn refuses_database_newer_than_binary() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.pragma_update(None, "user_version", latest_version() + 1)
+            .unwrap();
+        let err = run_migrations(&mut conn).unwrap_err();
+        assert_eq!(err.code, "schema_too_new");
+        assert_eq!(current_version(&conn).unwrap(), latest_version() + 1);
+    }
+}
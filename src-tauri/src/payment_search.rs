@@ -0,0 +1,428 @@
+use crate::error::{AppError, AppResult};
+use crate::{hydrate_payment_records, parse_payment_row, Database, PaymentRecord};
+use rusqlite::{params_from_iter, types::Value, Connection};
+use serde::{Deserialize, Serialize};
+
+const DEFAULT_PAGE_SIZE: i64 = 50;
+const MAX_PAGE_SIZE: i64 = 500;
+
+// Keyset cursor shared by every listing that runs newest first on
+// (created_at, id): payments, refunds and purchase orders.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct PageCursor {
+    pub(crate) created_at: String,
+    pub(crate) id: i64,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct SearchPaymentsPayload {
+    date_from: Option<String>,
+    date_to: Option<String>,
+    cashier_name: Option<String>,
+    invoice_prefix: Option<String>,
+    min_total: Option<i64>,
+    max_total: Option<i64>,
+    product_id: Option<i64>,
+    product_name: Option<String>,
+    note: Option<String>,
+    include_voided: Option<bool>,
+    cursor: Option<PageCursor>,
+    limit: Option<i64>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct PaymentPage {
+    items: Vec<PaymentRecord>,
+    total_count: i64,
+    next_cursor: Option<PageCursor>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct Page<T> {
+    pub(crate) items: Vec<T>,
+    pub(crate) next_cursor: Option<PageCursor>,
+}
+
+pub(crate) fn page_limit(limit: Option<i64>) -> AppResult<i64> {
+    let limit = limit.unwrap_or(DEFAULT_PAGE_SIZE);
+    if !(1..=MAX_PAGE_SIZE).contains(&limit) {
+        return Err(AppError::validation(
+            "search_limit_invalid",
+            "limit",
+            format!("Page size must be between 1 and {}", MAX_PAGE_SIZE),
+        ));
+    }
+    Ok(limit)
+}
+
+// Callers fetch one row past the limit; it is dropped here and only tells
+// whether a next page exists.
+pub(crate) fn take_page<T>(
+    rows: &mut Vec<T>,
+    limit: i64,
+    key: impl Fn(&T) -> PageCursor,
+) -> Option<PageCursor> {
+    let has_more = rows.len() as i64 > limit;
+    rows.truncate(limit as usize);
+    rows.last().filter(|_| has_more).map(key)
+}
+
+#[derive(Default)]
+struct PaymentFilter {
+    clauses: Vec<String>,
+    params: Vec<Value>,
+}
+
+impl PaymentFilter {
+    fn push(&mut self, clause: &str, values: impl IntoIterator<Item = Value>) {
+        self.clauses.push(clause.to_string());
+        self.params.extend(values);
+    }
+
+    fn where_sql(&self) -> String {
+        if self.clauses.is_empty() {
+            return String::new();
+        }
+        format!("WHERE {}", self.clauses.join(" AND "))
+    }
+}
+
+fn clean_text(value: Option<String>) -> Option<String> {
+    value
+        .map(|text| text.trim().to_string())
+        .filter(|text| !text.is_empty())
+}
+
+fn contains_pattern(text: &str) -> String {
+    let escaped = text
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+    format!("%{}%", escaped)
+}
+
+pub(crate) fn normalize_date(
+    conn: &Connection,
+    field: &str,
+    value: Option<String>,
+) -> AppResult<Option<String>> {
+    let Some(date) = clean_text(value) else {
+        return Ok(None);
+    };
+    let valid: bool = conn.query_row("SELECT date(?1) IS ?1", [date.as_str()], |row| row.get(0))?;
+    if !valid {
+        return Err(AppError::validation(
+            "search_date_invalid",
+            field,
+            "Dates must use the YYYY-MM-DD format",
+        ));
+    }
+    Ok(Some(date))
+}
+
+fn build_filter(conn: &Connection, payload: SearchPaymentsPayload) -> AppResult<PaymentFilter> {
+    let mut filter = PaymentFilter::default();
+    let date_from = normalize_date(conn, "dateFrom", payload.date_from)?;
+    let date_to = normalize_date(conn, "dateTo", payload.date_to)?;
+    if let (Some(from), Some(to)) = (&date_from, &date_to) {
+        if from > to {
+            return Err(AppError::validation(
+                "search_date_range_invalid",
+                "dateTo",
+                "End date cannot be before start date",
+            ));
+        }
+    }
+    if let Some(from) = date_from {
+        filter.push("p.business_date >= ?", [Value::Text(from)]);
+    }
+    if let Some(to) = date_to {
+        filter.push("p.business_date <= ?", [Value::Text(to)]);
+    }
+    if let Some(cashier) = clean_text(payload.cashier_name) {
+        filter.push("p.cashier_name = ?", [Value::Text(cashier)]);
+    }
+    // A range instead of LIKE so the unique invoice number index can be used.
+    if let Some(prefix) = clean_text(payload.invoice_prefix) {
+        let upper = format!("{}\u{10FFFF}", prefix);
+        filter.push(
+            "p.invoice_number >= ? AND p.invoice_number < ?",
+            [Value::Text(prefix), Value::Text(upper)],
+        );
+    }
+    if let (Some(min), Some(max)) = (payload.min_total, payload.max_total) {
+        if min > max {
+            return Err(AppError::validation(
+                "search_amount_range_invalid",
+                "maxTotal",
+                "Maximum amount cannot be below minimum amount",
+            ));
+        }
+    }
+    if let Some(min) = payload.min_total {
+        filter.push("p.total >= ?", [Value::Integer(min)]);
+    }
+    if let Some(max) = payload.max_total {
+        filter.push("p.total <= ?", [Value::Integer(max)]);
+    }
+    if let Some(product_id) = payload.product_id {
+        filter.push(
+            "EXISTS (SELECT 1 FROM payment_items pi
+                     WHERE pi.payment_id = p.id AND pi.product_id = ?)",
+            [Value::Integer(product_id)],
+        );
+    }
+    if let Some(name) = clean_text(payload.product_name) {
+        filter.push(
+            "EXISTS (SELECT 1 FROM payment_items pi
+                     WHERE pi.payment_id = p.id AND pi.name LIKE ? ESCAPE '\\')",
+            [Value::Text(contains_pattern(&name))],
+        );
+    }
+    if let Some(note) = clean_text(payload.note) {
+        filter.push(
+            "p.note LIKE ? ESCAPE '\\'",
+            [Value::Text(contains_pattern(&note))],
+        );
+    }
+    if !payload.include_voided.unwrap_or(true) {
+        filter.push("p.voided_at IS NULL", []);
+    }
+    Ok(filter)
+}
+
+#[tauri::command]
+pub(crate) fn search_payments(
+    database: tauri::State<'_, Database>,
+    payload: Option<SearchPaymentsPayload>,
+) -> AppResult<PaymentPage> {
+    let conn = database.lock()?;
+    find_payments(&conn, payload.unwrap_or_default())
+}
+
+fn find_payments(conn: &Connection, mut payload: SearchPaymentsPayload) -> AppResult<PaymentPage> {
+    let limit = page_limit(payload.limit)?;
+    let cursor = payload.cursor.take();
+    let mut filter = build_filter(conn, payload)?;
+
+    let total_count: i64 = conn.query_row(
+        &format!("SELECT COUNT(*) FROM payments p {}", filter.where_sql()),
+        params_from_iter(filter.params.iter()),
+        |row| row.get(0),
+    )?;
+
+    if let Some(cursor) = cursor {
+        // A row value lets SQLite seek straight to the cursor in the
+        // (created_at, id) index instead of scanning from the newest sale.
+        filter.push(
+            "(p.created_at, p.id) < (?, ?)",
+            [Value::Text(cursor.created_at), Value::Integer(cursor.id)],
+        );
+    }
+    filter.params.push(Value::Integer(limit + 1));
+    let mut statement = conn.prepare(&format!(
+        "SELECT p.id, p.invoice_number, p.cashier_name, p.subtotal, p.tax, p.total,
+                p.discount, p.paid_cash, p.change_due, p.note, p.created_at,
+                p.voided_at, p.voided_by, p.void_reason, p.business_date
+         FROM payments p
+         {}
+         ORDER BY p.created_at DESC, p.id DESC
+         LIMIT ?",
+        filter.where_sql()
+    ))?;
+    let mut rows = statement
+        .query_map(params_from_iter(filter.params.iter()), parse_payment_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+    let next_cursor = take_page(&mut rows, limit, |row| PageCursor {
+        created_at: row.created_at.clone(),
+        id: row.id,
+    });
+    let items = hydrate_payment_records(conn, rows)?;
+    Ok(PaymentPage {
+        items,
+        total_count,
+        next_cursor,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{initialize_schema, list_payment_rows};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::{Duration, Instant};
+
+    const GENERATED_SALES: i64 = 300_000;
+    const ROUNDS: u32 = 20;
+    // Generous enough for a slow till, far below a full scan of the table.
+    const PAGE_BUDGET: Duration = Duration::from_millis(50);
+
+    static ITEM_QUERIES: AtomicUsize = AtomicUsize::new(0);
+
+    fn count_item_queries(sql: &str) {
+        if sql.contains("FROM payment_items") {
+            ITEM_QUERIES.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    // One sale a minute from the start of 2024, two lines each, spread over
+    // four cashiers, all on the store clock like live data.
+    fn generate_sales(count: i64) -> Connection {
+        let mut conn = Connection::open_in_memory().unwrap();
+        initialize_schema(&mut conn).unwrap();
+        conn.execute_batch(&format!(
+            "BEGIN;
+            WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < {count})
+            INSERT INTO payments (
+                invoice_number, cashier_name, subtotal, tax, total, discount,
+                paid_cash, change_due, created_at, business_date
+            )
+            SELECT 'HD' || printf('%07d', i),
+                   CASE i % 4 WHEN 0 THEN 'Linh' WHEN 1 THEN 'Vi' WHEN 2 THEN 'An' ELSE 'Binh' END,
+                   30000, 0, 30000, 0, 50000, 20000,
+                   datetime('2024-01-01', '+' || i || ' minutes') || '+07:00',
+                   date('2024-01-01', '+' || i || ' minutes')
+            FROM n;
+            INSERT INTO payment_items (
+                payment_id, name, quantity, price, quantity_decimal, base_unit_price, line_subtotal
+            )
+            SELECT id, 'Rau muống', 1, 15000, 1.0, 15000, 15000 FROM payments;
+            INSERT INTO payment_items (
+                payment_id, name, quantity, price, quantity_decimal, base_unit_price, line_subtotal
+            )
+            SELECT id, 'Rau cải', 1, 15000, 1.0, 15000, 15000 FROM payments;
+            COMMIT;
+            ANALYZE;"
+        ))
+        .unwrap();
+        conn
+    }
+
+    // The listing as it was before items were batched: one items query per
+    // payment, over the same page of payments.
+    fn list_one_by_one(conn: &Connection) -> usize {
+        let ids: Vec<i64> = conn
+            .prepare("SELECT id FROM payments ORDER BY created_at DESC, id DESC LIMIT 200")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        let mut items = 0;
+        for id in ids {
+            let mut statement = conn
+                .prepare(
+                    "SELECT id, name, quantity, price FROM payment_items WHERE payment_id = ?1",
+                )
+                .unwrap();
+            items += statement
+                .query_map([id], |row| row.get::<_, i64>(0))
+                .unwrap()
+                .count();
+        }
+        items
+    }
+
+    fn average(rounds: u32, mut run: impl FnMut()) -> Duration {
+        run();
+        let started = Instant::now();
+        for _ in 0..rounds {
+            run();
+        }
+        started.elapsed() / rounds
+    }
+
+    fn search(conn: &Connection, payload: serde_json::Value) -> PaymentPage {
+        find_payments(conn, serde_json::from_value(payload).unwrap()).unwrap()
+    }
+
+    // Timing run over a generated database, kept out of the normal test run:
+    // cargo test --release -- --ignored payment_listing_benchmark
+    #[test]
+    #[ignore]
+    fn payment_listing_benchmark() {
+        let mut conn = generate_sales(GENERATED_SALES);
+
+        conn.trace(Some(count_item_queries));
+        ITEM_QUERIES.store(0, Ordering::Relaxed);
+        let rows = list_payment_rows(&conn).unwrap();
+        let records = hydrate_payment_records(&conn, rows).unwrap();
+        assert_eq!(records.len(), 200);
+        assert!(records.iter().all(|record| record.items.len() == 2));
+        assert_eq!(ITEM_QUERIES.load(Ordering::Relaxed), 1);
+        ITEM_QUERIES.store(0, Ordering::Relaxed);
+        assert_eq!(list_one_by_one(&conn), 400);
+        assert_eq!(ITEM_QUERIES.load(Ordering::Relaxed), 200);
+        conn.trace(None);
+
+        let one_by_one = average(ROUNDS, || assert_eq!(list_one_by_one(&conn), 400));
+        let batched = average(ROUNDS, || {
+            let rows = list_payment_rows(&conn).unwrap();
+            hydrate_payment_records(&conn, rows).unwrap();
+        });
+        assert!(
+            batched < one_by_one,
+            "batched {:?}, one by one {:?}",
+            batched,
+            one_by_one
+        );
+
+        let first_page = search(&conn, serde_json::json!({ "limit": 50 }));
+        assert_eq!(first_page.total_count, GENERATED_SALES);
+        let mut cursor = first_page.next_cursor.clone();
+        let walk = average(1, || {
+            for _ in 0..100 {
+                let page = search(&conn, serde_json::json!({ "limit": 50, "cursor": cursor }));
+                assert_eq!(page.items.len(), 50);
+                cursor = page.next_cursor;
+            }
+        }) / 100;
+        let day = average(ROUNDS, || {
+            let page = search(
+                &conn,
+                serde_json::json!({ "dateFrom": "2024-03-01", "dateTo": "2024-03-01" }),
+            );
+            assert_eq!(page.total_count, 1440);
+        });
+        let cashier_day = average(ROUNDS, || {
+            let page = search(
+                &conn,
+                serde_json::json!({
+                    "dateFrom": "2024-03-01",
+                    "dateTo": "2024-03-01",
+                    "cashierName": "Linh"
+                }),
+            );
+            assert_eq!(page.total_count, 360);
+        });
+        let deep = {
+            let mut deep_cursor = None;
+            for _ in 0..2000 {
+                let page = search(
+                    &conn,
+                    serde_json::json!({ "limit": 100, "cursor": deep_cursor }),
+                );
+                deep_cursor = page.next_cursor;
+            }
+            average(ROUNDS, || {
+                let page = search(
+                    &conn,
+                    serde_json::json!({ "limit": 50, "cursor": deep_cursor }),
+                );
+                assert_eq!(page.items.len(), 50);
+            })
+        };
+        for (label, elapsed) in [
+            ("page while walking the cursor", walk),
+            ("page after 200 000 rows", deep),
+            ("one business day", day),
+            ("one business day and cashier", cashier_day),
+        ] {
+            assert!(elapsed < PAGE_BUDGET, "{} took {:?}", label, elapsed);
+        }
+    }
+}
@@ -0,0 +1,433 @@
+use crate::business_day::store_now;
+use crate::error::{AppError, AppResult};
+use crate::{fetch_product_by_id, Database};
+use chrono::NaiveDateTime;
+use rusqlite::{Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+const EFFECTIVE_AT_FORMATS: [&str; 4] = [
+    "%Y-%m-%d %H:%M:%S",
+    "%Y-%m-%dT%H:%M:%S",
+    "%Y-%m-%d %H:%M",
+    "%Y-%m-%dT%H:%M",
+];
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum PriceChangeSource {
+    Manual,
+    Import,
+    Schedule,
+}
+
+impl PriceChangeSource {
+    fn as_str(self) -> &'static str {
+        match self {
+            PriceChangeSource::Manual => "manual",
+            PriceChangeSource::Import => "import",
+            PriceChangeSource::Schedule => "schedule",
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct PriceChangeRecord {
+    id: i64,
+    product_id: i64,
+    product_name: String,
+    old_price: i64,
+    new_price: i64,
+    source: String,
+    changed_by: Option<String>,
+    changed_at: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ScheduledPriceRecord {
+    id: i64,
+    product_id: i64,
+    product_name: String,
+    current_price: i64,
+    new_price: i64,
+    effective_at: String,
+    status: String,
+    note: Option<String>,
+    created_by: String,
+    created_at: String,
+    applied_at: Option<String>,
+    cancelled_by: Option<String>,
+    cancelled_at: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct PriceHistoryPayload {
+    product_id: Option<i64>,
+    limit: Option<i64>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ScheduledPriceInput {
+    product_id: i64,
+    price: i64,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct SchedulePricesPayload {
+    effective_at: String,
+    created_by: String,
+    note: Option<String>,
+    changes: Vec<ScheduledPriceInput>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ListScheduledPricesPayload {
+    product_id: Option<i64>,
+    pending_only: Option<bool>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct CancelScheduledPricePayload {
+    id: i64,
+    actor: String,
+}
+
+fn require_actor(value: &str, field: &'static str) -> AppResult<String> {
+    let cleaned = value.trim();
+    if cleaned.is_empty() {
+        return Err(AppError::validation(
+            "price_actor_required",
+            field,
+            "The person changing the price is required",
+        ));
+    }
+    Ok(cleaned.to_string())
+}
+
+// Schedules use the store's wall clock without an offset. A bare date means
+// the start of that day.
+fn parse_effective_at(value: &str) -> AppResult<String> {
+    let value = value.trim();
+    let parsed = EFFECTIVE_AT_FORMATS
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+        .or_else(|| {
+            chrono::NaiveDate::parse_from_str(value, "%Y-%m-%d")
+                .ok()
+                .and_then(|date| date.and_hms_opt(0, 0, 0))
+        });
+    let Some(parsed) = parsed else {
+        return Err(AppError::validation(
+            "price_schedule_time_invalid",
+            "effectiveAt",
+            "Effective time must look like 2024-05-01 06:00",
+        ));
+    };
+    Ok(parsed.format("%Y-%m-%d %H:%M:%S").to_string())
+}
+
+fn store_wall_clock(conn: &Connection) -> AppResult<(String, String)> {
+    let moment = store_now(conn)?;
+    let local = moment.timestamp[..19].to_string();
+    Ok((moment.timestamp, local))
+}
+
+pub(crate) fn record_price_change(
+    conn: &Connection,
+    product_id: i64,
+    old_price: i64,
+    new_price: i64,
+    source: PriceChangeSource,
+    changed_by: Option<&str>,
+) -> AppResult<()> {
+    if old_price == new_price {
+        return Ok(());
+    }
+    let moment = store_now(conn)?;
+    conn.execute(
+        "INSERT INTO price_changes (
+            product_id, old_price, new_price, source, changed_by, changed_at
+        )
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        (
+            product_id,
+            old_price,
+            new_price,
+            source.as_str(),
+            changed_by,
+            moment.timestamp.as_str(),
+        ),
+    )?;
+    Ok(())
+}
+
+// There is no background job: due schedules are applied whenever the catalog
+// is read and again when a cart is quoted or paid, so the sale itself never
+// uses a price that should have changed.
+// Several schedules for one product that fell due together end on the latest.
+pub(crate) fn apply_due_price_changes(conn: &Connection) -> AppResult<()> {
+    let (timestamp, local) = store_wall_clock(conn)?;
+    let due: bool = conn.query_row(
+        "SELECT EXISTS(
+            SELECT 1 FROM scheduled_prices
+            WHERE applied_at IS NULL AND cancelled_at IS NULL AND effective_at <= ?1
+        )",
+        [local.as_str()],
+        |row| row.get(0),
+    )?;
+    if !due {
+        return Ok(());
+    }
+    let tx = conn.unchecked_transaction()?;
+    let schedules = {
+        let mut statement = tx.prepare(
+            "SELECT id, product_id, new_price, created_by
+             FROM scheduled_prices
+             WHERE applied_at IS NULL AND cancelled_at IS NULL AND effective_at <= ?1
+             ORDER BY effective_at ASC, id ASC",
+        )?;
+        let rows = statement.query_map([local.as_str()], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, i64>(1)?,
+                row.get::<_, i64>(2)?,
+                row.get::<_, String>(3)?,
+            ))
+        })?;
+        rows.collect::<Result<Vec<_>, _>>()?
+    };
+    for (schedule_id, product_id, new_price, created_by) in schedules {
+        let old_price: i64 = tx.query_row(
+            "SELECT price FROM products WHERE id = ?1",
+            [product_id],
+            |row| row.get(0),
+        )?;
+        tx.execute(
+            "UPDATE products SET price = ?1 WHERE id = ?2",
+            (new_price, product_id),
+        )?;
+        record_price_change(
+            &tx,
+            product_id,
+            old_price,
+            new_price,
+            PriceChangeSource::Schedule,
+            Some(&created_by),
+        )?;
+        tx.execute(
+            "UPDATE scheduled_prices SET applied_at = ?1 WHERE id = ?2",
+            (timestamp.as_str(), schedule_id),
+        )?;
+    }
+    tx.commit()?;
+    Ok(())
+}
+
+fn parse_scheduled_row(row: &rusqlite::Row<'_>) -> Result<ScheduledPriceRecord, rusqlite::Error> {
+    let applied_at: Option<String> = row.get(9)?;
+    let cancelled_at: Option<String> = row.get(11)?;
+    let status = match (&applied_at, &cancelled_at) {
+        (Some(_), _) => "applied",
+        (None, Some(_)) => "cancelled",
+        (None, None) => "pending",
+    };
+    Ok(ScheduledPriceRecord {
+        id: row.get(0)?,
+        product_id: row.get(1)?,
+        product_name: row.get(2)?,
+        current_price: row.get(3)?,
+        new_price: row.get(4)?,
+        effective_at: row.get(5)?,
+        status: status.to_string(),
+        note: row.get(6)?,
+        created_by: row.get(7)?,
+        created_at: row.get(8)?,
+        applied_at,
+        cancelled_by: row.get(10)?,
+        cancelled_at,
+    })
+}
+
+const SCHEDULED_PRICE_SELECT: &str =
+    "SELECT s.id, s.product_id, p.name, p.price, s.new_price, s.effective_at, s.note,
+            s.created_by, s.created_at, s.applied_at, s.cancelled_by, s.cancelled_at
+     FROM scheduled_prices s
+     JOIN products p ON p.id = s.product_id";
+
+fn fetch_scheduled_price(conn: &Connection, id: i64) -> AppResult<ScheduledPriceRecord> {
+    conn.query_row(
+        &format!("{} WHERE s.id = ?1", SCHEDULED_PRICE_SELECT),
+        [id],
+        parse_scheduled_row,
+    )
+    .optional()?
+    .ok_or_else(|| {
+        AppError::not_found("price_schedule_not_found", "Scheduled price not found")
+            .with_field("id")
+    })
+}
+
+#[tauri::command]
+pub(crate) fn list_price_history(
+    database: tauri::State<'_, Database>,
+    payload: PriceHistoryPayload,
+) -> AppResult<Vec<PriceChangeRecord>> {
+    let limit = payload.limit.unwrap_or(100).clamp(1, 500);
+    let conn = database.lock()?;
+    apply_due_price_changes(&conn)?;
+    let mut statement = conn.prepare(
+        "SELECT c.id, c.product_id, p.name, c.old_price, c.new_price, c.source,
+                c.changed_by, c.changed_at
+         FROM price_changes c
+         JOIN products p ON p.id = c.product_id
+         WHERE ?1 IS NULL OR c.product_id = ?1
+         ORDER BY c.id DESC
+         LIMIT ?2",
+    )?;
+    let records = statement
+        .query_map((payload.product_id, limit), |row| {
+            Ok(PriceChangeRecord {
+                id: row.get(0)?,
+                product_id: row.get(1)?,
+                product_name: row.get(2)?,
+                old_price: row.get(3)?,
+                new_price: row.get(4)?,
+                source: row.get(5)?,
+                changed_by: row.get(6)?,
+                changed_at: row.get(7)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(records)
+}
+
+// A batch shares one effective time, so a whole price list can be switched
+// over at opening without anyone at the register.
+#[tauri::command]
+pub(crate) fn schedule_price_changes(
+    database: tauri::State<'_, Database>,
+    payload: SchedulePricesPayload,
+) -> AppResult<Vec<ScheduledPriceRecord>> {
+    let created_by = require_actor(&payload.created_by, "createdBy")?;
+    let effective_at = parse_effective_at(&payload.effective_at)?;
+    if payload.changes.is_empty() {
+        return Err(AppError::validation(
+            "price_schedule_empty",
+            "changes",
+            "Choose at least one product to reprice",
+        ));
+    }
+    let note = payload
+        .note
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty());
+    let mut conn = database.lock()?;
+    let (timestamp, local) = store_wall_clock(&conn)?;
+    if effective_at <= local {
+        return Err(AppError::validation(
+            "price_schedule_in_past",
+            "effectiveAt",
+            "Effective time must be in the future; edit the product to change its price now",
+        ));
+    }
+    let tx = conn.transaction()?;
+    let mut seen = HashSet::new();
+    let mut ids = Vec::with_capacity(payload.changes.len());
+    for (index, change) in payload.changes.iter().enumerate() {
+        let field = |name: &str| format!("changes[{}].{}", index, name);
+        let product = fetch_product_by_id(&tx, change.product_id)
+            .map_err(|err| err.with_field(field("productId")))?;
+        if product.archived_at.is_some() {
+            return Err(AppError::conflict(
+                "item_product_archived",
+                "Referenced product has been archived",
+            )
+            .with_field(field("productId")));
+        }
+        if !seen.insert(change.product_id) {
+            return Err(AppError::validation(
+                "price_schedule_duplicated",
+                field("productId"),
+                "Each product can only appear once in a batch",
+            ));
+        }
+        if change.price < 0 {
+            return Err(AppError::validation(
+                "price_schedule_price_invalid",
+                field("price"),
+                "Price cannot be negative",
+            ));
+        }
+        tx.execute(
+            "INSERT INTO scheduled_prices (
+                product_id, new_price, effective_at, note, created_by, created_at
+            )
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            (
+                change.product_id,
+                change.price,
+                effective_at.as_str(),
+                note.as_deref(),
+                created_by.as_str(),
+                timestamp.as_str(),
+            ),
+        )?;
+        ids.push(tx.last_insert_rowid());
+    }
+    tx.commit()?;
+    ids.into_iter()
+        .map(|id| fetch_scheduled_price(&conn, id))
+        .collect()
+}
+
+#[tauri::command]
+pub(crate) fn list_scheduled_prices(
+    database: tauri::State<'_, Database>,
+    payload: Option<ListScheduledPricesPayload>,
+) -> AppResult<Vec<ScheduledPriceRecord>> {
+    let payload = payload.unwrap_or_default();
+    let pending_only = payload.pending_only.unwrap_or(true);
+    let conn = database.lock()?;
+    apply_due_price_changes(&conn)?;
+    let mut statement = conn.prepare(&format!(
+        "{}
+         WHERE (?1 IS NULL OR s.product_id = ?1)
+           AND (NOT ?2 OR (s.applied_at IS NULL AND s.cancelled_at IS NULL))
+         ORDER BY s.effective_at DESC, s.id DESC
+         LIMIT 500",
+        SCHEDULED_PRICE_SELECT
+    ))?;
+    let records = statement
+        .query_map((payload.product_id, pending_only), parse_scheduled_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(records)
+}
+
+#[tauri::command]
+pub(crate) fn cancel_scheduled_price(
+    database: tauri::State<'_, Database>,
+    payload: CancelScheduledPricePayload,
+) -> AppResult<ScheduledPriceRecord> {
+    let actor = require_actor(&payload.actor, "actor")?;
+    let conn = database.lock()?;
+    apply_due_price_changes(&conn)?;
+    let current = fetch_scheduled_price(&conn, payload.id)?;
+    if current.status != "pending" {
+        return Err(AppError::conflict(
+            "price_schedule_not_pending",
+            "This price change has already been applied or cancelled",
+        ));
+    }
+    let moment = store_now(&conn)?;
+    conn.execute(
+        "UPDATE scheduled_prices SET cancelled_by = ?1, cancelled_at = ?2 WHERE id = ?3",
+        (actor.as_str(), moment.timestamp.as_str(), payload.id),
+    )?;
+    fetch_scheduled_price(&conn, payload.id)
+}
@@ -0,0 +1,473 @@
+use crate::error::{AppError, AppResult};
+use crate::price_changes::apply_due_price_changes;
+use crate::Database;
+use rusqlite::{Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+
+const MONEY_ROUNDING_MODE: &str = "floor";
+const QTY_PRECISION_FACTOR: i64 = 1_000;
+const TAX_RATE_PERCENT: i64 = 0;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct PaymentItemInput {
+    product_id: Option<i64>,
+    name: String,
+    quantity: f64,
+    base_unit_price: i64,
+    edited_unit_price: Option<i64>,
+    effective_unit_price: Option<i64>,
+    price: Option<i64>,
+    line_subtotal: Option<i64>,
+    line_discount: Option<i64>,
+    unit_id: Option<i64>,
+}
+
+// Quantities and prices are in the unit the line was sold in; unit_factor is
+// filled in from the product's units before the cart is saved and turns the
+// quantity into base units for stock.
+pub(crate) struct NormalizedPaymentItem {
+    pub product_id: Option<i64>,
+    pub name: String,
+    pub quantity_decimal: f64,
+    pub legacy_quantity: i64,
+    pub base_unit_price: i64,
+    pub edited_unit_price: Option<i64>,
+    pub effective_unit_price: i64,
+    pub line_subtotal: i64,
+    pub line_discount: i64,
+    pub line_tax: i64,
+    pub unit_id: Option<i64>,
+    pub unit_name: Option<String>,
+    pub unit_factor: f64,
+}
+
+impl NormalizedPaymentItem {
+    pub fn base_quantity(&self) -> f64 {
+        self.quantity_decimal * self.unit_factor
+    }
+}
+
+pub(crate) struct CartTotals {
+    pub subtotal: i64,
+    pub discount: i64,
+    pub tax: i64,
+    pub total: i64,
+}
+
+pub(crate) struct PricedCart {
+    pub items: Vec<NormalizedPaymentItem>,
+    pub totals: CartTotals,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct QuoteCartPayload {
+    items: Vec<PaymentItemInput>,
+    paid_cash: Option<i64>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct QuoteLine {
+    product_id: Option<i64>,
+    name: String,
+    quantity: f64,
+    base_unit_price: i64,
+    edited_unit_price: Option<i64>,
+    effective_unit_price: i64,
+    line_subtotal: i64,
+    line_discount: i64,
+    line_tax: i64,
+    line_total: i64,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct CartQuote {
+    lines: Vec<QuoteLine>,
+    subtotal: i64,
+    discount: i64,
+    tax: i64,
+    total: i64,
+    paid_cash: Option<i64>,
+    change_due: Option<i64>,
+}
+
+pub(crate) fn round_money(value: f64) -> i64 {
+    if MONEY_ROUNDING_MODE == "round" {
+        return value.round().max(0.0) as i64;
+    }
+    value.floor().max(0.0) as i64
+}
+
+pub(crate) fn scale_quantity(quantity: f64) -> i64 {
+    (quantity * QTY_PRECISION_FACTOR as f64).round() as i64
+}
+
+pub(crate) fn compute_line_subtotal(unit_price: i64, quantity: f64) -> i64 {
+    let scaled = unit_price as f64 * scale_quantity(quantity) as f64;
+    round_money(scaled / QTY_PRECISION_FACTOR as f64)
+}
+
+pub(crate) fn compute_tax(taxable_amount: i64) -> i64 {
+    round_money((taxable_amount * TAX_RATE_PERCENT) as f64 / 100.0)
+}
+
+pub(crate) fn figure_mismatch(field: String, claimed: i64, expected: i64) -> AppError {
+    AppError::validation(
+        "payment_figure_mismatch",
+        field,
+        format!(
+            "Submitted value {} does not match computed value {}",
+            claimed, expected
+        ),
+    )
+}
+
+pub(crate) fn reconcile_figure(field: &str, claimed: i64, expected: i64) -> AppResult<()> {
+    if claimed != expected {
+        return Err(figure_mismatch(field.to_string(), claimed, expected));
+    }
+    Ok(())
+}
+
+fn normalize_payment_items(items: Vec<PaymentItemInput>) -> AppResult<Vec<NormalizedPaymentItem>> {
+    if items.is_empty() {
+        return Err(AppError::validation(
+            "payment_items_empty",
+            "items",
+            "Payment must contain at least one item",
+        ));
+    }
+    let mut normalized = Vec::with_capacity(items.len());
+    for (index, item) in items.into_iter().enumerate() {
+        if !item.quantity.is_finite() || item.quantity <= 0.0 {
+            return Err(AppError::validation(
+                "item_quantity_invalid",
+                format!("items[{}].quantity", index),
+                "Item quantity must be greater than 0",
+            ));
+        }
+        let cleaned_name = item.name.trim();
+        if cleaned_name.is_empty() {
+            return Err(AppError::validation(
+                "item_name_required",
+                format!("items[{}].name", index),
+                "Item name cannot be empty",
+            ));
+        }
+        if item.base_unit_price < 0 {
+            return Err(AppError::validation(
+                "base_unit_price_negative",
+                format!("items[{}].baseUnitPrice", index),
+                "Base unit price cannot be negative",
+            ));
+        }
+        if item.edited_unit_price.is_some_and(|value| value < 0) {
+            return Err(AppError::validation(
+                "edited_unit_price_negative",
+                format!("items[{}].editedUnitPrice", index),
+                "Edited unit price cannot be negative",
+            ));
+        }
+        let edited_price = item.edited_unit_price;
+        let resolved_effective_price = edited_price.unwrap_or(item.base_unit_price);
+        if let Some(claimed) = item.effective_unit_price.or(item.price) {
+            if claimed != resolved_effective_price {
+                return Err(figure_mismatch(
+                    format!("items[{}].effectiveUnitPrice", index),
+                    claimed,
+                    resolved_effective_price,
+                ));
+            }
+        }
+        let line_subtotal = compute_line_subtotal(resolved_effective_price, item.quantity);
+        if let Some(claimed) = item.line_subtotal {
+            if claimed != line_subtotal {
+                return Err(figure_mismatch(
+                    format!("items[{}].lineSubtotal", index),
+                    claimed,
+                    line_subtotal,
+                ));
+            }
+        }
+        let line_discount = item.line_discount.unwrap_or(0);
+        if line_discount < 0 {
+            return Err(AppError::validation(
+                "line_discount_negative",
+                format!("items[{}].lineDiscount", index),
+                "Line discount cannot be negative",
+            ));
+        }
+        if line_discount > line_subtotal {
+            return Err(AppError::validation(
+                "line_discount_exceeds_subtotal",
+                format!("items[{}].lineDiscount", index),
+                "Line discount cannot exceed the line subtotal",
+            ));
+        }
+        let rounded_qty = item.quantity.round() as i64;
+        let legacy_quantity = if rounded_qty <= 0 { 1 } else { rounded_qty };
+        normalized.push(NormalizedPaymentItem {
+            product_id: item.product_id,
+            name: cleaned_name.to_string(),
+            quantity_decimal: item.quantity,
+            legacy_quantity,
+            base_unit_price: item.base_unit_price,
+            edited_unit_price: edited_price,
+            effective_unit_price: resolved_effective_price,
+            line_subtotal,
+            line_discount,
+            line_tax: compute_tax(line_subtotal - line_discount),
+            unit_id: item.unit_id,
+            unit_name: None,
+            unit_factor: 1.0,
+        });
+    }
+    Ok(normalized)
+}
+
+fn compute_cart_totals(items: &[NormalizedPaymentItem]) -> CartTotals {
+    let subtotal: i64 = items.iter().map(|item| item.line_subtotal).sum();
+    let discount: i64 = items.iter().map(|item| item.line_discount).sum();
+    let tax: i64 = items.iter().map(|item| item.line_tax).sum();
+    CartTotals {
+        subtotal,
+        discount,
+        tax,
+        total: subtotal - discount + tax,
+    }
+}
+
+// Linked lines are rung up at the catalog price, or the unit's own price when
+// sold by unit, so a schedule that fell due after the register loaded its
+// products still reaches the sale. Custom lines keep the price typed in, and
+// a unit that is not one of the product's own is refused rather than charged
+// at the base price.
+pub(crate) fn apply_catalog_prices(
+    conn: &Connection,
+    items: &mut [PaymentItemInput],
+) -> AppResult<()> {
+    apply_due_price_changes(conn)?;
+    let mut product_price = conn.prepare("SELECT price FROM products WHERE id = ?1")?;
+    let mut unit_price =
+        conn.prepare("SELECT price FROM product_units WHERE id = ?1 AND product_id = ?2")?;
+    for (index, item) in items.iter_mut().enumerate() {
+        let Some(product_id) = item.product_id else {
+            continue;
+        };
+        let price: Option<i64> = match item.unit_id {
+            Some(unit_id) => Some(
+                unit_price
+                    .query_row((unit_id, product_id), |row| row.get(0))
+                    .optional()?
+                    .ok_or_else(|| {
+                        AppError::not_found(
+                            "product_unit_not_found",
+                            "Selling unit does not belong to this product",
+                        )
+                        .with_field(format!("items[{}].unitId", index))
+                    })?,
+            ),
+            None => product_price
+                .query_row([product_id], |row| row.get(0))
+                .optional()?,
+        };
+        // Unknown products are reported when the sale is checked.
+        if let Some(price) = price {
+            item.base_unit_price = price;
+        }
+    }
+    Ok(())
+}
+
+pub(crate) fn price_cart(items: Vec<PaymentItemInput>) -> AppResult<PricedCart> {
+    let items = normalize_payment_items(items)?;
+    let totals = compute_cart_totals(&items);
+    Ok(PricedCart { items, totals })
+}
+
+#[tauri::command]
+pub(crate) fn quote_cart(
+    database: tauri::State<'_, Database>,
+    payload: QuoteCartPayload,
+) -> AppResult<CartQuote> {
+    let QuoteCartPayload {
+        mut items,
+        paid_cash,
+    } = payload;
+    {
+        let conn = database.lock()?;
+        apply_catalog_prices(&conn, &mut items)?;
+    }
+    let PricedCart { items, totals } = price_cart(items)?;
+    let lines = items
+        .into_iter()
+        .map(|item| QuoteLine {
+            product_id: item.product_id,
+            name: item.name,
+            quantity: item.quantity_decimal,
+            base_unit_price: item.base_unit_price,
+            edited_unit_price: item.edited_unit_price,
+            effective_unit_price: item.effective_unit_price,
+            line_subtotal: item.line_subtotal,
+            line_discount: item.line_discount,
+            line_tax: item.line_tax,
+            line_total: item.line_subtotal - item.line_discount + item.line_tax,
+        })
+        .collect();
+    Ok(CartQuote {
+        lines,
+        subtotal: totals.subtotal,
+        discount: totals.discount,
+        tax: totals.tax,
+        total: totals.total,
+        paid_cash,
+        change_due: paid_cash.map(|paid| (paid - totals.total).max(0)),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(quantity: f64, base_unit_price: i64) -> PaymentItemInput {
+        PaymentItemInput {
+            product_id: None,
+            name: "Cà phê".into(),
+            quantity,
+            base_unit_price,
+            edited_unit_price: None,
+            effective_unit_price: None,
+            price: None,
+            line_subtotal: None,
+            line_discount: None,
+            unit_id: None,
+        }
+    }
+
+    fn rejected(items: Vec<PaymentItemInput>) -> Option<(&'static str, Option<String>)> {
+        price_cart(items).err().map(|err| (err.code, err.field))
+    }
+
+    #[test]
+    fn money_rounds_down_and_never_goes_negative() {
+        assert_eq!(round_money(1999.99), 1999);
+        assert_eq!(round_money(-5.0), 0);
+        assert_eq!(compute_line_subtotal(12000, 1.5), 18000);
+        assert_eq!(compute_line_subtotal(999, 0.333), 332);
+        // Quantities are kept to three decimals before pricing.
+        assert_eq!(compute_line_subtotal(10000, 0.0004), 0);
+    }
+
+    #[test]
+    fn edited_prices_override_the_base_price() {
+        let mut item = line(2.0, 15000);
+        item.edited_unit_price = Some(12000);
+        let cart = price_cart(vec![item]).unwrap();
+        assert_eq!(cart.items[0].effective_unit_price, 12000);
+        assert_eq!(cart.totals.subtotal, 24000);
+
+        let mut negative = line(2.0, 15000);
+        negative.edited_unit_price = Some(-1);
+        assert_eq!(
+            rejected(vec![line(1.0, 5000), negative]),
+            Some((
+                "edited_unit_price_negative",
+                Some("items[1].editedUnitPrice".into())
+            ))
+        );
+    }
+
+    #[test]
+    fn line_discounts_stay_within_the_line() {
+        let mut item = line(2.0, 15000);
+        item.line_discount = Some(30000);
+        let cart = price_cart(vec![item, line(1.0, 5000)]).unwrap();
+        assert_eq!(cart.totals.subtotal, 35000);
+        assert_eq!(cart.totals.discount, 30000);
+        assert_eq!(cart.totals.total, 5000);
+
+        let mut negative = line(1.0, 5000);
+        negative.line_discount = Some(-1);
+        assert_eq!(
+            rejected(vec![negative]),
+            Some((
+                "line_discount_negative",
+                Some("items[0].lineDiscount".into())
+            ))
+        );
+        let mut excessive = line(1.0, 5000);
+        excessive.line_discount = Some(5001);
+        assert_eq!(
+            rejected(vec![excessive]),
+            Some((
+                "line_discount_exceeds_subtotal",
+                Some("items[0].lineDiscount".into())
+            ))
+        );
+    }
+
+    #[test]
+    fn submitted_figures_must_match_the_computed_ones() {
+        let mut item = line(1.5, 12000);
+        item.effective_unit_price = Some(12000);
+        item.line_subtotal = Some(18000);
+        assert!(price_cart(vec![item]).is_ok());
+
+        let mut stale_price = line(1.0, 12000);
+        stale_price.price = Some(11000);
+        assert_eq!(
+            rejected(vec![stale_price]),
+            Some((
+                "payment_figure_mismatch",
+                Some("items[0].effectiveUnitPrice".into())
+            ))
+        );
+        let mut stale_subtotal = line(1.5, 12000);
+        stale_subtotal.line_subtotal = Some(17999);
+        assert_eq!(
+            rejected(vec![stale_subtotal]),
+            Some((
+                "payment_figure_mismatch",
+                Some("items[0].lineSubtotal".into())
+            ))
+        );
+        assert!(reconcile_figure("total", 18000, 18000).is_ok());
+        assert_eq!(
+            reconcile_figure("total", 18001, 18000)
+                .err()
+                .map(|err| err.field),
+            Some(Some("total".into()))
+        );
+    }
+
+    #[test]
+    fn catalog_prices_require_the_product_own_unit() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        crate::initialize_schema(&mut conn).unwrap();
+        conn.execute_batch(
+            "INSERT INTO products (id, name, price) VALUES (1, 'Bia', 15000), (2, 'Nước ngọt', 10000);
+             INSERT INTO product_units (id, product_id, name, factor, price)
+             VALUES (1, 1, 'Thùng', 24, 330000);",
+        )
+        .unwrap();
+        let sold_by = |product_id, unit_id| {
+            let mut item = line(1.0, 0);
+            item.product_id = Some(product_id);
+            item.unit_id = unit_id;
+            item
+        };
+
+        let mut items = vec![sold_by(1, None), sold_by(1, Some(1))];
+        apply_catalog_prices(&conn, &mut items).unwrap();
+        assert_eq!(items[0].base_unit_price, 15000);
+        assert_eq!(items[1].base_unit_price, 330000);
+
+        let mut items = vec![sold_by(1, None), sold_by(2, Some(1))];
+        let err = apply_catalog_prices(&conn, &mut items).err().unwrap();
+        assert_eq!(err.code, "product_unit_not_found");
+        assert_eq!(err.field.as_deref(), Some("items[1].unitId"));
+    }
+}
@@ -0,0 +1,153 @@
+use crate::audit::record_audit;
+use crate::business_day::store_now;
+use crate::error::{AppError, AppResult};
+use crate::{fetch_product_by_id, parse_product_row, Database, ProductRecord, PRODUCT_COLUMNS};
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ProductLifecyclePayload {
+    id: i64,
+    actor: String,
+    reason: Option<String>,
+}
+
+impl ProductLifecyclePayload {
+    fn actor(&self) -> AppResult<&str> {
+        let actor = self.actor.trim();
+        if actor.is_empty() {
+            return Err(AppError::validation(
+                "product_actor_required",
+                "actor",
+                "The person changing the product is required",
+            ));
+        }
+        Ok(actor)
+    }
+
+    fn reason(&self) -> Option<&str> {
+        self.reason
+            .as_deref()
+            .map(str::trim)
+            .filter(|value| !value.is_empty())
+    }
+}
+
+#[tauri::command]
+pub(crate) fn list_archived_products(
+    database: tauri::State<'_, Database>,
+) -> AppResult<Vec<ProductRecord>> {
+    let conn = database.lock()?;
+    let mut statement = conn.prepare(&format!(
+        "SELECT {}
+         FROM products
+         WHERE archived_at IS NOT NULL
+         ORDER BY archived_at DESC, id DESC",
+        PRODUCT_COLUMNS
+    ))?;
+    let records = statement
+        .query_map([], parse_product_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(records)
+}
+
+// Archived products disappear from the register, lookups and exports but keep
+// their row, so past invoices still link to them.
+#[tauri::command]
+pub(crate) fn archive_product(
+    database: tauri::State<'_, Database>,
+    payload: ProductLifecyclePayload,
+) -> AppResult<ProductRecord> {
+    let actor = payload.actor()?;
+    let mut conn = database.lock()?;
+    let tx = conn.transaction()?;
+    let current = fetch_product_by_id(&tx, payload.id)?;
+    if current.archived_at.is_some() {
+        return Err(AppError::conflict(
+            "product_already_archived",
+            "Product is already archived",
+        ));
+    }
+    let moment = store_now(&tx)?;
+    tx.execute(
+        "UPDATE products SET archived_at = ?1 WHERE id = ?2",
+        (moment.timestamp.as_str(), payload.id),
+    )?;
+    record_audit(
+        &tx,
+        "product",
+        payload.id,
+        "archive",
+        actor,
+        payload.reason(),
+    )?;
+    tx.commit()?;
+    fetch_product_by_id(&conn, payload.id)
+}
+
+#[tauri::command]
+pub(crate) fn restore_product(
+    database: tauri::State<'_, Database>,
+    payload: ProductLifecyclePayload,
+) -> AppResult<ProductRecord> {
+    let actor = payload.actor()?;
+    let mut conn = database.lock()?;
+    let tx = conn.transaction()?;
+    let current = fetch_product_by_id(&tx, payload.id)?;
+    if current.archived_at.is_none() {
+        return Err(AppError::conflict(
+            "product_not_archived",
+            "Product is not archived",
+        ));
+    }
+    tx.execute(
+        "UPDATE products SET archived_at = NULL WHERE id = ?1",
+        [payload.id],
+    )?;
+    record_audit(
+        &tx,
+        "product",
+        payload.id,
+        "restore",
+        actor,
+        payload.reason(),
+    )?;
+    tx.commit()?;
+    fetch_product_by_id(&conn, payload.id)
+}
+
+// Only products that never appeared on an invoice, in the stock ledger, on a
+// purchase order or on a count sheet can be removed; anything else must be
+// archived so product_id links stay valid.
+#[tauri::command]
+pub(crate) fn delete_product(
+    database: tauri::State<'_, Database>,
+    payload: ProductLifecyclePayload,
+) -> AppResult<()> {
+    let actor = payload.actor()?;
+    let mut conn = database.lock()?;
+    let tx = conn.transaction()?;
+    let current = fetch_product_by_id(&tx, payload.id)?;
+    let referenced: bool = tx.query_row(
+        "SELECT EXISTS(SELECT 1 FROM payment_items WHERE product_id = ?1)
+             OR EXISTS(SELECT 1 FROM stock_movements WHERE product_id = ?1)
+             OR EXISTS(SELECT 1 FROM purchase_order_lines WHERE product_id = ?1)
+             OR EXISTS(SELECT 1 FROM stocktake_lines WHERE product_id = ?1)",
+        [payload.id],
+        |row| row.get(0),
+    )?;
+    if referenced {
+        return Err(AppError::conflict(
+            "product_in_use",
+            "Product appears on past invoices, stock or purchase records; archive it instead",
+        ));
+    }
+    tx.execute("DELETE FROM products WHERE id = ?1", [payload.id])?;
+    let reason = match payload.reason() {
+        Some(reason) => format!("{} ({})", current.name, reason),
+        None => current.name,
+    };
+    record_audit(&tx, "product", payload.id, "delete", actor, Some(&reason))?;
+    tx.commit()?;
+    Ok(())
+}
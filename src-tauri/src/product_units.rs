@@ -0,0 +1,269 @@
+use crate::barcodes::{ensure_unit_barcode_available, validate_barcode};
+use crate::error::{AppError, AppResult};
+use crate::pricing::NormalizedPaymentItem;
+use crate::{fetch_product_by_id, normalize_barcode, Database};
+use rusqlite::{Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ProductUnitRecord {
+    id: i64,
+    pub(crate) product_id: i64,
+    name: String,
+    pub(crate) factor: f64,
+    price: i64,
+    barcode: Option<String>,
+    display_order: i64,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ListProductUnitsPayload {
+    product_id: i64,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct SaveProductUnitPayload {
+    id: Option<i64>,
+    product_id: i64,
+    name: String,
+    factor: f64,
+    price: i64,
+    barcode: Option<String>,
+    internal_barcode: Option<bool>,
+    display_order: Option<i64>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct DeleteProductUnitPayload {
+    id: i64,
+}
+
+const UNIT_COLUMNS: &str = "id, product_id, name, factor, price, barcode, display_order";
+
+fn parse_unit_row(row: &rusqlite::Row<'_>) -> Result<ProductUnitRecord, rusqlite::Error> {
+    Ok(ProductUnitRecord {
+        id: row.get(0)?,
+        product_id: row.get(1)?,
+        name: row.get(2)?,
+        factor: row.get(3)?,
+        price: row.get(4)?,
+        barcode: row.get(5)?,
+        display_order: row.get(6)?,
+    })
+}
+
+fn fetch_unit(conn: &Connection, id: i64) -> AppResult<ProductUnitRecord> {
+    conn.query_row(
+        &format!("SELECT {} FROM product_units WHERE id = ?1", UNIT_COLUMNS),
+        [id],
+        parse_unit_row,
+    )
+    .optional()?
+    .ok_or_else(|| AppError::not_found("product_unit_not_found", "Selling unit not found"))
+}
+
+pub(crate) fn find_unit_by_barcode(
+    conn: &Connection,
+    barcode: &str,
+) -> AppResult<Option<ProductUnitRecord>> {
+    conn.query_row(
+        &format!(
+            "SELECT {} FROM product_units WHERE barcode = ?1",
+            UNIT_COLUMNS
+        ),
+        [barcode],
+        parse_unit_row,
+    )
+    .optional()
+    .map_err(AppError::from)
+}
+
+// Each alternate unit is a fixed number of base units: a carton of 24 cans has
+// factor 24, a gram of something stocked by the kilogram has factor 0.001.
+fn validate_unit(
+    conn: &Connection,
+    payload: &SaveProductUnitPayload,
+) -> AppResult<(String, Option<String>)> {
+    let product =
+        fetch_product_by_id(conn, payload.product_id).map_err(|err| err.with_field("productId"))?;
+    let name = payload.name.trim().to_string();
+    if name.is_empty() {
+        return Err(AppError::validation(
+            "unit_name_required",
+            "name",
+            "Unit name is required",
+        ));
+    }
+    if product
+        .unit_name
+        .as_deref()
+        .is_some_and(|base| base.eq_ignore_ascii_case(&name))
+    {
+        return Err(AppError::validation(
+            "unit_name_duplicated",
+            "name",
+            "This is already the product's base unit",
+        ));
+    }
+    let taken: bool = conn.query_row(
+        "SELECT EXISTS(
+            SELECT 1 FROM product_units
+            WHERE product_id = ?1 AND name = ?2 COLLATE NOCASE AND id IS NOT ?3
+        )",
+        (payload.product_id, name.as_str(), payload.id),
+        |row| row.get(0),
+    )?;
+    if taken {
+        return Err(AppError::validation(
+            "unit_name_duplicated",
+            "name",
+            "The product already has a unit with this name",
+        ));
+    }
+    if !payload.factor.is_finite() || payload.factor <= 0.0 {
+        return Err(AppError::validation(
+            "unit_factor_invalid",
+            "factor",
+            "Conversion factor must be greater than 0",
+        ));
+    }
+    if payload.price < 0 {
+        return Err(AppError::validation(
+            "unit_price_invalid",
+            "price",
+            "Price cannot be negative",
+        ));
+    }
+    let barcode = normalize_barcode(payload.barcode.clone());
+    if let Some(code) = barcode.as_deref() {
+        validate_barcode(code, payload.internal_barcode.unwrap_or(false))?;
+        ensure_unit_barcode_available(conn, code, payload.id)?;
+    }
+    Ok((name, barcode))
+}
+
+// Fills in each sold line's unit from the catalog so stock moves in base
+// units. A unit has to belong to the line's product.
+pub(crate) fn resolve_item_units(
+    conn: &Connection,
+    items: &mut [NormalizedPaymentItem],
+) -> AppResult<()> {
+    for (index, item) in items.iter_mut().enumerate() {
+        let Some(unit_id) = item.unit_id else {
+            continue;
+        };
+        let unit = conn
+            .query_row(
+                &format!("SELECT {} FROM product_units WHERE id = ?1", UNIT_COLUMNS),
+                [unit_id],
+                parse_unit_row,
+            )
+            .optional()?
+            .filter(|unit| Some(unit.product_id) == item.product_id);
+        let Some(unit) = unit else {
+            return Err(AppError::validation(
+                "item_unit_invalid",
+                format!("items[{}].unitId", index),
+                "Selling unit does not belong to this product",
+            ));
+        };
+        item.unit_name = Some(unit.name);
+        item.unit_factor = unit.factor;
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub(crate) fn list_product_units(
+    database: tauri::State<'_, Database>,
+    payload: ListProductUnitsPayload,
+) -> AppResult<Vec<ProductUnitRecord>> {
+    let conn = database.lock()?;
+    let mut statement = conn.prepare(&format!(
+        "SELECT {}
+         FROM product_units
+         WHERE product_id = ?1
+         ORDER BY display_order ASC, factor ASC, id ASC",
+        UNIT_COLUMNS
+    ))?;
+    let records = statement
+        .query_map([payload.product_id], parse_unit_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(records)
+}
+
+// Creates the unit without an id, updates it otherwise. Past sales keep the
+// unit name and factor they were rung up with.
+#[tauri::command]
+pub(crate) fn save_product_unit(
+    database: tauri::State<'_, Database>,
+    payload: SaveProductUnitPayload,
+) -> AppResult<ProductUnitRecord> {
+    let conn = database.lock()?;
+    let (name, barcode) = validate_unit(&conn, &payload)?;
+    let Some(id) = payload.id else {
+        let display_order = match payload.display_order {
+            Some(value) => value,
+            None => conn.query_row(
+                "SELECT COALESCE(MAX(display_order), 0) + 1 FROM product_units
+                 WHERE product_id = ?1",
+                [payload.product_id],
+                |row| row.get(0),
+            )?,
+        };
+        conn.execute(
+            "INSERT INTO product_units (product_id, name, factor, price, barcode, display_order)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            (
+                payload.product_id,
+                name.as_str(),
+                payload.factor,
+                payload.price,
+                barcode.as_deref(),
+                display_order,
+            ),
+        )?;
+        return fetch_unit(&conn, conn.last_insert_rowid());
+    };
+    let current = fetch_unit(&conn, id)?;
+    if current.product_id != payload.product_id {
+        return Err(AppError::validation(
+            "unit_product_mismatch",
+            "productId",
+            "A unit cannot be moved to another product",
+        ));
+    }
+    conn.execute(
+        "UPDATE product_units
+         SET name = ?1,
+             factor = ?2,
+             price = ?3,
+             barcode = ?4,
+             display_order = ?5
+         WHERE id = ?6",
+        (
+            name.as_str(),
+            payload.factor,
+            payload.price,
+            barcode.as_deref(),
+            payload.display_order.unwrap_or(current.display_order),
+            id,
+        ),
+    )?;
+    fetch_unit(&conn, id)
+}
+
+#[tauri::command]
+pub(crate) fn delete_product_unit(
+    database: tauri::State<'_, Database>,
+    payload: DeleteProductUnitPayload,
+) -> AppResult<()> {
+    let conn = database.lock()?;
+    fetch_unit(&conn, payload.id)?;
+    conn.execute("DELETE FROM product_units WHERE id = ?1", [payload.id])?;
+    Ok(())
+}
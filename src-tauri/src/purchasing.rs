@@ -0,0 +1,795 @@
+use crate::business_day::store_now;
+use crate::error::{AppError, AppResult};
+use crate::inventory::{
+    apply_receipt_cost, record_movements, round_quantity, MovementReason, MovementSource,
+};
+use crate::payment_search::{page_limit, take_page, Page, PageCursor};
+use crate::pricing::compute_line_subtotal;
+use crate::{fetch_product_by_id, Database};
+use rusqlite::{params_from_iter, types::Value, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+const STATUS_OPEN: &str = "open";
+const STATUS_PARTIAL: &str = "partially_received";
+const STATUS_RECEIVED: &str = "received";
+const STATUS_CLOSED: &str = "closed";
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct SupplierRecord {
+    id: i64,
+    name: String,
+    phone: Option<String>,
+    address: Option<String>,
+    note: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct CreateSupplierPayload {
+    name: String,
+    phone: Option<String>,
+    address: Option<String>,
+    note: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct UpdateSupplierPayload {
+    id: i64,
+    name: String,
+    phone: Option<String>,
+    address: Option<String>,
+    note: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct PurchaseLineInput {
+    product_id: i64,
+    quantity: f64,
+    unit_cost: i64,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct CreatePurchaseOrderPayload {
+    supplier_id: i64,
+    created_by: String,
+    note: Option<String>,
+    lines: Vec<PurchaseLineInput>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ListPurchaseOrdersPayload {
+    open_only: Option<bool>,
+    supplier_id: Option<i64>,
+    cursor: Option<PageCursor>,
+    limit: Option<i64>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct PurchaseOrderIdPayload {
+    order_id: i64,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ReceiptLineInput {
+    order_line_id: Option<i64>,
+    product_id: Option<i64>,
+    quantity: f64,
+    unit_cost: Option<i64>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ReceiveGoodsPayload {
+    order_id: Option<i64>,
+    supplier_id: Option<i64>,
+    received_by: String,
+    note: Option<String>,
+    lines: Vec<ReceiptLineInput>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct PurchaseOrderLineRecord {
+    id: i64,
+    product_id: i64,
+    product_name: String,
+    quantity_ordered: f64,
+    quantity_received: f64,
+    quantity_outstanding: f64,
+    unit_cost: i64,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct PurchaseOrderRecord {
+    id: i64,
+    order_number: String,
+    supplier_id: i64,
+    supplier_name: String,
+    status: String,
+    note: Option<String>,
+    created_by: String,
+    created_at: String,
+    business_date: String,
+    lines: Vec<PurchaseOrderLineRecord>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct GoodsReceiptLineRecord {
+    id: i64,
+    order_line_id: Option<i64>,
+    product_id: i64,
+    product_name: String,
+    quantity: f64,
+    unit_cost: i64,
+    line_cost: i64,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct GoodsReceiptRecord {
+    id: i64,
+    order_id: Option<i64>,
+    supplier_id: Option<i64>,
+    received_by: String,
+    note: Option<String>,
+    total_cost: i64,
+    created_at: String,
+    business_date: String,
+    lines: Vec<GoodsReceiptLineRecord>,
+}
+
+struct OrderLine {
+    product_id: i64,
+    outstanding: f64,
+    unit_cost: i64,
+}
+
+struct ResolvedReceiptLine {
+    order_line_id: Option<i64>,
+    product_id: i64,
+    quantity: f64,
+    unit_cost: i64,
+}
+
+fn clean_text(value: Option<String>) -> Option<String> {
+    value
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+}
+
+fn require_actor(value: &str, field: &'static str) -> AppResult<String> {
+    let cleaned = value.trim();
+    if cleaned.is_empty() {
+        return Err(AppError::validation(
+            "purchase_actor_required",
+            field,
+            "The person recording this is required",
+        ));
+    }
+    Ok(cleaned.to_string())
+}
+
+fn validate_quantity(quantity: f64, field: String) -> AppResult<f64> {
+    let rounded = round_quantity(quantity);
+    if !rounded.is_finite() || rounded <= 0.0 {
+        return Err(AppError::validation(
+            "purchase_quantity_invalid",
+            field,
+            "Quantity must be greater than 0",
+        ));
+    }
+    Ok(rounded)
+}
+
+fn validate_unit_cost(unit_cost: i64, field: String) -> AppResult<i64> {
+    if unit_cost < 0 {
+        return Err(AppError::validation(
+            "purchase_unit_cost_invalid",
+            field,
+            "Unit cost cannot be negative",
+        ));
+    }
+    Ok(unit_cost)
+}
+
+fn parse_supplier_row(row: &rusqlite::Row<'_>) -> Result<SupplierRecord, rusqlite::Error> {
+    Ok(SupplierRecord {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        phone: row.get(2)?,
+        address: row.get(3)?,
+        note: row.get(4)?,
+    })
+}
+
+fn fetch_supplier(conn: &Connection, id: i64) -> AppResult<SupplierRecord> {
+    conn.query_row(
+        "SELECT id, name, phone, address, note FROM suppliers WHERE id = ?1",
+        [id],
+        parse_supplier_row,
+    )
+    .optional()?
+    .ok_or_else(|| {
+        AppError::not_found("supplier_not_found", "Supplier not found").with_field("supplierId")
+    })
+}
+
+fn clean_supplier_name(name: &str) -> AppResult<String> {
+    let cleaned = name.trim();
+    if cleaned.is_empty() {
+        return Err(AppError::validation(
+            "supplier_name_required",
+            "name",
+            "Supplier name is required",
+        ));
+    }
+    Ok(cleaned.to_string())
+}
+
+fn load_lines_for_orders(
+    conn: &Connection,
+    order_ids: &[i64],
+) -> AppResult<HashMap<i64, Vec<PurchaseOrderLineRecord>>> {
+    let mut grouped: HashMap<i64, Vec<PurchaseOrderLineRecord>> = HashMap::new();
+    if order_ids.is_empty() {
+        return Ok(grouped);
+    }
+    let placeholders = vec!["?"; order_ids.len()].join(", ");
+    let mut statement = conn.prepare(&format!(
+        "SELECT l.order_id, l.id, l.product_id, p.name, l.quantity_ordered,
+                l.quantity_received, l.unit_cost
+         FROM purchase_order_lines l
+         JOIN products p ON p.id = l.product_id
+         WHERE l.order_id IN ({})
+         ORDER BY l.order_id ASC, l.id ASC",
+        placeholders
+    ))?;
+    let mut rows = statement.query(params_from_iter(order_ids))?;
+    while let Some(row) = rows.next()? {
+        let ordered: f64 = row.get(4)?;
+        let received: f64 = row.get(5)?;
+        let line = PurchaseOrderLineRecord {
+            id: row.get(1)?,
+            product_id: row.get(2)?,
+            product_name: row.get(3)?,
+            quantity_ordered: ordered,
+            quantity_received: received,
+            quantity_outstanding: round_quantity((ordered - received).max(0.0)),
+            unit_cost: row.get(6)?,
+        };
+        grouped.entry(row.get(0)?).or_default().push(line);
+    }
+    Ok(grouped)
+}
+
+// Fills in the lines of every order with a single query.
+fn query_orders(
+    conn: &Connection,
+    where_sql: &str,
+    params: &[Value],
+    limit: Option<i64>,
+) -> AppResult<Vec<PurchaseOrderRecord>> {
+    let mut statement = conn.prepare(&format!(
+        "SELECT o.id, o.order_number, o.supplier_id, s.name, o.status, o.note, o.created_by,
+                o.created_at, o.business_date
+         FROM purchase_orders o
+         JOIN suppliers s ON s.id = o.supplier_id
+         {}
+         ORDER BY o.created_at DESC, o.id DESC
+         LIMIT {}",
+        where_sql,
+        limit.unwrap_or(-1)
+    ))?;
+    let mut orders = statement
+        .query_map(params_from_iter(params), |row| {
+            Ok(PurchaseOrderRecord {
+                id: row.get(0)?,
+                order_number: row.get(1)?,
+                supplier_id: row.get(2)?,
+                supplier_name: row.get(3)?,
+                status: row.get(4)?,
+                note: row.get(5)?,
+                created_by: row.get(6)?,
+                created_at: row.get(7)?,
+                business_date: row.get(8)?,
+                lines: Vec::new(),
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    let ids: Vec<i64> = orders.iter().map(|order| order.id).collect();
+    let mut lines = load_lines_for_orders(conn, &ids)?;
+    for order in orders.iter_mut() {
+        order.lines = lines.remove(&order.id).unwrap_or_default();
+    }
+    Ok(orders)
+}
+
+fn load_purchase_order(conn: &Connection, order_id: i64) -> AppResult<PurchaseOrderRecord> {
+    query_orders(conn, "WHERE o.id = ?", &[Value::Integer(order_id)], None)?
+        .pop()
+        .ok_or_else(|| {
+            AppError::not_found("purchase_order_not_found", "Purchase order not found")
+                .with_field("orderId")
+        })
+}
+
+fn load_receipt(conn: &Connection, receipt_id: i64) -> AppResult<GoodsReceiptRecord> {
+    let mut receipt = conn.query_row(
+        "SELECT id, order_id, supplier_id, received_by, note, total_cost, created_at,
+                business_date
+         FROM goods_receipts
+         WHERE id = ?1",
+        [receipt_id],
+        |row| {
+            Ok(GoodsReceiptRecord {
+                id: row.get(0)?,
+                order_id: row.get(1)?,
+                supplier_id: row.get(2)?,
+                received_by: row.get(3)?,
+                note: row.get(4)?,
+                total_cost: row.get(5)?,
+                created_at: row.get(6)?,
+                business_date: row.get(7)?,
+                lines: Vec::new(),
+            })
+        },
+    )?;
+    let mut statement = conn.prepare(
+        "SELECT l.id, l.order_line_id, l.product_id, p.name, l.quantity, l.unit_cost, l.line_cost
+         FROM goods_receipt_lines l
+         JOIN products p ON p.id = l.product_id
+         WHERE l.receipt_id = ?1
+         ORDER BY l.id ASC",
+    )?;
+    receipt.lines = statement
+        .query_map([receipt_id], |row| {
+            Ok(GoodsReceiptLineRecord {
+                id: row.get(0)?,
+                order_line_id: row.get(1)?,
+                product_id: row.get(2)?,
+                product_name: row.get(3)?,
+                quantity: row.get(4)?,
+                unit_cost: row.get(5)?,
+                line_cost: row.get(6)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(receipt)
+}
+
+fn refresh_order_status(conn: &Connection, order_id: i64) -> AppResult<()> {
+    conn.execute(
+        "UPDATE purchase_orders
+         SET status = CASE
+             WHEN NOT EXISTS (
+                 SELECT 1 FROM purchase_order_lines
+                 WHERE order_id = ?1 AND quantity_received < quantity_ordered
+             ) THEN ?2
+             WHEN EXISTS (
+                 SELECT 1 FROM purchase_order_lines
+                 WHERE order_id = ?1 AND quantity_received > 0
+             ) THEN ?3
+             ELSE ?4
+         END
+         WHERE id = ?1",
+        (order_id, STATUS_RECEIVED, STATUS_PARTIAL, STATUS_OPEN),
+    )?;
+    Ok(())
+}
+
+#[tauri::command]
+pub(crate) fn list_suppliers(
+    database: tauri::State<'_, Database>,
+) -> AppResult<Vec<SupplierRecord>> {
+    let conn = database.lock()?;
+    let mut statement = conn.prepare(
+        "SELECT id, name, phone, address, note
+         FROM suppliers
+         ORDER BY name COLLATE NOCASE ASC, id ASC",
+    )?;
+    let records = statement
+        .query_map([], parse_supplier_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(records)
+}
+
+#[tauri::command]
+pub(crate) fn create_supplier(
+    database: tauri::State<'_, Database>,
+    payload: CreateSupplierPayload,
+) -> AppResult<SupplierRecord> {
+    let name = clean_supplier_name(&payload.name)?;
+    let conn = database.lock()?;
+    conn.execute(
+        "INSERT INTO suppliers (name, phone, address, note) VALUES (?1, ?2, ?3, ?4)",
+        (
+            name.as_str(),
+            clean_text(payload.phone).as_deref(),
+            clean_text(payload.address).as_deref(),
+            clean_text(payload.note).as_deref(),
+        ),
+    )?;
+    fetch_supplier(&conn, conn.last_insert_rowid())
+}
+
+#[tauri::command]
+pub(crate) fn update_supplier(
+    database: tauri::State<'_, Database>,
+    payload: UpdateSupplierPayload,
+) -> AppResult<SupplierRecord> {
+    let name = clean_supplier_name(&payload.name)?;
+    let conn = database.lock()?;
+    fetch_supplier(&conn, payload.id)?;
+    conn.execute(
+        "UPDATE suppliers SET name = ?1, phone = ?2, address = ?3, note = ?4 WHERE id = ?5",
+        (
+            name.as_str(),
+            clean_text(payload.phone).as_deref(),
+            clean_text(payload.address).as_deref(),
+            clean_text(payload.note).as_deref(),
+            payload.id,
+        ),
+    )?;
+    fetch_supplier(&conn, payload.id)
+}
+
+#[tauri::command]
+pub(crate) fn create_purchase_order(
+    database: tauri::State<'_, Database>,
+    payload: CreatePurchaseOrderPayload,
+) -> AppResult<PurchaseOrderRecord> {
+    let created_by = require_actor(&payload.created_by, "createdBy")?;
+    if payload.lines.is_empty() {
+        return Err(AppError::validation(
+            "purchase_lines_empty",
+            "lines",
+            "A purchase order needs at least one line",
+        ));
+    }
+    let mut conn = database.lock()?;
+    let tx = conn.transaction()?;
+    fetch_supplier(&tx, payload.supplier_id)?;
+    let mut seen = HashSet::new();
+    let mut lines = Vec::with_capacity(payload.lines.len());
+    for (index, line) in payload.lines.iter().enumerate() {
+        let field = |name: &str| format!("lines[{}].{}", index, name);
+        fetch_product_by_id(&tx, line.product_id)
+            .map_err(|err| err.with_field(field("productId")))?;
+        if !seen.insert(line.product_id) {
+            return Err(AppError::validation(
+                "purchase_line_duplicated",
+                field("productId"),
+                "Each product can appear only once per order",
+            ));
+        }
+        lines.push((
+            line.product_id,
+            validate_quantity(line.quantity, field("quantity"))?,
+            validate_unit_cost(line.unit_cost, field("unitCost"))?,
+        ));
+    }
+    let moment = store_now(&tx)?;
+    let sequence: i64 = tx.query_row(
+        "SELECT COUNT(*) + 1 FROM purchase_orders WHERE business_date = ?1",
+        [moment.business_date.as_str()],
+        |row| row.get(0),
+    )?;
+    let order_number = format!(
+        "PO{}-{:03}",
+        moment.business_date.replace('-', ""),
+        sequence
+    );
+    tx.execute(
+        "INSERT INTO purchase_orders (
+            order_number, supplier_id, status, note, created_by, created_at, business_date
+        )
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        (
+            order_number.as_str(),
+            payload.supplier_id,
+            STATUS_OPEN,
+            clean_text(payload.note).as_deref(),
+            created_by.as_str(),
+            moment.timestamp.as_str(),
+            moment.business_date.as_str(),
+        ),
+    )?;
+    let order_id = tx.last_insert_rowid();
+    for (product_id, quantity, unit_cost) in lines {
+        tx.execute(
+            "INSERT INTO purchase_order_lines (order_id, product_id, quantity_ordered, unit_cost)
+             VALUES (?1, ?2, ?3, ?4)",
+            (order_id, product_id, quantity, unit_cost),
+        )?;
+    }
+    tx.commit()?;
+    load_purchase_order(&conn, order_id)
+}
+
+#[tauri::command]
+pub(crate) fn list_purchase_orders(
+    database: tauri::State<'_, Database>,
+    payload: Option<ListPurchaseOrdersPayload>,
+) -> AppResult<Page<PurchaseOrderRecord>> {
+    let ListPurchaseOrdersPayload {
+        open_only,
+        supplier_id,
+        cursor,
+        limit,
+    } = payload.unwrap_or_default();
+    let limit = page_limit(limit)?;
+    let (cursor_created_at, cursor_id) = match cursor {
+        Some(cursor) => (Value::Text(cursor.created_at), Value::Integer(cursor.id)),
+        None => (Value::Null, Value::Null),
+    };
+    let conn = database.lock()?;
+    let mut items = query_orders(
+        &conn,
+        "WHERE (?1 = 0 OR o.status IN ('open', 'partially_received'))
+           AND o.supplier_id IS COALESCE(?2, o.supplier_id)
+           AND (?3 IS NULL OR (o.created_at, o.id) < (?3, ?4))",
+        &[
+            Value::from(open_only.unwrap_or(true)),
+            supplier_id.map_or(Value::Null, Value::Integer),
+            cursor_created_at,
+            cursor_id,
+        ],
+        Some(limit + 1),
+    )?;
+    let next_cursor = take_page(&mut items, limit, |order| PageCursor {
+        created_at: order.created_at.clone(),
+        id: order.id,
+    });
+    Ok(Page { items, next_cursor })
+}
+
+#[tauri::command]
+pub(crate) fn get_purchase_order(
+    database: tauri::State<'_, Database>,
+    payload: PurchaseOrderIdPayload,
+) -> AppResult<PurchaseOrderRecord> {
+    let conn = database.lock()?;
+    load_purchase_order(&conn, payload.order_id)
+}
+
+// Stops waiting for the rest of an order; whatever was received stays in stock.
+#[tauri::command]
+pub(crate) fn close_purchase_order(
+    database: tauri::State<'_, Database>,
+    payload: PurchaseOrderIdPayload,
+) -> AppResult<PurchaseOrderRecord> {
+    let conn = database.lock()?;
+    let order = load_purchase_order(&conn, payload.order_id)?;
+    if order.status == STATUS_RECEIVED || order.status == STATUS_CLOSED {
+        return Err(AppError::conflict(
+            "purchase_order_not_open",
+            "Purchase order is no longer open",
+        ));
+    }
+    conn.execute(
+        "UPDATE purchase_orders SET status = ?1 WHERE id = ?2",
+        (STATUS_CLOSED, payload.order_id),
+    )?;
+    load_purchase_order(&conn, payload.order_id)
+}
+
+fn resolve_order_lines(
+    conn: &Connection,
+    order_id: i64,
+    inputs: &[ReceiptLineInput],
+) -> AppResult<Vec<ResolvedReceiptLine>> {
+    let mut statement = conn.prepare(
+        "SELECT id, product_id, quantity_ordered - quantity_received, unit_cost
+         FROM purchase_order_lines
+         WHERE order_id = ?1",
+    )?;
+    let order_lines: HashMap<i64, OrderLine> = statement
+        .query_map([order_id], |row| {
+            Ok((
+                row.get(0)?,
+                OrderLine {
+                    product_id: row.get(1)?,
+                    outstanding: row.get(2)?,
+                    unit_cost: row.get(3)?,
+                },
+            ))
+        })?
+        .collect::<Result<_, _>>()?;
+    let mut seen = HashSet::new();
+    let mut resolved = Vec::with_capacity(inputs.len());
+    for (index, input) in inputs.iter().enumerate() {
+        let field = |name: &str| format!("lines[{}].{}", index, name);
+        // Lines can point at the order line directly or just name the product.
+        let matched = match (input.order_line_id, input.product_id) {
+            (Some(line_id), _) => order_lines.get_key_value(&line_id),
+            (None, Some(product_id)) => order_lines
+                .iter()
+                .find(|(_, line)| line.product_id == product_id),
+            (None, None) => None,
+        };
+        let Some((line_id, line)) = matched else {
+            return Err(AppError::validation(
+                "receipt_line_not_in_order",
+                field("orderLineId"),
+                "Line is not part of this purchase order",
+            ));
+        };
+        if !seen.insert(*line_id) {
+            return Err(AppError::validation(
+                "receipt_line_duplicated",
+                field("orderLineId"),
+                "Each order line can appear only once per receipt",
+            ));
+        }
+        let quantity = validate_quantity(input.quantity, field("quantity"))?;
+        if quantity > round_quantity(line.outstanding) {
+            return Err(AppError::validation(
+                "receipt_quantity_exceeds_order",
+                field("quantity"),
+                format!(
+                    "Only {} is still outstanding",
+                    round_quantity(line.outstanding)
+                ),
+            ));
+        }
+        resolved.push(ResolvedReceiptLine {
+            order_line_id: Some(*line_id),
+            product_id: line.product_id,
+            quantity,
+            unit_cost: validate_unit_cost(
+                input.unit_cost.unwrap_or(line.unit_cost),
+                field("unitCost"),
+            )?,
+        });
+    }
+    Ok(resolved)
+}
+
+fn resolve_direct_lines(
+    conn: &Connection,
+    inputs: &[ReceiptLineInput],
+) -> AppResult<Vec<ResolvedReceiptLine>> {
+    let mut seen = HashSet::new();
+    let mut resolved = Vec::with_capacity(inputs.len());
+    for (index, input) in inputs.iter().enumerate() {
+        let field = |name: &str| format!("lines[{}].{}", index, name);
+        let (Some(product_id), Some(unit_cost)) = (input.product_id, input.unit_cost) else {
+            return Err(AppError::validation(
+                "receipt_line_incomplete",
+                field("productId"),
+                "Receiving without an order needs a product and a unit cost on every line",
+            ));
+        };
+        fetch_product_by_id(conn, product_id).map_err(|err| err.with_field(field("productId")))?;
+        if !seen.insert(product_id) {
+            return Err(AppError::validation(
+                "receipt_line_duplicated",
+                field("productId"),
+                "Each product can appear only once per receipt",
+            ));
+        }
+        resolved.push(ResolvedReceiptLine {
+            order_line_id: None,
+            product_id,
+            quantity: validate_quantity(input.quantity, field("quantity"))?,
+            unit_cost: validate_unit_cost(unit_cost, field("unitCost"))?,
+        });
+    }
+    Ok(resolved)
+}
+
+// Posts a delivery into stock. Against an order, each line may cover part of
+// what is outstanding and the order stays open until every line is complete.
+#[tauri::command]
+pub(crate) fn receive_goods(
+    database: tauri::State<'_, Database>,
+    payload: ReceiveGoodsPayload,
+) -> AppResult<GoodsReceiptRecord> {
+    let received_by = require_actor(&payload.received_by, "receivedBy")?;
+    if payload.lines.is_empty() {
+        return Err(AppError::validation(
+            "receipt_lines_empty",
+            "lines",
+            "A receipt needs at least one line",
+        ));
+    }
+    let mut conn = database.lock()?;
+    let tx = conn.transaction()?;
+    let (supplier_id, lines) = match payload.order_id {
+        Some(order_id) => {
+            let order = load_purchase_order(&tx, order_id)?;
+            if order.status == STATUS_RECEIVED || order.status == STATUS_CLOSED {
+                return Err(AppError::conflict(
+                    "purchase_order_not_open",
+                    "Purchase order is no longer open",
+                ));
+            }
+            (
+                Some(order.supplier_id),
+                resolve_order_lines(&tx, order_id, &payload.lines)?,
+            )
+        }
+        None => {
+            if let Some(supplier_id) = payload.supplier_id {
+                fetch_supplier(&tx, supplier_id)?;
+            }
+            (
+                payload.supplier_id,
+                resolve_direct_lines(&tx, &payload.lines)?,
+            )
+        }
+    };
+    let total_cost: i64 = lines
+        .iter()
+        .map(|line| compute_line_subtotal(line.unit_cost, line.quantity))
+        .sum();
+    let moment = store_now(&tx)?;
+    let note = clean_text(payload.note);
+    tx.execute(
+        "INSERT INTO goods_receipts (
+            order_id, supplier_id, received_by, note, total_cost, created_at, business_date
+        )
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        (
+            payload.order_id,
+            supplier_id,
+            received_by.as_str(),
+            note.as_deref(),
+            total_cost,
+            moment.timestamp.as_str(),
+            moment.business_date.as_str(),
+        ),
+    )?;
+    let receipt_id = tx.last_insert_rowid();
+    for line in &lines {
+        tx.execute(
+            "INSERT INTO goods_receipt_lines (
+                receipt_id, order_line_id, product_id, quantity, unit_cost, line_cost
+            )
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            (
+                receipt_id,
+                line.order_line_id,
+                line.product_id,
+                line.quantity,
+                line.unit_cost,
+                compute_line_subtotal(line.unit_cost, line.quantity),
+            ),
+        )?;
+        apply_receipt_cost(&tx, line.product_id, line.quantity, line.unit_cost)?;
+        if let Some(order_line_id) = line.order_line_id {
+            tx.execute(
+                "UPDATE purchase_order_lines
+                 SET quantity_received = ROUND(quantity_received + ?1, 3)
+                 WHERE id = ?2",
+                (line.quantity, order_line_id),
+            )?;
+        }
+    }
+    if let Some(order_id) = payload.order_id {
+        refresh_order_status(&tx, order_id)?;
+    }
+    record_movements(
+        &tx,
+        &moment,
+        &MovementSource {
+            reason: MovementReason::Receipt,
+            source_id: Some(receipt_id),
+            actor: Some(&received_by),
+            note: note.as_deref(),
+        },
+        lines.iter().map(|line| (line.product_id, line.quantity)),
+    )?;
+    tx.commit()?;
+    load_receipt(&conn, receipt_id)
+}
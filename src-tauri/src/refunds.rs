@@ -0,0 +1,516 @@
+use crate::business_day::store_now;
+use crate::error::{AppError, AppResult};
+use crate::inventory::{record_movements, MovementReason, MovementSource};
+use crate::payment_search::{page_limit, take_page, Page, PageCursor};
+use crate::pricing::{compute_tax, scale_quantity};
+use crate::Database;
+use rusqlite::{params_from_iter, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct RefundLineInput {
+    payment_item_id: i64,
+    quantity: f64,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct CreateRefundPayload {
+    payment_id: i64,
+    cashier_name: String,
+    reason: Option<String>,
+    lines: Vec<RefundLineInput>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ListRefundsPayload {
+    payment_id: Option<i64>,
+    cursor: Option<PageCursor>,
+    limit: Option<i64>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct RefundItemRecord {
+    id: i64,
+    payment_item_id: i64,
+    product_id: Option<i64>,
+    name: String,
+    quantity: f64,
+    unit_price: i64,
+    amount: i64,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct RefundRecord {
+    id: i64,
+    payment_id: i64,
+    refund_number: String,
+    invoice_number: String,
+    cashier_name: String,
+    reason: Option<String>,
+    total: i64,
+    created_at: String,
+    business_date: Option<String>,
+    items: Vec<RefundItemRecord>,
+}
+
+struct SoldLine {
+    product_id: Option<i64>,
+    name: String,
+    quantity: f64,
+    unit_price: i64,
+    net_amount: i64,
+    unit_factor: f64,
+}
+
+struct RefundedSoFar {
+    scaled_quantity: i64,
+    amount: i64,
+}
+
+fn fetch_sold_line(
+    conn: &Connection,
+    payment_id: i64,
+    payment_item_id: i64,
+) -> AppResult<Option<SoldLine>> {
+    conn.query_row(
+        "SELECT product_id, name, COALESCE(quantity_decimal, quantity), price,
+                COALESCE(line_subtotal, price * quantity), line_discount, unit_factor
+         FROM payment_items
+         WHERE id = ?1 AND payment_id = ?2",
+        (payment_item_id, payment_id),
+        |row| {
+            let line_subtotal: i64 = row.get(4)?;
+            let line_discount: i64 = row.get(5)?;
+            let net = line_subtotal - line_discount;
+            Ok(SoldLine {
+                product_id: row.get(0)?,
+                name: row.get(1)?,
+                quantity: row.get(2)?,
+                unit_price: row.get(3)?,
+                net_amount: net + compute_tax(net),
+                unit_factor: row.get(6)?,
+            })
+        },
+    )
+    .optional()
+    .map_err(AppError::from)
+}
+
+fn fetch_refunded_so_far(conn: &Connection, payment_item_id: i64) -> AppResult<RefundedSoFar> {
+    let mut statement =
+        conn.prepare("SELECT quantity, amount FROM refund_items WHERE payment_item_id = ?1")?;
+    let mut rows = statement.query([payment_item_id])?;
+    let mut totals = RefundedSoFar {
+        scaled_quantity: 0,
+        amount: 0,
+    };
+    while let Some(row) = rows.next()? {
+        totals.scaled_quantity += scale_quantity(row.get(0)?);
+        totals.amount += row.get::<_, i64>(1)?;
+    }
+    Ok(totals)
+}
+
+fn fetch_items_for_refunds(
+    conn: &Connection,
+    refund_ids: &[i64],
+) -> AppResult<HashMap<i64, Vec<RefundItemRecord>>> {
+    let mut grouped: HashMap<i64, Vec<RefundItemRecord>> = HashMap::new();
+    if refund_ids.is_empty() {
+        return Ok(grouped);
+    }
+    let placeholders = vec!["?"; refund_ids.len()].join(", ");
+    let mut statement = conn.prepare(&format!(
+        "SELECT refund_id, id, payment_item_id, product_id, name, quantity, unit_price, amount
+         FROM refund_items
+         WHERE refund_id IN ({})
+         ORDER BY refund_id ASC, id ASC",
+        placeholders
+    ))?;
+    let mut rows = statement.query(params_from_iter(refund_ids))?;
+    while let Some(row) = rows.next()? {
+        let item = RefundItemRecord {
+            id: row.get(1)?,
+            payment_item_id: row.get(2)?,
+            product_id: row.get(3)?,
+            name: row.get(4)?,
+            quantity: row.get(5)?,
+            unit_price: row.get(6)?,
+            amount: row.get(7)?,
+        };
+        grouped.entry(row.get(0)?).or_default().push(item);
+    }
+    Ok(grouped)
+}
+
+fn parse_refund_row(row: &rusqlite::Row<'_>) -> Result<RefundRecord, rusqlite::Error> {
+    Ok(RefundRecord {
+        id: row.get(0)?,
+        payment_id: row.get(1)?,
+        refund_number: row.get(2)?,
+        invoice_number: row.get(3)?,
+        cashier_name: row.get(4)?,
+        reason: row.get(5)?,
+        total: row.get(6)?,
+        created_at: row.get(7)?,
+        business_date: row.get(8)?,
+        items: Vec::new(),
+    })
+}
+
+fn load_refund_by_id(conn: &Connection, refund_id: i64) -> AppResult<RefundRecord> {
+    let mut record = conn.query_row(
+        "SELECT r.id, r.payment_id, r.refund_number, p.invoice_number, r.cashier_name,
+                r.reason, r.total, r.created_at, r.business_date
+         FROM refunds r
+         JOIN payments p ON p.id = r.payment_id
+         WHERE r.id = ?1",
+        [refund_id],
+        parse_refund_row,
+    )?;
+    record.items = fetch_items_for_refunds(conn, &[refund_id])?
+        .remove(&refund_id)
+        .unwrap_or_default();
+    Ok(record)
+}
+
+#[tauri::command]
+pub(crate) fn create_refund(
+    database: tauri::State<'_, Database>,
+    payload: CreateRefundPayload,
+) -> AppResult<RefundRecord> {
+    let mut conn = database.lock()?;
+    record_refund(&mut conn, payload)
+}
+
+fn record_refund(conn: &mut Connection, payload: CreateRefundPayload) -> AppResult<RefundRecord> {
+    let CreateRefundPayload {
+        payment_id,
+        cashier_name,
+        reason,
+        lines,
+    } = payload;
+    let cleaned_cashier = cashier_name.trim().to_string();
+    if cleaned_cashier.is_empty() {
+        return Err(AppError::validation(
+            "cashier_name_required",
+            "cashierName",
+            "Cashier name is required",
+        ));
+    }
+    if lines.is_empty() {
+        return Err(AppError::validation(
+            "refund_lines_empty",
+            "lines",
+            "Refund must contain at least one line",
+        ));
+    }
+    let cleaned_reason = reason
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty());
+    let tx = conn.transaction()?;
+    let payment: Option<(String, Option<String>)> = tx
+        .query_row(
+            "SELECT invoice_number, voided_at FROM payments WHERE id = ?1",
+            [payment_id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()?;
+    let Some((invoice_number, voided_at)) = payment else {
+        return Err(AppError::not_found(
+            "payment_not_found",
+            "Payment not found",
+        ));
+    };
+    if voided_at.is_some() {
+        return Err(AppError::conflict(
+            "payment_voided",
+            "Voided payments cannot be refunded",
+        ));
+    }
+
+    let mut seen = HashSet::new();
+    let mut resolved = Vec::with_capacity(lines.len());
+    for (index, line) in lines.iter().enumerate() {
+        if !seen.insert(line.payment_item_id) {
+            return Err(AppError::validation(
+                "refund_line_duplicated",
+                format!("lines[{}].paymentItemId", index),
+                "Each sold line can appear only once per refund",
+            ));
+        }
+        let requested = scale_quantity(line.quantity);
+        if !line.quantity.is_finite() || requested <= 0 {
+            return Err(AppError::validation(
+                "refund_quantity_invalid",
+                format!("lines[{}].quantity", index),
+                "Refund quantity must be greater than 0",
+            ));
+        }
+        let sold = fetch_sold_line(&tx, payment_id, line.payment_item_id)?.ok_or_else(|| {
+            AppError::not_found(
+                "refund_line_not_in_payment",
+                "Line does not belong to this payment",
+            )
+            .with_field(format!("lines[{}].paymentItemId", index))
+        })?;
+        let already = fetch_refunded_so_far(&tx, line.payment_item_id)?;
+        let sold_scaled = scale_quantity(sold.quantity);
+        let remaining = sold_scaled - already.scaled_quantity;
+        if requested > remaining {
+            return Err(AppError::validation(
+                "refund_quantity_exceeds_sold",
+                format!("lines[{}].quantity", index),
+                "Refund quantity exceeds what remains refundable on this line",
+            ));
+        }
+        // The final refund of a line takes whatever amount is left so rounding
+        // across several partial refunds never pays back more than was taken.
+        let amount = if requested == remaining {
+            sold.net_amount - already.amount
+        } else {
+            sold.net_amount * requested / sold_scaled
+        };
+        resolved.push((line.payment_item_id, line.quantity, sold, amount));
+    }
+
+    let refund_total: i64 = resolved.iter().map(|(_, _, _, amount)| *amount).sum();
+    let sequence: i64 = tx.query_row(
+        "SELECT COUNT(*) + 1 FROM refunds WHERE payment_id = ?1",
+        [payment_id],
+        |row| row.get(0),
+    )?;
+    let refund_number = format!("{}-TH{}", invoice_number, sequence);
+    let moment = store_now(&tx)?;
+    tx.execute(
+        "INSERT INTO refunds (
+            payment_id, refund_number, cashier_name, reason, total, created_at, business_date
+        )
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        (
+            payment_id,
+            refund_number.as_str(),
+            cleaned_cashier.as_str(),
+            cleaned_reason.as_deref(),
+            refund_total,
+            moment.timestamp.as_str(),
+            moment.business_date.as_str(),
+        ),
+    )?;
+    let refund_id = tx.last_insert_rowid();
+    let returned: Vec<(i64, f64)> = resolved
+        .iter()
+        .filter_map(|(_, quantity, sold, _)| {
+            sold.product_id.map(|id| (id, *quantity * sold.unit_factor))
+        })
+        .collect();
+    record_movements(
+        &tx,
+        &moment,
+        &MovementSource {
+            reason: MovementReason::Refund,
+            source_id: Some(refund_id),
+            actor: Some(&cleaned_cashier),
+            note: None,
+        },
+        returned,
+    )?;
+    for (payment_item_id, quantity, sold, amount) in resolved {
+        tx.execute(
+            "INSERT INTO refund_items (
+                refund_id, payment_item_id, product_id, name, quantity, unit_price, amount
+            )
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            (
+                refund_id,
+                payment_item_id,
+                sold.product_id,
+                sold.name.as_str(),
+                quantity,
+                sold.unit_price,
+                amount,
+            ),
+        )?;
+    }
+    tx.commit()?;
+    load_refund_by_id(conn, refund_id)
+}
+
+#[tauri::command]
+pub(crate) fn list_refunds(
+    database: tauri::State<'_, Database>,
+    payload: Option<ListRefundsPayload>,
+) -> AppResult<Page<RefundRecord>> {
+    let conn = database.lock()?;
+    load_refund_page(&conn, payload.unwrap_or_default())
+}
+
+fn load_refund_page(
+    conn: &Connection,
+    payload: ListRefundsPayload,
+) -> AppResult<Page<RefundRecord>> {
+    let ListRefundsPayload {
+        payment_id,
+        cursor,
+        limit,
+    } = payload;
+    let limit = page_limit(limit)?;
+    let (cursor_created_at, cursor_id) = match cursor {
+        Some(cursor) => (Some(cursor.created_at), Some(cursor.id)),
+        None => (None, None),
+    };
+    let mut statement = conn.prepare(
+        "SELECT r.id, r.payment_id, r.refund_number, p.invoice_number, r.cashier_name,
+                r.reason, r.total, r.created_at, r.business_date
+         FROM refunds r
+         JOIN payments p ON p.id = r.payment_id
+         WHERE (?1 IS NULL OR r.payment_id = ?1)
+           AND (?2 IS NULL OR (r.created_at, r.id) < (?2, ?3))
+         ORDER BY r.created_at DESC, r.id DESC
+         LIMIT ?4",
+    )?;
+    let mut records = statement
+        .query_map(
+            (payment_id, cursor_created_at, cursor_id, limit + 1),
+            parse_refund_row,
+        )?
+        .collect::<Result<Vec<_>, _>>()?;
+    let next_cursor = take_page(&mut records, limit, |record| PageCursor {
+        created_at: record.created_at.clone(),
+        id: record.id,
+    });
+    let ids: Vec<i64> = records.iter().map(|record| record.id).collect();
+    let mut items = fetch_items_for_refunds(conn, &ids)?;
+    for record in records.iter_mut() {
+        record.items = items.remove(&record.id).unwrap_or_default();
+    }
+    Ok(Page {
+        items: records,
+        next_cursor,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // One sale of 3 x 10000 with no discount or tax, and a second sale to
+    // page over.
+    fn sold_database() -> Connection {
+        let mut conn = Connection::open_in_memory().unwrap();
+        crate::initialize_schema(&mut conn).unwrap();
+        conn.execute_batch(
+            "INSERT INTO products (id, name, price) VALUES (1, 'Nước suối', 10000);
+             INSERT INTO payments (id, invoice_number, cashier_name, subtotal, tax, total,
+                                   discount, paid_cash, change_due, created_at)
+             VALUES (1, 'HD0001', 'Linh', 30000, 0, 30000, 0, 30000, 0, '2026-01-05 09:00:00'),
+                    (2, 'HD0002', 'Linh', 10000, 0, 10000, 0, 10000, 0, '2026-01-05 09:30:00');
+             INSERT INTO payment_items (id, payment_id, product_id, name, quantity, price,
+                                        line_subtotal, line_discount, unit_factor)
+             VALUES (1, 1, 1, 'Nước suối', 3, 10000, 30000, 0, 1),
+                    (2, 2, 1, 'Nước suối', 1, 10000, 10000, 0, 1);",
+        )
+        .unwrap();
+        conn
+    }
+
+    fn refund(
+        conn: &mut Connection,
+        payment_id: i64,
+        payment_item_id: i64,
+        quantity: f64,
+    ) -> AppResult<RefundRecord> {
+        record_refund(
+            conn,
+            CreateRefundPayload {
+                payment_id,
+                cashier_name: "Linh".into(),
+                reason: None,
+                lines: vec![RefundLineInput {
+                    payment_item_id,
+                    quantity,
+                }],
+            },
+        )
+    }
+
+    #[test]
+    fn partial_refunds_add_up_to_the_line() {
+        let mut conn = sold_database();
+        let first = refund(&mut conn, 1, 1, 1.0).unwrap();
+        let second = refund(&mut conn, 1, 1, 2.0).unwrap();
+        assert_eq!(first.total + second.total, 30000);
+        assert_eq!(second.refund_number, "HD0001-TH2");
+
+        let over = refund(&mut conn, 1, 1, 0.5);
+        assert_eq!(
+            over.err().map(|err| err.code),
+            Some("refund_quantity_exceeds_sold")
+        );
+    }
+
+    #[test]
+    fn refunds_cannot_exceed_the_sold_quantity() {
+        let mut conn = sold_database();
+        let over = refund(&mut conn, 1, 1, 4.0);
+        assert_eq!(
+            over.err().map(|err| err.code),
+            Some("refund_quantity_exceeds_sold")
+        );
+        let elsewhere = refund(&mut conn, 1, 2, 1.0);
+        assert_eq!(
+            elsewhere.err().map(|err| err.code),
+            Some("refund_line_not_in_payment")
+        );
+        let count: i64 = conn
+            .query_row("SELECT COUNT(*) FROM refunds", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(count, 0);
+    }
+
+    #[test]
+    fn voided_payments_cannot_be_refunded() {
+        let mut conn = sold_database();
+        conn.execute(
+            "UPDATE payments SET voided_at = '2026-01-05 10:00:00', voided_by = 'Linh',
+                                 void_reason = 'Nhập sai'
+             WHERE id = 1",
+            [],
+        )
+        .unwrap();
+        let refused = refund(&mut conn, 1, 1, 1.0);
+        assert_eq!(refused.err().map(|err| err.code), Some("payment_voided"));
+    }
+
+    #[test]
+    fn refund_list_pages_newest_first() {
+        let mut conn = sold_database();
+        let older = refund(&mut conn, 1, 1, 1.0).unwrap();
+        let newer = refund(&mut conn, 2, 2, 1.0).unwrap();
+        let page = |cursor| {
+            load_refund_page(
+                &conn,
+                ListRefundsPayload {
+                    payment_id: None,
+                    cursor,
+                    limit: Some(1),
+                },
+            )
+            .unwrap()
+        };
+
+        let first = page(None);
+        assert_eq!(first.items.len(), 1);
+        assert_eq!(first.items[0].id, newer.id);
+        assert_eq!(first.items[0].items.len(), 1);
+        let second = page(first.next_cursor);
+        assert_eq!(second.items[0].id, older.id);
+        assert!(second.next_cursor.is_none());
+    }
+}
@@ -0,0 +1,719 @@
+use crate::error::{AppError, AppResult};
+use crate::payment_search::normalize_date;
+use crate::pricing::compute_line_subtotal;
+use crate::spreadsheet::{normalize_file_path, write_table, Cell, SheetFormat};
+use crate::Database;
+use rusqlite::{Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+const SALES_EXPORT_HEADERS: [&str; 20] = [
+    "Số hoá đơn",
+    "Ngày làm việc",
+    "Thời gian",
+    "Thu ngân",
+    "Trạng thái",
+    "Ghi chú",
+    "Tạm tính",
+    "Giảm giá",
+    "Thuế",
+    "Tổng tiền",
+    "Tiền khách đưa",
+    "Tiền thừa",
+    "Mã sản phẩm",
+    "Tên sản phẩm",
+    "Số lượng",
+    "Giá gốc",
+    "Giá sửa",
+    "Đơn giá",
+    "Thành tiền",
+    "Giảm giá dòng",
+];
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum ReportGrouping {
+    #[default]
+    Day,
+    Week,
+    Month,
+    Cashier,
+    Hour,
+}
+
+impl ReportGrouping {
+    fn bucket_sql(self) -> &'static str {
+        match self {
+            ReportGrouping::Day => "business_date",
+            ReportGrouping::Week => "date(business_date, 'weekday 0', '-6 days')",
+            ReportGrouping::Month => "substr(business_date, 1, 7)",
+            ReportGrouping::Cashier => "cashier_name",
+            ReportGrouping::Hour => "substr(created_at, 12, 2)",
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ExportSalesPayload {
+    date_from: String,
+    date_to: String,
+    format: SheetFormat,
+    path: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ExportSummary {
+    path: String,
+    payments: usize,
+    lines: usize,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct RevenueReportPayload {
+    date_from: String,
+    date_to: String,
+    group_by: Option<ReportGrouping>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct RevenueBucket {
+    key: String,
+    count: i64,
+    revenue: i64,
+    refunds: i64,
+    discounts: i64,
+    paid: i64,
+    change: i64,
+    average: i64,
+    max_total: Option<i64>,
+    min_total: Option<i64>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct CategoryBucket {
+    category_id: Option<i64>,
+    name: Option<String>,
+    parent_id: Option<i64>,
+    quantity: f64,
+    revenue: i64,
+    refunds: i64,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct MarginReportPayload {
+    date_from: String,
+    date_to: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct MarginBucket {
+    key: Option<String>,
+    name: Option<String>,
+    quantity: f64,
+    revenue: i64,
+    cost: i64,
+    gross_profit: i64,
+    margin_percent: Option<f64>,
+    uncosted_lines: i64,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct MarginReport {
+    date_from: String,
+    date_to: String,
+    summary: MarginBucket,
+    days: Vec<MarginBucket>,
+    products: Vec<MarginBucket>,
+    categories: Vec<MarginBucket>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ReceiptExtreme {
+    payment_id: i64,
+    invoice_number: String,
+    total: i64,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct RevenueReport {
+    date_from: String,
+    date_to: String,
+    summary: RevenueBucket,
+    largest_receipt: Option<ReceiptExtreme>,
+    smallest_receipt: Option<ReceiptExtreme>,
+    groups: Vec<RevenueBucket>,
+    cashiers: Vec<RevenueBucket>,
+    categories: Vec<CategoryBucket>,
+}
+
+// Voided sales are left out entirely, together with any refunds made against
+// them; other refunds count as negative revenue on the business day they were
+// paid back. Hours are the store's local wall clock.
+fn aggregate(
+    conn: &Connection,
+    bucket_sql: &str,
+    date_from: &str,
+    date_to: &str,
+) -> AppResult<Vec<RevenueBucket>> {
+    let mut statement = conn.prepare(&format!(
+        "WITH entries AS (
+            SELECT cashier_name, created_at, business_date, total AS amount, discount,
+                   paid_cash, change_due, 1 AS is_sale
+            FROM payments
+            WHERE voided_at IS NULL AND business_date BETWEEN ?1 AND ?2
+            UNION ALL
+            SELECT r.cashier_name, r.created_at, r.business_date, -r.total, 0, 0, 0, 0
+            FROM refunds r
+            JOIN payments p ON p.id = r.payment_id AND p.voided_at IS NULL
+            WHERE r.business_date BETWEEN ?1 AND ?2
+        )
+        SELECT CAST({} AS TEXT) AS bucket,
+               SUM(is_sale),
+               SUM(amount),
+               SUM(CASE WHEN is_sale = 0 THEN -amount ELSE 0 END),
+               SUM(discount),
+               SUM(paid_cash),
+               SUM(change_due),
+               SUM(CASE WHEN is_sale = 1 THEN amount ELSE 0 END),
+               MAX(CASE WHEN is_sale = 1 THEN amount END),
+               MIN(CASE WHEN is_sale = 1 THEN amount END)
+        FROM entries
+        GROUP BY bucket
+        ORDER BY bucket",
+        bucket_sql
+    ))?;
+    let rows = statement.query_map([date_from, date_to], |row| {
+        let count: i64 = row.get(1)?;
+        let sales: i64 = row.get(7)?;
+        Ok(RevenueBucket {
+            key: row.get(0)?,
+            count,
+            revenue: row.get(2)?,
+            refunds: row.get(3)?,
+            discounts: row.get(4)?,
+            paid: row.get(5)?,
+            change: row.get(6)?,
+            average: if count > 0 {
+                (sales + count / 2) / count
+            } else {
+                0
+            },
+            max_total: row.get(8)?,
+            min_total: row.get(9)?,
+        })
+    })?;
+    rows.collect::<Result<Vec<_>, _>>().map_err(AppError::from)
+}
+
+// Line totals net of each line's own discount, filed under each product's
+// current category. Discounts only exist per line, so the categories add up to
+// the revenue summary before tax, which is not split across them. Unlinked or
+// uncategorized lines share the bucket without a category id. Quantities are
+// in each product's base unit.
+fn category_breakdown(
+    conn: &Connection,
+    date_from: &str,
+    date_to: &str,
+) -> AppResult<Vec<CategoryBucket>> {
+    let mut statement = conn.prepare(
+        "WITH lines AS (
+            SELECT i.product_id,
+                   COALESCE(i.quantity_decimal, i.quantity) * i.unit_factor AS quantity,
+                   COALESCE(
+                       i.line_subtotal,
+                       CAST(i.price * COALESCE(i.quantity_decimal, i.quantity) AS INTEGER)
+                   ) - i.line_discount AS amount,
+                   0 AS refunded
+            FROM payment_items i
+            JOIN payments p ON p.id = i.payment_id
+            WHERE p.voided_at IS NULL AND p.business_date BETWEEN ?1 AND ?2
+            UNION ALL
+            SELECT ri.product_id, -ri.quantity * i.unit_factor, -ri.amount, ri.amount
+            FROM refund_items ri
+            JOIN refunds r ON r.id = ri.refund_id
+            JOIN payments p ON p.id = r.payment_id AND p.voided_at IS NULL
+            JOIN payment_items i ON i.id = ri.payment_item_id
+            WHERE r.business_date BETWEEN ?1 AND ?2
+        )
+        SELECT c.id, c.name, c.parent_id,
+               SUM(l.quantity), SUM(l.amount), SUM(l.refunded)
+        FROM lines l
+        LEFT JOIN products pr ON pr.id = l.product_id
+        LEFT JOIN categories c ON c.id = pr.category_id
+        GROUP BY c.id
+        ORDER BY c.id IS NULL ASC, SUM(l.amount) DESC",
+    )?;
+    let rows = statement.query_map([date_from, date_to], |row| {
+        Ok(CategoryBucket {
+            category_id: row.get(0)?,
+            name: row.get(1)?,
+            parent_id: row.get(2)?,
+            quantity: row.get(3)?,
+            revenue: row.get(4)?,
+            refunds: row.get(5)?,
+        })
+    })?;
+    rows.collect::<Result<Vec<_>, _>>().map_err(AppError::from)
+}
+
+// Revenue and cost per line after line discounts. Refunded lines take back
+// their share of the original line's cost on the day of the refund. Lines sold
+// before the product had a cost count as zero cost and are reported in
+// uncosted_lines so the margin can be read with care.
+fn margin_breakdown(
+    conn: &Connection,
+    key_sql: &str,
+    name_sql: &str,
+    order_sql: &str,
+    date_from: &str,
+    date_to: &str,
+) -> AppResult<Vec<MarginBucket>> {
+    let mut statement = conn.prepare(&format!(
+        "WITH lines AS (
+            SELECT i.product_id, i.name, p.business_date,
+                   COALESCE(i.quantity_decimal, i.quantity) * i.unit_factor AS quantity,
+                   COALESCE(
+                       i.line_subtotal,
+                       CAST(i.price * COALESCE(i.quantity_decimal, i.quantity) AS INTEGER)
+                   ) - i.line_discount AS amount,
+                   CAST(ROUND(i.unit_cost * COALESCE(i.quantity_decimal, i.quantity))
+                        AS INTEGER) AS cost
+            FROM payment_items i
+            JOIN payments p ON p.id = i.payment_id
+            WHERE p.voided_at IS NULL AND p.business_date BETWEEN ?1 AND ?2
+            UNION ALL
+            SELECT ri.product_id, ri.name, r.business_date, -ri.quantity * i.unit_factor,
+                   -ri.amount,
+                   -CAST(ROUND(i.unit_cost * ri.quantity) AS INTEGER)
+            FROM refund_items ri
+            JOIN refunds r ON r.id = ri.refund_id
+            JOIN payments p ON p.id = r.payment_id AND p.voided_at IS NULL
+            JOIN payment_items i ON i.id = ri.payment_item_id
+            WHERE r.business_date BETWEEN ?1 AND ?2
+        )
+        SELECT CAST({key} AS TEXT) AS bucket,
+               {name},
+               COALESCE(SUM(l.quantity), 0),
+               COALESCE(SUM(l.amount), 0),
+               COALESCE(SUM(l.cost), 0),
+               COALESCE(SUM(l.cost IS NULL), 0)
+        FROM lines l
+        LEFT JOIN products pr ON pr.id = l.product_id
+        LEFT JOIN categories c ON c.id = pr.category_id
+        GROUP BY bucket
+        ORDER BY {order}",
+        key = key_sql,
+        name = name_sql,
+        order = order_sql,
+    ))?;
+    let rows = statement.query_map([date_from, date_to], |row| {
+        let revenue: i64 = row.get(3)?;
+        let cost: i64 = row.get(4)?;
+        let gross_profit = revenue - cost;
+        Ok(MarginBucket {
+            key: row.get(0)?,
+            name: row.get(1)?,
+            quantity: row.get(2)?,
+            revenue,
+            cost,
+            gross_profit,
+            margin_percent: (revenue != 0)
+                .then(|| (gross_profit as f64 * 1000.0 / revenue as f64).round() / 10.0),
+            uncosted_lines: row.get(5)?,
+        })
+    })?;
+    rows.collect::<Result<Vec<_>, _>>().map_err(AppError::from)
+}
+
+fn find_extreme(
+    conn: &Connection,
+    order: &str,
+    date_from: &str,
+    date_to: &str,
+) -> AppResult<Option<ReceiptExtreme>> {
+    conn.query_row(
+        &format!(
+            "SELECT id, invoice_number, total
+             FROM payments
+             WHERE voided_at IS NULL AND business_date BETWEEN ?1 AND ?2
+             ORDER BY total {}, id ASC
+             LIMIT 1",
+            order
+        ),
+        [date_from, date_to],
+        |row| {
+            Ok(ReceiptExtreme {
+                payment_id: row.get(0)?,
+                invoice_number: row.get(1)?,
+                total: row.get(2)?,
+            })
+        },
+    )
+    .optional()
+    .map_err(AppError::from)
+}
+
+fn resolve_date_range(
+    conn: &Connection,
+    date_from: String,
+    date_to: String,
+) -> AppResult<(String, String)> {
+    let date_from = normalize_date(conn, "dateFrom", Some(date_from))?;
+    let date_to = normalize_date(conn, "dateTo", Some(date_to))?;
+    let (Some(date_from), Some(date_to)) = (date_from, date_to) else {
+        return Err(AppError::validation(
+            "report_date_range_required",
+            "dateFrom",
+            "A report needs both a start and an end date",
+        ));
+    };
+    if date_from > date_to {
+        return Err(AppError::validation(
+            "search_date_range_invalid",
+            "dateTo",
+            "End date cannot be before start date",
+        ));
+    }
+    Ok((date_from, date_to))
+}
+
+#[tauri::command]
+pub(crate) fn revenue_report(
+    database: tauri::State<'_, Database>,
+    payload: RevenueReportPayload,
+) -> AppResult<RevenueReport> {
+    let conn = database.lock()?;
+    build_revenue_report(&conn, payload)
+}
+
+fn build_revenue_report(
+    conn: &Connection,
+    payload: RevenueReportPayload,
+) -> AppResult<RevenueReport> {
+    let (date_from, date_to) = resolve_date_range(conn, payload.date_from, payload.date_to)?;
+    let grouping = payload.group_by.unwrap_or_default();
+    let summary = aggregate(conn, "'total'", &date_from, &date_to)?
+        .pop()
+        .unwrap_or(RevenueBucket {
+            key: "total".into(),
+            count: 0,
+            revenue: 0,
+            refunds: 0,
+            discounts: 0,
+            paid: 0,
+            change: 0,
+            average: 0,
+            max_total: None,
+            min_total: None,
+        });
+    Ok(RevenueReport {
+        largest_receipt: find_extreme(conn, "DESC", &date_from, &date_to)?,
+        smallest_receipt: find_extreme(conn, "ASC", &date_from, &date_to)?,
+        groups: aggregate(conn, grouping.bucket_sql(), &date_from, &date_to)?,
+        cashiers: aggregate(
+            conn,
+            ReportGrouping::Cashier.bucket_sql(),
+            &date_from,
+            &date_to,
+        )?,
+        categories: category_breakdown(conn, &date_from, &date_to)?,
+        summary,
+        date_from,
+        date_to,
+    })
+}
+
+#[tauri::command]
+pub(crate) fn margin_report(
+    database: tauri::State<'_, Database>,
+    payload: MarginReportPayload,
+) -> AppResult<MarginReport> {
+    let conn = database.lock()?;
+    build_margin_report(&conn, payload)
+}
+
+fn build_margin_report(conn: &Connection, payload: MarginReportPayload) -> AppResult<MarginReport> {
+    let (date_from, date_to) = resolve_date_range(conn, payload.date_from, payload.date_to)?;
+    let breakdown = |key: &str, name: &str, order: &str| {
+        margin_breakdown(conn, key, name, order, &date_from, &date_to)
+    };
+    let summary = breakdown("'total'", "NULL", "bucket")?
+        .pop()
+        .unwrap_or(MarginBucket {
+            key: Some("total".into()),
+            name: None,
+            quantity: 0.0,
+            revenue: 0,
+            cost: 0,
+            gross_profit: 0,
+            margin_percent: None,
+            uncosted_lines: 0,
+        });
+    let days = breakdown("l.business_date", "NULL", "bucket")?;
+    let products = breakdown(
+        "l.product_id",
+        "COALESCE(pr.name, MAX(l.name))",
+        "bucket IS NULL ASC, SUM(l.amount) - COALESCE(SUM(l.cost), 0) DESC",
+    )?;
+    let categories = breakdown(
+        "c.id",
+        "c.name",
+        "bucket IS NULL ASC, SUM(l.amount) - COALESCE(SUM(l.cost), 0) DESC",
+    )?;
+    Ok(MarginReport {
+        summary,
+        days,
+        products,
+        categories,
+        date_from,
+        date_to,
+    })
+}
+
+#[tauri::command]
+pub(crate) fn export_sales(
+    database: tauri::State<'_, Database>,
+    payload: ExportSalesPayload,
+) -> AppResult<ExportSummary> {
+    let path = normalize_file_path(&payload.path)?;
+    let conn = database.lock()?;
+    let (date_from, date_to) = resolve_date_range(&conn, payload.date_from, payload.date_to)?;
+    let mut statement = conn.prepare(
+        "SELECT p.id, p.invoice_number, p.business_date, p.created_at, p.cashier_name,
+                p.voided_at IS NOT NULL, p.note, p.subtotal, p.discount, p.tax, p.total,
+                p.paid_cash, p.change_due,
+                i.product_id, i.name, COALESCE(i.quantity_decimal, i.quantity),
+                COALESCE(i.base_unit_price, i.price), i.edited_unit_price, i.price,
+                i.line_subtotal, i.line_discount
+         FROM payments p
+         JOIN payment_items i ON i.payment_id = p.id
+         WHERE p.business_date BETWEEN ?1 AND ?2
+         ORDER BY p.business_date ASC, p.created_at ASC, p.id ASC, i.id ASC",
+    )?;
+    let mut payment_ids = HashSet::new();
+    let mut rows = Vec::new();
+    let mut query = statement.query([&date_from, &date_to])?;
+    while let Some(row) = query.next()? {
+        payment_ids.insert(row.get::<_, i64>(0)?);
+        let voided: bool = row.get(5)?;
+        let quantity: f64 = row.get(15)?;
+        let unit_price: i64 = row.get(18)?;
+        let line_subtotal: Option<i64> = row.get(19)?;
+        rows.push(vec![
+            Cell::Text(row.get(1)?),
+            Cell::from(row.get::<_, Option<String>>(2)?),
+            Cell::Text(row.get(3)?),
+            Cell::Text(row.get(4)?),
+            Cell::Text(if voided { "Đã huỷ" } else { "Hoàn tất" }.into()),
+            Cell::from(row.get::<_, Option<String>>(6)?),
+            Cell::Integer(row.get(7)?),
+            Cell::Integer(row.get(8)?),
+            Cell::Integer(row.get(9)?),
+            Cell::Integer(row.get(10)?),
+            Cell::Integer(row.get(11)?),
+            Cell::Integer(row.get(12)?),
+            Cell::from(row.get::<_, Option<i64>>(13)?),
+            Cell::Text(row.get(14)?),
+            Cell::Decimal(quantity),
+            Cell::Integer(row.get(16)?),
+            Cell::from(row.get::<_, Option<i64>>(17)?),
+            Cell::Integer(unit_price),
+            Cell::Integer(
+                line_subtotal.unwrap_or_else(|| compute_line_subtotal(unit_price, quantity)),
+            ),
+            Cell::Integer(row.get(20)?),
+        ]);
+    }
+    write_table(
+        path,
+        payload.format,
+        "Bán hàng",
+        &SALES_EXPORT_HEADERS,
+        &rows,
+    )?;
+    Ok(ExportSummary {
+        path: path.to_string_lossy().into_owned(),
+        payments: payment_ids.len(),
+        lines: rows.len(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Two business days of sales:
+    // - HD1 sells 2 coffees with a line discount, one of which is refunded
+    //   the next day;
+    // - HD2 is voided after a refund was paid against it;
+    // - HD3 sells bread that has no cost yet.
+    // Coffee's cost has gone up since, which must not reach past sales.
+    fn sales_database() -> Connection {
+        let mut conn = Connection::open_in_memory().unwrap();
+        crate::initialize_schema(&mut conn).unwrap();
+        conn.execute_batch(
+            "INSERT INTO categories (id, name) VALUES (1, 'Đồ uống');
+             INSERT INTO products (id, name, price, cost_price, category_id)
+             VALUES (1, 'Cà phê', 20000, 12000, 1), (2, 'Bánh mì', 15000, NULL, NULL);
+             INSERT INTO payments (id, invoice_number, cashier_name, subtotal, tax, total,
+                                   discount, paid_cash, change_due, created_at,
+                                   business_date, voided_at)
+             VALUES (1, 'HD1', 'Linh', 40000, 0, 35000, 5000, 50000, 15000,
+                     '2026-01-05 08:15:00+07:00', '2026-01-05', NULL),
+                    (2, 'HD2', 'Vi', 20000, 0, 20000, 0, 20000, 0,
+                     '2026-01-05 09:00:00+07:00', '2026-01-05', '2026-01-05 09:30:00+07:00'),
+                    (3, 'HD3', 'Vi', 45000, 0, 45000, 0, 50000, 5000,
+                     '2026-01-06 10:00:00+07:00', '2026-01-06', NULL);
+             INSERT INTO payment_items (id, payment_id, product_id, name, quantity, price,
+                                        quantity_decimal, line_subtotal, line_discount,
+                                        unit_factor, unit_cost)
+             VALUES (1, 1, 1, 'Cà phê', 2, 20000, 2, 40000, 5000, 1, 8000),
+                    (2, 2, 1, 'Cà phê', 1, 20000, 1, 20000, 0, 1, 8000),
+                    (3, 3, 2, 'Bánh mì', 3, 15000, 3, 45000, 0, 1, NULL);
+             INSERT INTO refunds (id, payment_id, refund_number, cashier_name, total,
+                                  created_at, business_date)
+             VALUES (1, 2, 'HD2-TH1', 'Vi', 20000, '2026-01-05 09:10:00+07:00', '2026-01-05'),
+                    (2, 1, 'HD1-TH1', 'Linh', 17500, '2026-01-06 08:00:00+07:00', '2026-01-06');
+             INSERT INTO refund_items (refund_id, payment_item_id, product_id, name, quantity,
+                                       unit_price, amount)
+             VALUES (1, 2, 1, 'Cà phê', 1, 20000, 20000),
+                    (2, 1, 1, 'Cà phê', 1, 20000, 17500);",
+        )
+        .unwrap();
+        conn
+    }
+
+    fn revenue(conn: &Connection) -> RevenueReport {
+        build_revenue_report(
+            conn,
+            RevenueReportPayload {
+                date_from: "2026-01-05".into(),
+                date_to: "2026-01-06".into(),
+                group_by: None,
+            },
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn voided_sales_and_their_refunds_are_left_out() {
+        let report = revenue(&sales_database());
+        let summary = &report.summary;
+        assert_eq!(summary.count, 2);
+        assert_eq!(summary.revenue, 35000 + 45000 - 17500);
+        assert_eq!(summary.refunds, 17500);
+        assert_eq!(summary.discounts, 5000);
+        assert_eq!(summary.average, 40000);
+        assert_eq!(
+            report.largest_receipt.map(|receipt| receipt.payment_id),
+            Some(3)
+        );
+        assert_eq!(
+            report.smallest_receipt.map(|receipt| receipt.payment_id),
+            Some(1)
+        );
+    }
+
+    #[test]
+    fn refunds_land_on_the_day_they_were_paid() {
+        let report = revenue(&sales_database());
+        let days: Vec<(&str, i64, i64, i64)> = report
+            .groups
+            .iter()
+            .map(|day| (day.key.as_str(), day.count, day.revenue, day.refunds))
+            .collect();
+        assert_eq!(
+            days,
+            vec![("2026-01-05", 1, 35000, 0), ("2026-01-06", 1, 27500, 17500)]
+        );
+        let cashiers: Vec<(&str, i64)> = report
+            .cashiers
+            .iter()
+            .map(|cashier| (cashier.key.as_str(), cashier.revenue))
+            .collect();
+        assert_eq!(cashiers, vec![("Linh", 17500), ("Vi", 45000)]);
+    }
+
+    #[test]
+    fn categories_use_line_totals_net_of_refunds() {
+        let report = revenue(&sales_database());
+        let categories: Vec<(Option<i64>, f64, i64, i64)> = report
+            .categories
+            .iter()
+            .map(|bucket| {
+                (
+                    bucket.category_id,
+                    bucket.quantity,
+                    bucket.revenue,
+                    bucket.refunds,
+                )
+            })
+            .collect();
+        assert_eq!(
+            categories,
+            vec![(Some(1), 1.0, 17500, 17500), (None, 3.0, 45000, 0)]
+        );
+    }
+
+    fn margin(conn: &Connection, date_from: &str, date_to: &str) -> MarginReport {
+        build_margin_report(
+            conn,
+            MarginReportPayload {
+                date_from: date_from.into(),
+                date_to: date_to.into(),
+            },
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn margin_uses_the_cost_recorded_on_the_sale() {
+        let report = margin(&sales_database(), "2026-01-05", "2026-01-06");
+        // Coffee: 35000 - 17500 refunded at 8000 a cup, not today's 12000.
+        let coffee = &report.products[1];
+        assert_eq!(coffee.key.as_deref(), Some("1"));
+        assert_eq!((coffee.quantity, coffee.revenue), (1.0, 17500));
+        assert_eq!((coffee.cost, coffee.gross_profit), (8000, 9500));
+        assert_eq!(coffee.margin_percent, Some(54.3));
+        assert_eq!(coffee.uncosted_lines, 0);
+
+        // Bread was sold before it had a cost.
+        let bread = &report.products[0];
+        assert_eq!(bread.key.as_deref(), Some("2"));
+        assert_eq!((bread.cost, bread.uncosted_lines), (0, 1));
+
+        let summary = &report.summary;
+        assert_eq!((summary.revenue, summary.cost), (62500, 8000));
+        assert_eq!(summary.uncosted_lines, 1);
+    }
+
+    #[test]
+    fn refunds_take_back_cost_on_their_own_day() {
+        let report = margin(&sales_database(), "2026-01-06", "2026-01-06");
+        let coffee = report
+            .products
+            .iter()
+            .find(|bucket| bucket.key.as_deref() == Some("1"))
+            .unwrap();
+        assert_eq!(
+            (coffee.quantity, coffee.revenue, coffee.cost),
+            (-1.0, -17500, -8000)
+        );
+        let days: Vec<(Option<&str>, i64, i64)> = report
+            .days
+            .iter()
+            .map(|day| (day.key.as_deref(), day.revenue, day.cost))
+            .collect();
+        assert_eq!(days, vec![(Some("2026-01-06"), 27500, -8000)]);
+    }
+}
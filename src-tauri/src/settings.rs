@@ -0,0 +1,38 @@
+use crate::error::{AppError, AppResult};
+use rusqlite::{Connection, OptionalExtension};
+use serde::{de::DeserializeOwned, Serialize};
+
+pub(crate) fn load_setting<T: DeserializeOwned>(
+    conn: &Connection,
+    key: &str,
+) -> AppResult<Option<T>> {
+    let raw: Option<String> = conn
+        .query_row("SELECT value FROM settings WHERE key = ?1", [key], |row| {
+            row.get(0)
+        })
+        .optional()?;
+    raw.map(|value| {
+        serde_json::from_str(&value).map_err(|err| {
+            AppError::storage(
+                "setting_corrupt",
+                format!("Setting {} is invalid: {}", key, err),
+            )
+        })
+    })
+    .transpose()
+}
+
+pub(crate) fn store_setting<T: Serialize>(
+    conn: &Connection,
+    key: &str,
+    value: &T,
+) -> AppResult<()> {
+    let encoded = serde_json::to_string(value)
+        .map_err(|err| AppError::storage("setting_corrupt", err.to_string()))?;
+    conn.execute(
+        "INSERT INTO settings (key, value) VALUES (?1, ?2)
+         ON CONFLICT(key) DO UPDATE SET value = excluded.value, updated_at = CURRENT_TIMESTAMP",
+        (key, encoded.as_str()),
+    )?;
+    Ok(())
+}
@@ -0,0 +1,195 @@
+use crate::error::{AppError, AppResult};
+use calamine::{open_workbook_auto, Data, Reader};
+use rust_xlsxwriter::{Format, Workbook};
+use serde::Deserialize;
+use std::{fs::File, io::Write, path::Path};
+
+const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum SheetFormat {
+    Csv,
+    Xlsx,
+}
+
+impl SheetFormat {
+    pub fn from_path(path: &Path) -> SheetFormat {
+        let extension = path
+            .extension()
+            .and_then(|value| value.to_str())
+            .map(str::to_ascii_lowercase);
+        match extension.as_deref() {
+            Some("xlsx" | "xlsm" | "xls" | "ods") => SheetFormat::Xlsx,
+            _ => SheetFormat::Csv,
+        }
+    }
+}
+
+pub(crate) enum Cell {
+    Text(String),
+    Integer(i64),
+    Decimal(f64),
+    Empty,
+}
+
+impl From<String> for Cell {
+    fn from(value: String) -> Self {
+        Cell::Text(value)
+    }
+}
+
+impl From<i64> for Cell {
+    fn from(value: i64) -> Self {
+        Cell::Integer(value)
+    }
+}
+
+impl From<f64> for Cell {
+    fn from(value: f64) -> Self {
+        Cell::Decimal(value)
+    }
+}
+
+impl<T: Into<Cell>> From<Option<T>> for Cell {
+    fn from(value: Option<T>) -> Self {
+        value.map(Into::into).unwrap_or(Cell::Empty)
+    }
+}
+
+fn export_failed(err: impl std::fmt::Display) -> AppError {
+    AppError::storage("export_failed", err.to_string())
+}
+
+fn import_unreadable(err: impl std::fmt::Display) -> AppError {
+    AppError::validation("import_file_unreadable", "path", err.to_string())
+}
+
+pub(crate) fn normalize_file_path(path: &str) -> AppResult<&Path> {
+    let trimmed = path.trim();
+    if trimmed.is_empty() {
+        return Err(AppError::validation(
+            "file_path_required",
+            "path",
+            "Choose a file",
+        ));
+    }
+    Ok(Path::new(trimmed))
+}
+
+// CSV gets a BOM so Excel opens Vietnamese text as UTF-8 instead of the
+// system code page.
+fn write_csv(path: &Path, headers: &[&str], rows: &[Vec<Cell>]) -> AppResult<()> {
+    let mut file = File::create(path)?;
+    file.write_all(UTF8_BOM)?;
+    let mut writer = csv::Writer::from_writer(file);
+    writer.write_record(headers).map_err(export_failed)?;
+    for row in rows {
+        let record = row.iter().map(|cell| match cell {
+            Cell::Text(value) => value.clone(),
+            Cell::Integer(value) => value.to_string(),
+            Cell::Decimal(value) => value.to_string(),
+            Cell::Empty => String::new(),
+        });
+        writer.write_record(record).map_err(export_failed)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+fn write_xlsx(
+    path: &Path,
+    sheet_name: &str,
+    headers: &[&str],
+    rows: &[Vec<Cell>],
+) -> AppResult<()> {
+    let mut workbook = Workbook::new();
+    let header_format = Format::new().set_bold();
+    let sheet = workbook.add_worksheet();
+    sheet.set_name(sheet_name).map_err(export_failed)?;
+    for (column, header) in headers.iter().enumerate() {
+        sheet
+            .write_string_with_format(0, column as u16, *header, &header_format)
+            .map_err(export_failed)?;
+    }
+    for (index, row) in rows.iter().enumerate() {
+        let row_number = index as u32 + 1;
+        for (column, cell) in row.iter().enumerate() {
+            let column = column as u16;
+            match cell {
+                Cell::Text(value) => sheet.write_string(row_number, column, value),
+                Cell::Integer(value) => sheet.write_number(row_number, column, *value as f64),
+                Cell::Decimal(value) => sheet.write_number(row_number, column, *value),
+                Cell::Empty => continue,
+            }
+            .map_err(export_failed)?;
+        }
+    }
+    sheet.autofit();
+    workbook.save(path).map_err(export_failed)?;
+    Ok(())
+}
+
+pub(crate) fn write_table(
+    path: &Path,
+    format: SheetFormat,
+    sheet_name: &str,
+    headers: &[&str],
+    rows: &[Vec<Cell>],
+) -> AppResult<()> {
+    match format {
+        SheetFormat::Csv => write_csv(path, headers, rows),
+        SheetFormat::Xlsx => write_xlsx(path, sheet_name, headers, rows),
+    }
+}
+
+fn read_csv(path: &Path) -> AppResult<Vec<Vec<String>>> {
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_path(path)
+        .map_err(import_unreadable)?;
+    let mut rows = Vec::new();
+    for record in reader.records() {
+        let record = record.map_err(import_unreadable)?;
+        rows.push(
+            record
+                .iter()
+                .map(|value| value.trim_start_matches('\u{feff}').to_string())
+                .collect(),
+        );
+    }
+    Ok(rows)
+}
+
+fn data_to_text(value: &Data) -> String {
+    match value {
+        Data::Empty => String::new(),
+        Data::String(text) => text.clone(),
+        Data::Int(number) => number.to_string(),
+        Data::Float(number) if number.fract() == 0.0 => format!("{}", *number as i64),
+        Data::Bool(flag) => flag.to_string(),
+        other => other.to_string(),
+    }
+}
+
+fn read_workbook(path: &Path) -> AppResult<Vec<Vec<String>>> {
+    let mut workbook = open_workbook_auto(path).map_err(import_unreadable)?;
+    let range = workbook
+        .worksheet_range_at(0)
+        .ok_or_else(|| import_unreadable("Workbook has no sheets"))?
+        .map_err(import_unreadable)?;
+    Ok(range
+        .rows()
+        .map(|row| row.iter().map(data_to_text).collect())
+        .collect())
+}
+
+// Every cell comes back as text so CSV and workbook imports share one
+// validation path.
+pub(crate) fn read_table(path: &Path) -> AppResult<Vec<Vec<String>>> {
+    match SheetFormat::from_path(path) {
+        SheetFormat::Csv => read_csv(path),
+        SheetFormat::Xlsx => read_workbook(path),
+    }
+}
@@ -0,0 +1,642 @@
+use crate::business_day::store_now;
+use crate::error::{AppError, AppResult};
+use crate::inventory::{on_hand, record_movements, round_quantity, MovementReason, MovementSource};
+use crate::pricing::compute_line_subtotal;
+use crate::product_units::find_unit_by_barcode;
+use crate::{fetch_product_by_id, normalize_barcode, Database};
+use rusqlite::{params_from_iter, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+const STATUS_OPEN: &str = "open";
+const STATUS_APPROVED: &str = "approved";
+const STATUS_CANCELLED: &str = "cancelled";
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct StartStocktakePayload {
+    created_by: String,
+    note: Option<String>,
+    category_id: Option<i64>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct RecordCountPayload {
+    stocktake_id: i64,
+    product_id: Option<i64>,
+    barcode: Option<String>,
+    quantity: Option<f64>,
+    add: Option<bool>,
+    counted_by: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct StocktakeIdPayload {
+    stocktake_id: i64,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct CloseStocktakePayload {
+    stocktake_id: i64,
+    actor: String,
+    zero_uncounted: Option<bool>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct StocktakeLineRecord {
+    product_id: i64,
+    name: String,
+    barcode: Option<String>,
+    expected_quantity: f64,
+    counted_quantity: Option<f64>,
+    variance: Option<f64>,
+    unit_value: i64,
+    variance_value: Option<i64>,
+    counted_by: Option<String>,
+    counted_at: Option<String>,
+}
+
+#[derive(Serialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct StocktakeSummary {
+    line_count: usize,
+    counted_count: usize,
+    shortage_value: i64,
+    surplus_value: i64,
+    net_variance_value: i64,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct StocktakeRecord {
+    id: i64,
+    status: String,
+    note: Option<String>,
+    category_id: Option<i64>,
+    created_by: String,
+    created_at: String,
+    business_date: String,
+    closed_by: Option<String>,
+    closed_at: Option<String>,
+    summary: StocktakeSummary,
+    lines: Vec<StocktakeLineRecord>,
+}
+
+fn require_actor(value: &str, field: &'static str) -> AppResult<String> {
+    let cleaned = value.trim();
+    if cleaned.is_empty() {
+        return Err(AppError::validation(
+            "stocktake_actor_required",
+            field,
+            "The person doing the count is required",
+        ));
+    }
+    Ok(cleaned.to_string())
+}
+
+fn variance_value(unit_value: i64, variance: f64) -> i64 {
+    let value = compute_line_subtotal(unit_value, variance.abs());
+    if variance < 0.0 {
+        -value
+    } else {
+        value
+    }
+}
+
+fn load_lines_for_stocktakes(
+    conn: &Connection,
+    stocktake_ids: &[i64],
+) -> AppResult<HashMap<i64, Vec<StocktakeLineRecord>>> {
+    let mut grouped: HashMap<i64, Vec<StocktakeLineRecord>> = HashMap::new();
+    if stocktake_ids.is_empty() {
+        return Ok(grouped);
+    }
+    let placeholders = vec!["?"; stocktake_ids.len()].join(", ");
+    let mut statement = conn.prepare(&format!(
+        "SELECT l.stocktake_id, l.product_id, p.name, p.barcode, l.expected_quantity,
+                l.counted_quantity, l.unit_value, l.counted_by, l.counted_at
+         FROM stocktake_lines l
+         JOIN products p ON p.id = l.product_id
+         WHERE l.stocktake_id IN ({})
+         ORDER BY l.stocktake_id ASC, p.display_order ASC, p.id ASC",
+        placeholders
+    ))?;
+    let mut rows = statement.query(params_from_iter(stocktake_ids))?;
+    while let Some(row) = rows.next()? {
+        let expected: f64 = row.get(4)?;
+        let counted: Option<f64> = row.get(5)?;
+        let unit_value: i64 = row.get(6)?;
+        let variance = counted.map(|counted| round_quantity(counted - expected));
+        let line = StocktakeLineRecord {
+            product_id: row.get(1)?,
+            name: row.get(2)?,
+            barcode: row.get(3)?,
+            expected_quantity: expected,
+            counted_quantity: counted,
+            variance,
+            unit_value,
+            variance_value: variance.map(|variance| variance_value(unit_value, variance)),
+            counted_by: row.get(7)?,
+            counted_at: row.get(8)?,
+        };
+        grouped.entry(row.get(0)?).or_default().push(line);
+    }
+    Ok(grouped)
+}
+
+fn summarize(lines: &[StocktakeLineRecord]) -> StocktakeSummary {
+    let mut summary = StocktakeSummary {
+        line_count: lines.len(),
+        ..StocktakeSummary::default()
+    };
+    for value in lines.iter().filter_map(|line| line.variance_value) {
+        if value < 0 {
+            summary.shortage_value -= value;
+        } else {
+            summary.surplus_value += value;
+        }
+        summary.net_variance_value += value;
+    }
+    summary.counted_count = lines
+        .iter()
+        .filter(|line| line.counted_quantity.is_some())
+        .count();
+    summary
+}
+
+const STOCKTAKE_COLUMNS: &str = "id, status, note, category_id, created_by, created_at, \
+     business_date, closed_by, closed_at";
+
+fn parse_stocktake_row(row: &rusqlite::Row<'_>) -> Result<StocktakeRecord, rusqlite::Error> {
+    Ok(StocktakeRecord {
+        id: row.get(0)?,
+        status: row.get(1)?,
+        note: row.get(2)?,
+        category_id: row.get(3)?,
+        created_by: row.get(4)?,
+        created_at: row.get(5)?,
+        business_date: row.get(6)?,
+        closed_by: row.get(7)?,
+        closed_at: row.get(8)?,
+        summary: StocktakeSummary::default(),
+        lines: Vec::new(),
+    })
+}
+
+// Fills in the lines of every stocktake with a single query.
+fn hydrate_stocktakes(
+    conn: &Connection,
+    mut records: Vec<StocktakeRecord>,
+) -> AppResult<Vec<StocktakeRecord>> {
+    let ids: Vec<i64> = records.iter().map(|record| record.id).collect();
+    let mut lines = load_lines_for_stocktakes(conn, &ids)?;
+    for record in records.iter_mut() {
+        record.lines = lines.remove(&record.id).unwrap_or_default();
+        record.summary = summarize(&record.lines);
+    }
+    Ok(records)
+}
+
+fn load_stocktake(conn: &Connection, stocktake_id: i64) -> AppResult<StocktakeRecord> {
+    let record = conn
+        .query_row(
+            &format!("SELECT {} FROM stocktakes WHERE id = ?1", STOCKTAKE_COLUMNS),
+            [stocktake_id],
+            parse_stocktake_row,
+        )
+        .optional()?
+        .ok_or_else(|| {
+            AppError::not_found("stocktake_not_found", "Stocktake not found")
+                .with_field("stocktakeId")
+        })?;
+    Ok(hydrate_stocktakes(conn, vec![record])?.remove(0))
+}
+
+fn ensure_open(conn: &Connection, stocktake_id: i64) -> AppResult<()> {
+    let status: String = conn
+        .query_row(
+            "SELECT status FROM stocktakes WHERE id = ?1",
+            [stocktake_id],
+            |row| row.get(0),
+        )
+        .optional()?
+        .ok_or_else(|| {
+            AppError::not_found("stocktake_not_found", "Stocktake not found")
+                .with_field("stocktakeId")
+        })?;
+    if status != STATUS_OPEN {
+        return Err(AppError::conflict(
+            "stocktake_not_open",
+            "Stocktake has already been closed",
+        ));
+    }
+    Ok(())
+}
+
+// Returns the product and how many base units one count stands for, so
+// scanning a carton barcode counts the whole carton.
+fn find_product_for_count(
+    conn: &Connection,
+    payload: &RecordCountPayload,
+) -> AppResult<(i64, f64)> {
+    if let Some(product_id) = payload.product_id {
+        return fetch_product_by_id(conn, product_id)
+            .map(|product| (product.id, 1.0))
+            .map_err(|err| err.with_field("productId"));
+    }
+    let Some(barcode) = normalize_barcode(payload.barcode.clone()) else {
+        return Err(AppError::validation(
+            "stocktake_product_required",
+            "barcode",
+            "Scan a barcode or choose a product",
+        ));
+    };
+    let product_id: Option<i64> = conn
+        .query_row(
+            "SELECT id FROM products WHERE barcode = ?1 AND archived_at IS NULL",
+            [barcode.as_str()],
+            |row| row.get(0),
+        )
+        .optional()?;
+    if let Some(product_id) = product_id {
+        return Ok((product_id, 1.0));
+    }
+    let unit = find_unit_by_barcode(conn, &barcode)?;
+    unit.map(|unit| (unit.product_id, unit.factor))
+        .ok_or_else(|| {
+            AppError::not_found("product_not_found", "No product has this barcode")
+                .with_field("barcode")
+        })
+}
+
+// Lines start with the stock on hand when the session opens as their expected
+// quantity; counting a line moves it to the stock on hand at that moment.
+// Lines are valued at cost, falling back to the selling price for products
+// that have none yet.
+#[tauri::command]
+pub(crate) fn start_stocktake(
+    database: tauri::State<'_, Database>,
+    payload: StartStocktakePayload,
+) -> AppResult<StocktakeRecord> {
+    let mut conn = database.lock()?;
+    open_stocktake(&mut conn, payload)
+}
+
+fn open_stocktake(
+    conn: &mut Connection,
+    payload: StartStocktakePayload,
+) -> AppResult<StocktakeRecord> {
+    let created_by = require_actor(&payload.created_by, "createdBy")?;
+    let note = payload
+        .note
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty());
+    let tx = conn.transaction()?;
+    let open: Option<i64> = tx
+        .query_row(
+            "SELECT id FROM stocktakes WHERE status = ?1 LIMIT 1",
+            [STATUS_OPEN],
+            |row| row.get(0),
+        )
+        .optional()?;
+    if open.is_some() {
+        return Err(AppError::conflict(
+            "stocktake_already_open",
+            "Finish or cancel the open stocktake first",
+        ));
+    }
+    if let Some(category_id) = payload.category_id {
+        crate::categories::ensure_category_exists(&tx, category_id)?;
+    }
+    let moment = store_now(&tx)?;
+    tx.execute(
+        "INSERT INTO stocktakes (status, note, category_id, created_by, created_at, business_date)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        (
+            STATUS_OPEN,
+            note.as_deref(),
+            payload.category_id,
+            created_by.as_str(),
+            moment.timestamp.as_str(),
+            moment.business_date.as_str(),
+        ),
+    )?;
+    let stocktake_id = tx.last_insert_rowid();
+    tx.execute(
+        "INSERT INTO stocktake_lines (stocktake_id, product_id, expected_quantity, unit_value)
+         SELECT ?1, p.id,
+                ROUND(COALESCE((SELECT SUM(m.quantity) FROM stock_movements m
+                                WHERE m.product_id = p.id), 0), 3),
+                COALESCE(p.cost_price, p.price)
+         FROM products p
+         WHERE p.archived_at IS NULL
+           AND (?2 IS NULL
+                OR p.category_id = ?2
+                OR p.category_id IN (SELECT id FROM categories WHERE parent_id = ?2))",
+        (stocktake_id, payload.category_id),
+    )?;
+    tx.commit()?;
+    load_stocktake(conn, stocktake_id)
+}
+
+// A scan adds one unit (or the given quantity) to the count; typing a quantity
+// against a product replaces it. The shelf is counted as it stands, after any
+// sale or receipt rung up since the session opened, so the line's expected
+// quantity becomes the stock on hand now. Products outside the sheet join it.
+#[tauri::command]
+pub(crate) fn record_count(
+    database: tauri::State<'_, Database>,
+    payload: RecordCountPayload,
+) -> AppResult<StocktakeRecord> {
+    let mut conn = database.lock()?;
+    count_line(&mut conn, payload)
+}
+
+fn count_line(conn: &mut Connection, payload: RecordCountPayload) -> AppResult<StocktakeRecord> {
+    let counted_by = require_actor(&payload.counted_by, "countedBy")?;
+    let add = payload.add.unwrap_or(payload.barcode.is_some());
+    let quantity = round_quantity(payload.quantity.unwrap_or(1.0));
+    if !quantity.is_finite() || quantity < 0.0 || (add && quantity == 0.0) {
+        return Err(AppError::validation(
+            "stocktake_quantity_invalid",
+            "quantity",
+            "Counted quantity cannot be negative",
+        ));
+    }
+    let tx = conn.transaction()?;
+    ensure_open(&tx, payload.stocktake_id)?;
+    let (product_id, factor) = find_product_for_count(&tx, &payload)?;
+    let quantity = round_quantity(quantity * factor);
+    let expected = on_hand(&tx, product_id)?;
+    let moment = store_now(&tx)?;
+    tx.execute(
+        "INSERT INTO stocktake_lines (stocktake_id, product_id, expected_quantity, unit_value)
+         SELECT ?1, id, ?2, COALESCE(cost_price, price) FROM products WHERE id = ?3
+         ON CONFLICT(stocktake_id, product_id) DO NOTHING",
+        (payload.stocktake_id, expected, product_id),
+    )?;
+    tx.execute(
+        "UPDATE stocktake_lines
+         SET counted_quantity = ROUND(
+                 CASE WHEN ?1 THEN COALESCE(counted_quantity, 0) + ?2 ELSE ?2 END, 3),
+             expected_quantity = ?3,
+             counted_by = ?4,
+             counted_at = ?5
+         WHERE stocktake_id = ?6 AND product_id = ?7",
+        (
+            add,
+            quantity,
+            expected,
+            counted_by.as_str(),
+            moment.timestamp.as_str(),
+            payload.stocktake_id,
+            product_id,
+        ),
+    )?;
+    tx.commit()?;
+    load_stocktake(conn, payload.stocktake_id)
+}
+
+#[tauri::command]
+pub(crate) fn get_stocktake(
+    database: tauri::State<'_, Database>,
+    payload: StocktakeIdPayload,
+) -> AppResult<StocktakeRecord> {
+    let conn = database.lock()?;
+    load_stocktake(&conn, payload.stocktake_id)
+}
+
+#[tauri::command]
+pub(crate) fn list_stocktakes(
+    database: tauri::State<'_, Database>,
+) -> AppResult<Vec<StocktakeRecord>> {
+    let conn = database.lock()?;
+    let mut statement = conn.prepare(&format!(
+        "SELECT {} FROM stocktakes ORDER BY id DESC LIMIT 50",
+        STOCKTAKE_COLUMNS
+    ))?;
+    let records = statement
+        .query_map([], parse_stocktake_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+    hydrate_stocktakes(&conn, records)
+}
+
+// Posts every counted variance as a stocktake movement. Each variance is
+// measured against the stock on hand when its line was counted, so sales and
+// receipts rung up during the session stay on the books exactly once.
+// Uncounted lines are left alone unless the caller says they were counted as
+// zero, which happens now.
+#[tauri::command]
+pub(crate) fn approve_stocktake(
+    database: tauri::State<'_, Database>,
+    payload: CloseStocktakePayload,
+) -> AppResult<StocktakeRecord> {
+    let mut conn = database.lock()?;
+    approve(&mut conn, payload)
+}
+
+fn approve(conn: &mut Connection, payload: CloseStocktakePayload) -> AppResult<StocktakeRecord> {
+    let actor = require_actor(&payload.actor, "actor")?;
+    let tx = conn.transaction()?;
+    ensure_open(&tx, payload.stocktake_id)?;
+    let moment = store_now(&tx)?;
+    if payload.zero_uncounted.unwrap_or(false) {
+        tx.execute(
+            "UPDATE stocktake_lines
+             SET counted_quantity = 0,
+                 expected_quantity = ROUND(COALESCE(
+                     (SELECT SUM(m.quantity) FROM stock_movements m
+                      WHERE m.product_id = stocktake_lines.product_id), 0), 3),
+                 counted_by = ?1,
+                 counted_at = ?2
+             WHERE stocktake_id = ?3 AND counted_quantity IS NULL",
+            (
+                actor.as_str(),
+                moment.timestamp.as_str(),
+                payload.stocktake_id,
+            ),
+        )?;
+    }
+    let variances: Vec<(i64, f64)> = load_lines_for_stocktakes(&tx, &[payload.stocktake_id])?
+        .remove(&payload.stocktake_id)
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|line| line.variance.map(|variance| (line.product_id, variance)))
+        .collect();
+    record_movements(
+        &tx,
+        &moment,
+        &MovementSource {
+            reason: MovementReason::Stocktake,
+            source_id: Some(payload.stocktake_id),
+            actor: Some(&actor),
+            note: None,
+        },
+        variances,
+    )?;
+    tx.execute(
+        "UPDATE stocktakes SET status = ?1, closed_by = ?2, closed_at = ?3 WHERE id = ?4",
+        (
+            STATUS_APPROVED,
+            actor.as_str(),
+            moment.timestamp.as_str(),
+            payload.stocktake_id,
+        ),
+    )?;
+    tx.commit()?;
+    load_stocktake(conn, payload.stocktake_id)
+}
+
+#[tauri::command]
+pub(crate) fn cancel_stocktake(
+    database: tauri::State<'_, Database>,
+    payload: CloseStocktakePayload,
+) -> AppResult<StocktakeRecord> {
+    let actor = require_actor(&payload.actor, "actor")?;
+    let conn = database.lock()?;
+    ensure_open(&conn, payload.stocktake_id)?;
+    let moment = store_now(&conn)?;
+    conn.execute(
+        "UPDATE stocktakes SET status = ?1, closed_by = ?2, closed_at = ?3 WHERE id = ?4",
+        (
+            STATUS_CANCELLED,
+            actor.as_str(),
+            moment.timestamp.as_str(),
+            payload.stocktake_id,
+        ),
+    )?;
+    load_stocktake(&conn, payload.stocktake_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::inventory::record_sale;
+    use crate::pricing::NormalizedPaymentItem;
+
+    fn stocked_database() -> Connection {
+        let mut conn = Connection::open_in_memory().unwrap();
+        crate::initialize_schema(&mut conn).unwrap();
+        conn.execute_batch(
+            "INSERT INTO products (id, name, price, barcode) VALUES
+                (1, 'Sữa', 10000, 'SUA1'),
+                (2, 'Bánh', 5000, 'BANH1');",
+        )
+        .unwrap();
+        move_stock(&conn, MovementReason::Receipt, &[(1, 10.0), (2, 4.0)]);
+        conn
+    }
+
+    fn move_stock(conn: &Connection, reason: MovementReason, lines: &[(i64, f64)]) {
+        let moment = store_now(conn).unwrap();
+        let source = MovementSource {
+            reason,
+            source_id: None,
+            actor: Some("Linh"),
+            note: None,
+        };
+        record_movements(conn, &moment, &source, lines.iter().copied()).unwrap();
+    }
+
+    fn sell(conn: &Connection, product_id: i64, quantity: f64) {
+        let moment = store_now(conn).unwrap();
+        let item = NormalizedPaymentItem {
+            product_id: Some(product_id),
+            name: "Sữa".into(),
+            quantity_decimal: quantity,
+            legacy_quantity: quantity as i64,
+            base_unit_price: 10000,
+            edited_unit_price: None,
+            effective_unit_price: 10000,
+            line_subtotal: 10000,
+            line_discount: 0,
+            line_tax: 0,
+            unit_id: None,
+            unit_name: None,
+            unit_factor: 1.0,
+        };
+        record_sale(conn, &moment, 99, &[item]).unwrap();
+    }
+
+    fn start(conn: &mut Connection) -> i64 {
+        open_stocktake(
+            conn,
+            StartStocktakePayload {
+                created_by: "Linh".into(),
+                note: None,
+                category_id: None,
+            },
+        )
+        .unwrap()
+        .id
+    }
+
+    fn count(conn: &mut Connection, stocktake_id: i64, product_id: i64, quantity: f64) {
+        count_line(
+            conn,
+            RecordCountPayload {
+                stocktake_id,
+                product_id: Some(product_id),
+                barcode: None,
+                quantity: Some(quantity),
+                add: None,
+                counted_by: "Linh".into(),
+            },
+        )
+        .unwrap();
+    }
+
+    fn close(conn: &mut Connection, stocktake_id: i64, zero_uncounted: bool) -> StocktakeRecord {
+        approve(
+            conn,
+            CloseStocktakePayload {
+                stocktake_id,
+                actor: "Linh".into(),
+                zero_uncounted: Some(zero_uncounted),
+            },
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn sales_during_the_count_are_not_counted_twice() {
+        let mut conn = stocked_database();
+        let stocktake_id = start(&mut conn);
+
+        // Two sold before the shelf is counted: the 7 left are one short.
+        sell(&conn, 1, 2.0);
+        count(&mut conn, stocktake_id, 1, 7.0);
+        // One more sold after the count stays sold.
+        sell(&conn, 1, 1.0);
+        let record = close(&mut conn, stocktake_id, false);
+
+        let line = &record.lines[0];
+        assert_eq!(line.expected_quantity, 8.0);
+        assert_eq!(line.variance, Some(-1.0));
+        assert_eq!(on_hand(&conn, 1).unwrap(), 6.0);
+        // The uncounted line posts nothing.
+        assert_eq!(on_hand(&conn, 2).unwrap(), 4.0);
+        assert_eq!(record.summary.counted_count, 1);
+    }
+
+    #[test]
+    fn receipts_during_the_count_are_not_counted_twice() {
+        let mut conn = stocked_database();
+        let stocktake_id = start(&mut conn);
+
+        move_stock(&conn, MovementReason::Receipt, &[(1, 5.0)]);
+        count(&mut conn, stocktake_id, 1, 15.0);
+        move_stock(&conn, MovementReason::Receipt, &[(2, 6.0)]);
+        let record = close(&mut conn, stocktake_id, true);
+
+        assert_eq!(record.lines[0].variance, Some(0.0));
+        assert_eq!(on_hand(&conn, 1).unwrap(), 15.0);
+        // Zeroing an uncounted line writes off what is on hand at approval.
+        assert_eq!(record.lines[1].expected_quantity, 10.0);
+        assert_eq!(on_hand(&conn, 2).unwrap(), 0.0);
+    }
+}
@@ -22,7 +22,12 @@ function App() {
           onOpenReport={() => setActiveScreen("report")}
         />
       )}
-      {activeScreen === "product" && <ProductManagement onBack={() => setActiveScreen("pos")} />}
+      {activeScreen === "product" && (
+        <ProductManagement
+          currentCashier={currentCashier}
+          onBack={() => setActiveScreen("pos")}
+        />
+      )}
       {activeScreen === "history" && <InvoiceHistory onBack={() => setActiveScreen("pos")} />}
       {activeScreen === "cashier" && (
         <CashierSelection
@@ -0,0 +1,78 @@
+import { invoke } from "@tauri-apps/api/core";
+import { useCallback, useEffect, useRef, useState } from "react";
+import { translateError } from "../utils/errorMessages";
+
+const PAGE_SIZE = 50;
+
+export const fetchPaymentPage = (filters, cursor = null, limit = PAGE_SIZE) =>
+  invoke("search_payments", { payload: { ...filters, cursor, limit } });
+
+// Tải hoá đơn của một ngày theo từng trang; màn hình gọi loadMore khi cần xem thêm.
+const usePaymentPages = (date, { includeVoided = true } = {}) => {
+  const [items, setItems] = useState([]);
+  const [totalCount, setTotalCount] = useState(0);
+  const [nextCursor, setNextCursor] = useState(null);
+  const [isLoading, setIsLoading] = useState(true);
+  const [error, setError] = useState("");
+  // Bỏ qua kết quả trả về muộn của ngày đã chọn trước đó.
+  const generationRef = useRef(0);
+
+  const loadPage = useCallback(
+    async (cursor) => {
+      const generation = generationRef.current;
+      setIsLoading(true);
+      setError("");
+      try {
+        const page = await fetchPaymentPage(
+          { dateFrom: date, dateTo: date, includeVoided },
+          cursor,
+        );
+        if (generation !== generationRef.current) return;
+        const rows = page?.items ?? [];
+        setItems((current) => (cursor ? [...current, ...rows] : rows));
+        setTotalCount(page?.totalCount ?? 0);
+        setNextCursor(page?.nextCursor ?? null);
+      } catch (loadError) {
+        if (generation !== generationRef.current) return;
+        console.error("Không thể tải danh sách hoá đơn:", loadError);
+        setError(translateError(loadError));
+        if (!cursor) {
+          setItems([]);
+          setTotalCount(0);
+          setNextCursor(null);
+        }
+      } finally {
+        if (generation === generationRef.current) {
+          setIsLoading(false);
+        }
+      }
+    },
+    [date, includeVoided],
+  );
+
+  useEffect(() => {
+    setItems([]);
+    setTotalCount(0);
+    setNextCursor(null);
+    loadPage(null);
+    return () => {
+      generationRef.current += 1;
+    };
+  }, [loadPage]);
+
+  const loadMore = useCallback(() => {
+    if (!nextCursor || isLoading) return;
+    loadPage(nextCursor);
+  }, [isLoading, loadPage, nextCursor]);
+
+  return {
+    items,
+    totalCount,
+    hasMore: Boolean(nextCursor),
+    isLoading,
+    error,
+    loadMore,
+  };
+};
+
+export default usePaymentPages;
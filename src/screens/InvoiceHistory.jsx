@@ -1,6 +1,7 @@
 import { invoke } from "@tauri-apps/api/core";
 import { useEffect, useMemo, useState } from "react";
 import ReceiptPrinter from "../components/ReceiptPrinter";
+import usePaymentPages from "../hooks/usePaymentPages";
 
 const formatCurrency = (value) => `${Number(value || 0).toLocaleString("vi-VN")}đ`;
 const formatQuantity = (value) => {
@@ -35,50 +36,31 @@ const STORE_PROFILE = {
 const DEFAULT_PAPER_WIDTH = "58mm";
 
 const InvoiceHistory = ({ onBack }) => {
-  const [selectedDate, setSelectedDate] = useState(() => new Date().toISOString().slice(0, 10));
-  const [invoices, setInvoices] = useState([]);
+  const [selectedDate, setSelectedDate] = useState(() => new Date().toLocaleDateString("sv-SE"));
+  const {
+    items: invoices,
+    totalCount,
+    hasMore,
+    isLoading,
+    error: loadError,
+    loadMore,
+  } = usePaymentPages(selectedDate);
   const [activeInvoice, setActiveInvoice] = useState(null);
-  const [isLoading, setIsLoading] = useState(true);
   const [pendingPrint, setPendingPrint] = useState(null);
 
   useEffect(() => {
-    let mounted = true;
-    (async () => {
-      try {
-        const rows = await invoke("list_payments");
-        if (!mounted) return;
-        if (Array.isArray(rows)) {
-          setInvoices(rows);
-          setActiveInvoice((current) => {
-            if (!rows.length) return null;
-            if (current) {
-              const existing = rows.find((invoice) => invoice.id === current.id);
-              if (existing) return existing;
-            }
-            return rows[0];
-          });
-        } else {
-          setInvoices([]);
-          setActiveInvoice(null);
-        }
-      } catch (error) {
-        console.error("Không thể tải lịch sử hoá đơn:", error);
-        setInvoices([]);
-        setActiveInvoice(null);
-      } finally {
-        if (mounted) {
-          setIsLoading(false);
-        }
-      }
-    })();
-    return () => {
-      mounted = false;
-    };
+    invoke("current_business_date")
+      .then((businessDate) => {
+        if (businessDate) setSelectedDate(businessDate);
+      })
+      .catch((error) => console.error("Không thể lấy ngày làm việc:", error));
   }, []);
 
   const filteredInvoices = useMemo(() => {
     if (!selectedDate) return invoices;
-    return invoices.filter((invoice) => extractDatePart(invoice.createdAt) === selectedDate);
+    return invoices.filter(
+      (invoice) => (invoice.businessDate ?? extractDatePart(invoice.createdAt)) === selectedDate,
+    );
   }, [invoices, selectedDate]);
 
   useEffect(() => {
@@ -136,8 +118,9 @@ const InvoiceHistory = ({ onBack }) => {
       <div className="history-content">
         <section className="invoice-list">
           <h2>Hoá đơn trong ngày</h2>
+          {loadError && <p className="error-text">{loadError}</p>}
           <div className="invoice-scroll">
-            {isLoading ? (
+            {isLoading && !filteredInvoices.length ? (
               <p className="empty-state">Đang tải dữ liệu...</p>
             ) : filteredInvoices.length ? (
               filteredInvoices.map((invoice) => (
@@ -151,6 +134,7 @@ const InvoiceHistory = ({ onBack }) => {
                     <p>{extractTimePart(invoice.createdAt)}</p>
                   </div>
                   <div className="invoice-meta">
+                    {invoice.voided && <span className="status-pill status-off">Đã huỷ</span>}
                     <span>{invoice.cashierName}</span>
                     <strong>{formatCurrency(invoice.total)}</strong>
                   </div>
@@ -159,6 +143,11 @@ const InvoiceHistory = ({ onBack }) => {
             ) : (
               <p className="empty-state">Không có hoá đơn nào trong ngày.</p>
             )}
+            {hasMore && (
+              <button className="ghost-btn" onClick={loadMore} disabled={isLoading}>
+                {isLoading ? "Đang tải..." : `Tải thêm (${invoices.length}/${totalCount})`}
+              </button>
+            )}
           </div>
         </section>
 
@@ -178,6 +167,15 @@ const InvoiceHistory = ({ onBack }) => {
                 <span>Thu ngân</span>
                 <strong>{activeInvoice.cashierName}</strong>
               </div>
+              {activeInvoice.voided && (
+                <div className="detail-row">
+                  <span>Đã huỷ</span>
+                  <strong>
+                    {formatFullTimestamp(activeInvoice.voidedAt)} · {activeInvoice.voidedBy} ·{" "}
+                    {activeInvoice.voidReason}
+                  </strong>
+                </div>
+              )}
               <div className="detail-items">
                 <p>Sản phẩm</p>
                 <ul>
@@ -1,13 +1,15 @@
 import { invoke } from "@tauri-apps/api/core";
 import { useCallback, useEffect, useMemo, useRef, useState } from "react";
 import ReceiptPrinter from "../components/ReceiptPrinter";
+import { translateError } from "../utils/errorMessages";
 
-const MONEY_ROUNDING_MODE = "floor"; // chuyển thành "round" nếu cần làm tròn gần nhất
 const MAX_EDITABLE_PRICE = 9_999_999;
 const QTY_DECIMAL_PRECISION = 3;
 const DECIMAL_STEP = 0.001;
-const PRECISION_FACTOR = 10 ** QTY_DECIMAL_PRECISION;
+const QTY_PRECISION_FACTOR = 10 ** QTY_DECIMAL_PRECISION;
 const DEFAULT_ALLOW_DECIMAL_QTY = true;
+const QUOTE_DEBOUNCE_MS = 150;
+const PRODUCT_REFRESH_MS = 60_000;
 
 const formatCurrency = (value) => `${Number(value || 0).toLocaleString("vi-VN")}đ`;
 
@@ -36,7 +38,7 @@ const formatQtyValue = (value, allowDecimal) => {
   if (!allowDecimal) {
     return String(Math.max(1, Math.round(value)));
   }
-  const normalized = Math.round(value * PRECISION_FACTOR) / PRECISION_FACTOR;
+  const normalized = Math.round(value * QTY_PRECISION_FACTOR) / QTY_PRECISION_FACTOR;
   return normalized
     .toFixed(QTY_DECIMAL_PRECISION)
     .replace(/\.0+$/, "")
@@ -62,7 +64,7 @@ const validateQtyInput = (value, allowDecimal) => {
   if (allowDecimal && fraction && fraction.length > QTY_DECIMAL_PRECISION) {
     return { value: 0, error: `Tối đa ${QTY_DECIMAL_PRECISION} chữ số thập phân` };
   }
-  const rounded = Math.round(numeric * PRECISION_FACTOR) / PRECISION_FACTOR;
+  const rounded = Math.round(numeric * QTY_PRECISION_FACTOR) / QTY_PRECISION_FACTOR;
   return { value: rounded, error: null };
 };
 
@@ -83,27 +85,26 @@ const validateUnitPriceInput = (value) => {
   return { value: numeric, error: null };
 };
 
-const roundMoney = (value) => {
-  const safe = Number.isFinite(value) ? value : 0;
-  if (MONEY_ROUNDING_MODE === "round") {
-    return Math.max(0, Math.round(safe));
-  }
-  return Math.max(0, Math.floor(safe));
-};
-
-const calculateLineSubtotal = (unitPrice, qty) => {
-  const safePrice = Number(unitPrice) || 0;
-  const scaledQty = Math.round((Number(qty) || 0) * PRECISION_FACTOR);
-  const raw = (safePrice * scaledQty) / PRECISION_FACTOR;
-  return roundMoney(raw);
-};
-
 const getEffectiveUnitPrice = (item) =>
   item.editedUnitPrice != null ? item.editedUnitPrice : item.baseUnitPrice;
 
-const formatUnitLabel = (allowDecimalQty) => (allowDecimalQty ? "/ kg" : "/ món");
+// Dòng gửi backend: thành tiền, thuế và tổng đều do quote_cart tính
+const toPaymentItem = (item) => ({
+  productId: typeof item.productId === "number" ? item.productId : null,
+  unitId: item.unitId ?? null,
+  name: item.name,
+  quantity: item.qty,
+  baseUnitPrice: item.baseUnitPrice,
+  editedUnitPrice: item.editedUnitPrice,
+  lineDiscount: 0,
+});
+
+const formatUnitLabel = (allowDecimalQty, unitName) => {
+  if (unitName) return `/ ${unitName}`;
+  return allowDecimalQty ? "/ kg" : "/ món";
+};
 
-const buildCartItem = (product, { initialQty, initialEditedPrice } = {}) => {
+const buildCartItem = (product, { initialQty, initialEditedPrice, lineId } = {}) => {
   const allowDecimalSetting = product.allowDecimalQty ?? product.allow_decimal_qty;
   const allowDecimalQty =
     allowDecimalSetting == null
@@ -119,7 +120,7 @@ const buildCartItem = (product, { initialQty, initialEditedPrice } = {}) => {
         ? product.qty
         : 1;
   const normalizedQty = allowDecimalQty
-    ? Math.max(DECIMAL_STEP, Math.round(qtyValue * PRECISION_FACTOR) / PRECISION_FACTOR)
+    ? Math.max(DECIMAL_STEP, Math.round(qtyValue * QTY_PRECISION_FACTOR) / QTY_PRECISION_FACTOR)
     : Math.max(1, Math.round(qtyValue));
   const editedUnitPrice =
     typeof initialEditedPrice === "number"
@@ -129,7 +130,10 @@ const buildCartItem = (product, { initialQty, initialEditedPrice } = {}) => {
         : null;
   const displayPrice = editedUnitPrice != null ? editedUnitPrice : baseUnitPrice;
   return {
-    id: product.id,
+    id: lineId ?? product.id,
+    productId: product.id,
+    unitId: product.unitId ?? null,
+    unitName: product.unitName ?? null,
     name: product.name,
     allowDecimalQty,
     baseUnitPrice,
@@ -146,7 +150,7 @@ const normalizeQtyWithStep = (qty, allowDecimal) => {
   if (allowDecimal) {
     const min = DECIMAL_STEP;
     const safe = Math.max(min, qty);
-    return Math.round(safe * PRECISION_FACTOR) / PRECISION_FACTOR;
+    return Math.round(safe * QTY_PRECISION_FACTOR) / QTY_PRECISION_FACTOR;
   }
   return Math.max(1, Math.round(qty));
 };
@@ -162,13 +166,6 @@ const computeQtyAfterDelta = (item, deltaSteps) => {
   return normalizeQtyWithStep(raw, item.allowDecimalQty);
 };
 
-const generateInvoiceNumber = () => {
-  const now = new Date();
-  const timestamp = now.toISOString().replace(/[-:TZ.]/g, "").slice(0, 14);
-  const random = Math.floor(Math.random() * 900) + 100;
-  return `HD${timestamp}${random}`;
-};
-
 const STORE_PROFILE = {
   name: "HTX DIỄN QUẢNG",
   address: "123 Đường POS, Q.1, TP.HCM",
@@ -180,13 +177,26 @@ const DEFAULT_PAPER_WIDTH = "58mm";
 const POSScreen = ({ currentCashier, onOpenSettings, onOpenHistory, onOpenReport, onSwitchCashier }) => {
   const [searchTerm, setSearchTerm] = useState("");
   const [products, setProducts] = useState([]);
+  const [categories, setCategories] = useState([]);
+  const [activeCategoryId, setActiveCategoryId] = useState(null);
   const [cartItems, setCartItems] = useState([]);
   const [note, setNote] = useState("");
   const searchRef = useRef(null);
   const [showPaymentModal, setShowPaymentModal] = useState(false);
   const [cashGiven, setCashGiven] = useState("");
   const cashInputRef = useRef(null);
+  // Khoá chống lưu trùng khi bấm thanh toán nhiều lần hoặc gọi lại IPC
+  const checkoutKeyRef = useRef(null);
   const [isSavingPayment, setIsSavingPayment] = useState(false);
+  const [quote, setQuote] = useState(null);
+  // Tăng lên để báo giá lại dù giỏ hàng không đổi (ví dụ backend vừa từ chối số tiền)
+  const [quoteRevision, setQuoteRevision] = useState(0);
+  // Lỗi backend hiển thị cho thu ngân (đã dịch qua translateError)
+  const [scanError, setScanError] = useState("");
+  const [quoteError, setQuoteError] = useState("");
+  const [paymentError, setPaymentError] = useState("");
+  // Hàng bán vượt tồn kho của hoá đơn vừa lưu (chính sách "cảnh báo")
+  const [stockWarnings, setStockWarnings] = useState([]);
   const [pendingReceipt, setPendingReceipt] = useState(null);
   const handleReceiptPrinted = useCallback(() => {
     setPendingReceipt(null);
@@ -198,22 +208,40 @@ const POSScreen = ({ currentCashier, onOpenSettings, onOpenHistory, onOpenReport
   }, []);
 
   // Nạp sản phẩm từ SQLite thông qua Tauri
+  const loadProducts = useCallback(async () => {
+    try {
+      const rows = await invoke("list_products");
+      if (Array.isArray(rows) && rows.length) {
+        setProducts(
+          rows.map((row) => ({
+            ...row,
+            price: Number(row.price),
+          })),
+        );
+      }
+    } catch (error) {
+      console.error("Không thể tải sản phẩm từ SQLite:", error);
+    }
+  }, []);
+
+  // Tải lại định kỳ để giá theo lịch đổi giá hiện lên lưới sản phẩm
+  useEffect(() => {
+    loadProducts();
+    const timer = setInterval(loadProducts, PRODUCT_REFRESH_MS);
+    return () => clearInterval(timer);
+  }, [loadProducts]);
+
+  // Nạp danh mục để lọc lưới sản phẩm theo nhóm
   useEffect(() => {
     let mounted = true;
     (async () => {
       try {
-        const rows = await invoke("list_products");
-        if (!mounted) return;
-        if (Array.isArray(rows) && rows.length) {
-          setProducts(
-            rows.map((row) => ({
-              ...row,
-              price: Number(row.price),
-            })),
-          );
+        const rows = await invoke("list_categories");
+        if (mounted && Array.isArray(rows)) {
+          setCategories(rows);
         }
       } catch (error) {
-        console.error("Không thể tải sản phẩm từ SQLite:", error);
+        console.error("Không thể tải danh mục:", error);
       }
     })();
     return () => {
@@ -221,9 +249,30 @@ const POSScreen = ({ currentCashier, onOpenSettings, onOpenHistory, onOpenReport
     };
   }, []);
 
+  const topCategories = useMemo(
+    () => categories.filter((category) => category.parentId == null),
+    [categories],
+  );
+
+  // Danh mục cha gồm luôn sản phẩm của các danh mục con
+  const activeCategoryIds = useMemo(() => {
+    if (activeCategoryId == null) return null;
+    const ids = new Set([activeCategoryId]);
+    categories.forEach((category) => {
+      if (category.parentId === activeCategoryId) ids.add(category.id);
+    });
+    return ids;
+  }, [activeCategoryId, categories]);
+
   const visibleProducts = useMemo(
-    () => products.filter((item) => item.visible !== false && item.quick_display !== false),
-    [products],
+    () =>
+      products.filter(
+        (item) =>
+          item.visible !== false &&
+          item.quick_display !== false &&
+          (!activeCategoryIds || activeCategoryIds.has(item.categoryId)),
+      ),
+    [products, activeCategoryIds],
   );
 
   // Lọc sản phẩm theo từ khoá nhập nhanh
@@ -253,6 +302,115 @@ const POSScreen = ({ currentCashier, onOpenSettings, onOpenHistory, onOpenReport
     });
   };
 
+  // Tem cân: cộng dồn khối lượng vào dòng sẵn có; tem giá tách dòng riêng theo mã tem.
+  const addScannedProduct = (scan, code) => {
+    const product = { ...scan.product, price: Number(scan.product.price) };
+    if (scan.unit) {
+      // Mã vạch của đơn vị quy đổi (thùng, lốc...): dòng riêng theo giá của đơn vị đó
+      const lineId = `${product.id}:u${scan.unit.id}`;
+      setCartItems((prev) => {
+        const existing = prev.find((item) => item.id === lineId);
+        if (existing) {
+          const nextQty = existing.qty + 1;
+          return prev.map((item) =>
+            item.id === lineId
+              ? {
+                ...item,
+                qty: nextQty,
+                qtyInput: formatQtyValue(nextQty, item.allowDecimalQty),
+                qtyError: null,
+              }
+              : item,
+          );
+        }
+        return [
+          ...prev,
+          buildCartItem(
+            {
+              ...product,
+              name: `${product.name} (${scan.unit.name})`,
+              price: Number(scan.unit.price),
+              unitId: scan.unit.id,
+              unitName: scan.unit.name,
+              allowDecimalQty: false,
+            },
+            { initialQty: 1, lineId },
+          ),
+        ];
+      });
+      return;
+    }
+    if (scan.quantity == null && scan.linePrice == null) {
+      addProduct(product);
+      return;
+    }
+    if (scan.linePrice != null) {
+      const lineId = `${product.id}:${code}`;
+      setCartItems((prev) => {
+        const existing = prev.find((item) => item.id === lineId);
+        if (existing) {
+          const nextQty = existing.qty + 1;
+          return prev.map((item) =>
+            item.id === lineId
+              ? {
+                ...item,
+                qty: nextQty,
+                qtyInput: formatQtyValue(nextQty, item.allowDecimalQty),
+                qtyError: null,
+              }
+              : item,
+          );
+        }
+        return [
+          ...prev,
+          buildCartItem(product, {
+            initialQty: 1,
+            initialEditedPrice: Number(scan.linePrice),
+            lineId,
+          }),
+        ];
+      });
+      return;
+    }
+    const weight = Number(scan.quantity);
+    setCartItems((prev) => {
+      const existing = prev.find((item) => item.id === product.id);
+      if (existing) {
+        const nextQty = normalizeQtyWithStep(existing.qty + weight, true);
+        return prev.map((item) =>
+          item.id === product.id
+            ? {
+              ...item,
+              allowDecimalQty: true,
+              qty: nextQty,
+              qtyInput: formatQtyValue(nextQty, true),
+              qtyError: null,
+            }
+            : item,
+        );
+      }
+      return [...prev, buildCartItem({ ...product, allowDecimalQty: true }, { initialQty: weight })];
+    });
+  };
+
+  // Máy quét gửi mã kèm phím Enter: tra đúng mã hoặc tem cân trong SQLite thay vì lọc theo tên.
+  const handleSearchKeyDown = async (event) => {
+    if (event.key !== "Enter") return;
+    const code = searchTerm.trim();
+    if (!code) return;
+    try {
+      const scan = await invoke("resolve_scan", { payload: { barcode: code } });
+      setScanError("");
+      if (scan) {
+        addScannedProduct(scan, code);
+        setSearchTerm("");
+      }
+    } catch (error) {
+      console.error("Không thể tra cứu barcode:", error);
+      setScanError(translateError(error));
+    }
+  };
+
   const updateQuantity = (productId, deltaSteps) => {
     setCartItems((prev) =>
       prev
@@ -277,28 +435,82 @@ const POSScreen = ({ currentCashier, onOpenSettings, onOpenHistory, onOpenReport
     setCartItems((prev) => prev.filter((item) => item.id !== productId));
   };
 
-  // Tính toán tổng tiền giỏ hàng (không áp dụng thuế)
-  const cartSubtotal = useMemo(
-    () =>
-      cartItems.reduce((sum, item) => {
-        const unitPrice = getEffectiveUnitPrice(item);
-        return sum + calculateLineSubtotal(unitPrice, item.qty);
-      }, 0),
-    [cartItems],
-  );
-  const tax = 0;
-  const total = cartSubtotal;
   const cartHasErrors = useMemo(
     () => cartItems.some((item) => item.qtyError || item.unitPriceError),
     [cartItems],
   );
   const parsedCashGiven = Number(cashGiven) || 0;
-  const changeDue = Math.max(parsedCashGiven - total, 0);
-  const canConfirmPayment = total > 0 && parsedCashGiven >= total && !cartHasErrors;
+
+  // Giỏ hàng và tiền khách đưa được gói thành một khoá; chỉ gọi lại quote_cart khi khoá đổi
+  const quoteRequestKey = useMemo(() => {
+    if (!cartItems.length || cartHasErrors) return null;
+    return JSON.stringify({ items: cartItems.map(toPaymentItem), paidCash: parsedCashGiven });
+  }, [cartItems, cartHasErrors, parsedCashGiven]);
+
+  // Số tiền hiển thị lấy từ backend (debounce) để khớp tuyệt đối với hoá đơn được lưu
+  useEffect(() => {
+    if (!quoteRequestKey) {
+      setQuote(null);
+      setQuoteError("");
+      return undefined;
+    }
+    let cancelled = false;
+    const timer = setTimeout(async () => {
+      try {
+        const result = await invoke("quote_cart", { payload: JSON.parse(quoteRequestKey) });
+        if (!cancelled) {
+          setQuote({ ...result, requestKey: quoteRequestKey });
+          setQuoteError("");
+        }
+      } catch (error) {
+        if (!cancelled) {
+          setQuote(null);
+          setQuoteError(translateError(error));
+        }
+        console.error("Không thể tính tiền giỏ hàng:", error);
+      }
+    }, QUOTE_DEBOUNCE_MS);
+    return () => {
+      cancelled = true;
+      clearTimeout(timer);
+    };
+  }, [quoteRequestKey, quoteRevision]);
+
+  const quoteIsCurrent = quote != null && quote.requestKey === quoteRequestKey;
+  const quotedLines = quote?.lines?.length === cartItems.length ? quote.lines : null;
+
+  // Backend định giá theo danh mục hiện tại; giỏ hàng nhận lại giá gốc mới nếu giá vừa đổi
+  useEffect(() => {
+    if (!quoteIsCurrent || !quotedLines) return;
+    setCartItems((prev) => {
+      let changed = false;
+      const next = prev.map((item, index) => {
+        const line = quotedLines[index];
+        if (!line || line.baseUnitPrice === item.baseUnitPrice) return item;
+        changed = true;
+        return {
+          ...item,
+          baseUnitPrice: line.baseUnitPrice,
+          unitPriceInput:
+            item.editedUnitPrice != null ? item.unitPriceInput : String(line.baseUnitPrice),
+        };
+      });
+      return changed ? next : prev;
+    });
+  }, [quoteIsCurrent, quotedLines]);
+  const subtotal = quote?.subtotal ?? 0;
+  const tax = quote?.tax ?? 0;
+  const total = quote?.total ?? 0;
+  const changeDue = quote?.changeDue ?? 0;
+  const canConfirmPayment =
+    quoteIsCurrent && total > 0 && parsedCashGiven >= total && !cartHasErrors;
 
   // Modal thanh toán
   const openPaymentModal = useCallback(() => {
     if (!cartItems.length || cartHasErrors) return;
+    checkoutKeyRef.current = crypto.randomUUID();
+    setPaymentError("");
+    setStockWarnings([]);
     setShowPaymentModal(true);
     setTimeout(() => cashInputRef.current?.focus(), 0);
   }, [cartItems.length, cartHasErrors]);
@@ -310,66 +522,60 @@ const POSScreen = ({ currentCashier, onOpenSettings, onOpenHistory, onOpenReport
 
   const confirmPayment = useCallback(async () => {
     if (!canConfirmPayment || isSavingPayment || cartHasErrors) return;
-    if (!cartItems.length) return;
     const noteValue = note.trim();
-    const items = cartItems.map((item) => {
-      const effectiveUnitPrice = getEffectiveUnitPrice(item);
-      const lineSubtotal = calculateLineSubtotal(effectiveUnitPrice, item.qty);
-      return {
-        productId: typeof item.id === "number" ? item.id : null,
-        name: item.name,
-        quantity: item.qty,
-        baseUnitPrice: item.baseUnitPrice,
-        editedUnitPrice: item.editedUnitPrice,
-        effectiveUnitPrice,
-        lineSubtotal,
-        price: effectiveUnitPrice,
-        lineDiscount: 0,
-      };
-    });
+    // Gửi đúng giỏ hàng vừa được báo giá
+    const { items } = JSON.parse(quote.requestKey);
     if (!items.length) return;
-    const payload = {
-      invoiceNumber: generateInvoiceNumber(),
-      cashierName: currentCashier,
-      subtotal: cartSubtotal,
-      tax,
-      total,
-      discount: 0,
-      paidCash: parsedCashGiven,
-      changeDue,
-      note: noteValue ? noteValue : null,
-      items,
-    };
     setIsSavingPayment(true);
+    setPaymentError("");
     try {
+      const payload = {
+        cashierName: currentCashier,
+        subtotal: quote.subtotal,
+        tax: quote.tax,
+        total: quote.total,
+        discount: quote.discount,
+        paidCash: parsedCashGiven,
+        changeDue: quote.changeDue ?? 0,
+        note: noteValue ? noteValue : null,
+        idempotencyKey: checkoutKeyRef.current,
+        items,
+      };
       const savedPayment = await invoke("create_payment", { payload });
+      // Chính sách "cảnh báo" vẫn cho bán khi thiếu hàng, thu ngân được báo lại
+      setStockWarnings(savedPayment.stockWarnings ?? []);
       setPendingReceipt({
         ...savedPayment,
         note: savedPayment.note ?? payload.note,
         paperWidth: DEFAULT_PAPER_WIDTH,
         store: STORE_PROFILE,
       });
+      checkoutKeyRef.current = null;
       setCartItems([]);
       setNote("");
       closePaymentModal();
     } catch (error) {
       console.error("Không thể lưu hoá đơn:", error);
+      setPaymentError(translateError(error));
+      if (error?.code === "payment_figure_mismatch") {
+        // Giá đã đổi kể từ lần báo giá trước: báo giá lại và tải lại sản phẩm
+        setQuote(null);
+        setQuoteRevision((value) => value + 1);
+        loadProducts();
+      }
     } finally {
       setIsSavingPayment(false);
     }
   }, [
     canConfirmPayment,
     isSavingPayment,
-    cartItems,
+    quote,
     note,
     currentCashier,
-    cartSubtotal,
-    tax,
-    total,
     parsedCashGiven,
-    changeDue,
     closePaymentModal,
     cartHasErrors,
+    loadProducts,
   ]);
 
   useEffect(() => {
@@ -570,12 +776,39 @@ const POSScreen = ({ currentCashier, onOpenSettings, onOpenHistory, onOpenReport
               type="text"
               placeholder="Tìm kiếm hoặc quét barcode..."
               value={searchTerm}
-              onChange={(event) => setSearchTerm(event.target.value)}
+              onChange={(event) => {
+                setSearchTerm(event.target.value);
+                setScanError("");
+              }}
+              onKeyDown={handleSearchKeyDown}
             />
             <button className="ghost-btn" onClick={() => setSearchTerm("")}>
               Xoá
             </button>
           </div>
+          {scanError && <p className="input-error">{scanError}</p>}
+          {topCategories.length > 0 && (
+            <div className="pos-categories">
+              <button
+                type="button"
+                className={`ghost-btn ${activeCategoryId == null ? "active" : ""}`}
+                onClick={() => setActiveCategoryId(null)}
+              >
+                Tất cả
+              </button>
+              {topCategories.map((category) => (
+                <button
+                  key={category.id}
+                  type="button"
+                  className={`ghost-btn ${activeCategoryId === category.id ? "active" : ""}`}
+                  style={category.color ? { borderColor: category.color } : undefined}
+                  onClick={() => setActiveCategoryId(category.id)}
+                >
+                  {category.name}
+                </button>
+              ))}
+            </div>
+          )}
           <div className="pos-grid">
             {filteredProducts.map((product) => (
               <button
@@ -611,10 +844,10 @@ const POSScreen = ({ currentCashier, onOpenSettings, onOpenHistory, onOpenReport
           </div>
           <div className="cart-content">
             <div className="cart-lines">
-              {cartItems.map((item) => {
-                const unitLabel = formatUnitLabel(item.allowDecimalQty);
+              {cartItems.map((item, index) => {
+                const unitLabel = formatUnitLabel(item.allowDecimalQty, item.unitName);
                 const effectiveUnitPrice = getEffectiveUnitPrice(item);
-                const lineSubtotal = calculateLineSubtotal(effectiveUnitPrice, item.qty);
+                const quotedLine = quotedLines?.[index];
                 const formattedQty = formatQtyDisplay(item.qty, item.allowDecimalQty);
                 return (
                   <div
@@ -680,7 +913,7 @@ const POSScreen = ({ currentCashier, onOpenSettings, onOpenHistory, onOpenReport
                         <div className="cart-field qty-field">
                           <label>Thành tiền</label>
                           <div className="cart-line-total">
-                            <strong>{formatCurrency(lineSubtotal)}</strong>
+                            <strong>{quotedLine ? formatCurrency(quotedLine.lineSubtotal) : "…"}</strong>
                           </div>
                         </div>
                       </div>
@@ -693,6 +926,23 @@ const POSScreen = ({ currentCashier, onOpenSettings, onOpenHistory, onOpenReport
               {cartHasErrors && cartItems.length > 0 && (
                 <div className="cart-warning">Vui lòng sửa các ô có viền đỏ trước khi thanh toán.</div>
               )}
+              {quoteError && !cartHasErrors && <div className="cart-warning">{quoteError}</div>}
+              {stockWarnings.length > 0 && (
+                <div className="cart-warning" role="status">
+                  <p className="cart-warning-title">Hoá đơn vừa lưu đã bán vượt tồn kho:</p>
+                  <ul>
+                    {stockWarnings.map((warning) => (
+                      <li key={warning.productId}>
+                        {warning.name}: bán {formatQtyValue(warning.requested, true)}, tồn{" "}
+                        {formatQtyValue(warning.onHand, true)}
+                      </li>
+                    ))}
+                  </ul>
+                  <button type="button" className="link-btn" onClick={() => setStockWarnings([])}>
+                    Đã hiểu
+                  </button>
+                </div>
+              )}
             </div>
             <div className="cart-note">
               <label htmlFor="note">Ghi chú đơn</label>
@@ -707,10 +957,10 @@ const POSScreen = ({ currentCashier, onOpenSettings, onOpenHistory, onOpenReport
           <div className="totals-panel">
             <div className="summary-row">
               <span>Tạm tính</span>
-              <strong>{formatCurrency(cartSubtotal)}</strong>
+              <strong>{formatCurrency(subtotal)}</strong>
             </div>
             <div className="summary-row">
-              <span>Thuế (0%)</span>
+              <span>Thuế</span>
               <strong>{formatCurrency(tax)}</strong>
             </div>
             <div className="summary-row total">
@@ -783,6 +1033,7 @@ const POSScreen = ({ currentCashier, onOpenSettings, onOpenHistory, onOpenReport
                   {formatCurrency(changeDue)}
                 </strong>
               </div>
+              {paymentError && <p className="error-text">{paymentError}</p>}
               <div className="modal-actions">
                 <button
                   className="primary-btn"
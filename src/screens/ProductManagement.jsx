@@ -1,5 +1,7 @@
 import { invoke } from "@tauri-apps/api/core";
+import { open, save } from "@tauri-apps/plugin-dialog";
 import { useEffect, useMemo, useState } from "react";
+import { translateError } from "../utils/errorMessages";
 
 const mapDatabaseProduct = (record) => ({
   ...record,
@@ -10,12 +12,16 @@ const mapDatabaseProduct = (record) => ({
 const buildFormState = (product) => ({
   ...product,
   price: product.price.toString(),
+  costPrice: product.costPrice != null ? product.costPrice.toString() : "",
+  unitName: product.unitName || "",
   barcode: product.barcode || "",
   displayOrder: product.displayOrder.toString(),
 });
 
-const buildProductPayload = (product, { includeId = false } = {}) => {
+const buildProductPayload = (product, { includeId = false, changedBy = null } = {}) => {
   const barcodeValue = typeof product.barcode === "string" ? product.barcode.trim() : "";
+  const costValue =
+    product.costPrice === "" || product.costPrice == null ? null : Number(product.costPrice);
   const payload = {
     name: product.name.trim(),
     price: Number(product.price),
@@ -23,9 +29,16 @@ const buildProductPayload = (product, { includeId = false } = {}) => {
     quickDisplay: Boolean(product.quickDisplay),
     displayOrder: Number(product.displayOrder),
     visible: Boolean(product.visible),
+    internalBarcode: Boolean(product.internalBarcode),
+    categoryId: product.categoryId ? Number(product.categoryId) : null,
+    // Để trống giá vốn thì backend giữ nguyên giá vốn bình quân hiện có
+    costPrice: costValue,
+    unitName: product.unitName?.trim() || null,
   };
   if (includeId) {
     payload.id = Number(product.id);
+    // Người sửa được ghi vào lịch sử giá khi giá bán thay đổi
+    payload.changedBy = changedBy;
   }
   return payload;
 };
@@ -82,21 +95,33 @@ const createEmptyForm = (count) => ({
   id: null,
   name: "",
   price: "",
+  costPrice: "",
+  unitName: "",
   barcode: "",
   quickDisplay: false,
   displayOrder: String(count + 1),
   visible: true,
+  categoryId: null,
 });
 
 const formatCurrency = (value) => `${value.toLocaleString("vi-VN")}đ`;
 
-const ProductManagement = ({ onBack }) => {
+const EMPTY_UNIT_DRAFT = { name: "", factor: "", price: "", barcode: "" };
+
+const ProductManagement = ({ currentCashier, onBack }) => {
   const [products, setProducts] = useState(fallbackProducts);
   const [searchTerm, setSearchTerm] = useState("");
   const [formData, setFormData] = useState(createEmptyForm(fallbackProducts.length));
   const [formErrors, setFormErrors] = useState({});
   const [isEditing, setIsEditing] = useState(false);
   const [isSubmitting, setIsSubmitting] = useState(false);
+  const [importPreview, setImportPreview] = useState(null);
+  const [archivedProducts, setArchivedProducts] = useState([]);
+  const [showArchived, setShowArchived] = useState(false);
+  const [categories, setCategories] = useState([]);
+  const [units, setUnits] = useState([]);
+  const [unitDraft, setUnitDraft] = useState(EMPTY_UNIT_DRAFT);
+  const [unitErrors, setUnitErrors] = useState({});
 
   useEffect(() => {
     let mounted = true;
@@ -117,6 +142,28 @@ const ProductManagement = ({ onBack }) => {
     };
   }, []);
 
+  useEffect(() => {
+    let mounted = true;
+    (async () => {
+      try {
+        const rows = await invoke("list_categories");
+        if (mounted && Array.isArray(rows)) {
+          setCategories(rows);
+        }
+      } catch (error) {
+        console.error("Không thể tải danh mục:", error);
+      }
+    })();
+    return () => {
+      mounted = false;
+    };
+  }, []);
+
+  const categoryNames = useMemo(
+    () => new Map(categories.map((category) => [category.id, category.name])),
+    [categories],
+  );
+
   const stats = useMemo(
     () => ({
       total: products.length,
@@ -137,16 +184,177 @@ const ProductManagement = ({ onBack }) => {
     return [...result].sort((a, b) => a.displayOrder - b.displayOrder);
   }, [products, searchTerm]);
 
+  const reloadProducts = async () => {
+    const rows = await invoke("list_products");
+    if (Array.isArray(rows)) {
+      setProducts(rows.map((row) => mapDatabaseProduct(row)));
+    }
+  };
+
+  const reloadArchived = async () => {
+    const rows = await invoke("list_archived_products");
+    if (Array.isArray(rows)) {
+      setArchivedProducts(rows.map((row) => mapDatabaseProduct(row)));
+    }
+  };
+
+  const toggleArchivedView = async () => {
+    if (!showArchived) {
+      try {
+        await reloadArchived();
+      } catch (error) {
+        console.error("Không thể tải sản phẩm đã lưu trữ:", error);
+        return;
+      }
+    }
+    setShowArchived((prev) => !prev);
+  };
+
+  // Lưu trữ: ẩn khỏi POS và danh sách nhưng giữ lại cho hoá đơn cũ.
+  const archiveProduct = async (product) => {
+    try {
+      await invoke("archive_product", {
+        payload: { id: product.id, actor: currentCashier ?? "" },
+      });
+      setProducts((prev) => prev.filter((item) => item.id !== product.id));
+      if (formData.id === product.id) {
+        resetForm(products.length - 1);
+      }
+      await reloadArchived();
+    } catch (error) {
+      console.error("Không thể lưu trữ sản phẩm:", error);
+    }
+  };
+
+  const restoreProduct = async (product) => {
+    try {
+      await invoke("restore_product", {
+        payload: { id: product.id, actor: currentCashier ?? "" },
+      });
+      await Promise.all([reloadProducts(), reloadArchived()]);
+    } catch (error) {
+      console.error("Không thể khôi phục sản phẩm:", error);
+    }
+  };
+
+  // Chỉ xoá hẳn được sản phẩm chưa từng bán; backend từ chối nếu đã có trong hoá đơn.
+  const deleteProduct = async (product) => {
+    try {
+      await invoke("delete_product", {
+        payload: { id: product.id, actor: currentCashier ?? "" },
+      });
+      await reloadArchived();
+    } catch (error) {
+      console.error("Không thể xoá sản phẩm:", error);
+    }
+  };
+
+  // Chạy thử trước để xem lỗi từng dòng, chỉ ghi thật khi người dùng xác nhận.
+  const previewImport = async () => {
+    try {
+      const path = await open({
+        multiple: false,
+        filters: [{ name: "CSV / Excel", extensions: ["csv", "xlsx", "xls"] }],
+      });
+      if (!path) return;
+      const report = await invoke("import_products", {
+        payload: { path, mode: "upsert", dryRun: true },
+      });
+      setImportPreview({ path, report });
+    } catch (error) {
+      console.error("Không thể đọc file sản phẩm:", error);
+    }
+  };
+
+  const confirmImport = async () => {
+    if (!importPreview || importPreview.report.errors.length) return;
+    try {
+      await invoke("import_products", {
+        payload: { path: importPreview.path, mode: importPreview.report.mode, dryRun: false },
+      });
+      setImportPreview(null);
+      await reloadProducts();
+    } catch (error) {
+      console.error("Không thể nhập sản phẩm:", error);
+    }
+  };
+
+  const exportCatalog = async () => {
+    try {
+      const path = await save({
+        defaultPath: "san-pham.xlsx",
+        filters: [
+          { name: "Excel", extensions: ["xlsx"] },
+          { name: "CSV", extensions: ["csv"] },
+        ],
+      });
+      if (!path) return;
+      const format = path.toLowerCase().endsWith(".csv") ? "csv" : "xlsx";
+      await invoke("export_products", { payload: { path, format } });
+    } catch (error) {
+      console.error("Không thể xuất danh sách sản phẩm:", error);
+    }
+  };
+
   const resetForm = (count = products.length) => {
     setIsEditing(false);
     setFormData(createEmptyForm(count));
     setFormErrors({});
+    setUnits([]);
+    setUnitDraft(EMPTY_UNIT_DRAFT);
+    setUnitErrors({});
+  };
+
+  const loadUnits = async (productId) => {
+    try {
+      const rows = await invoke("list_product_units", { payload: { productId } });
+      setUnits(Array.isArray(rows) ? rows : []);
+    } catch (error) {
+      console.error("Không thể tải đơn vị bán:", error);
+      setUnits([]);
+    }
   };
 
   const handleEditProduct = (product) => {
     setIsEditing(true);
     setFormData(buildFormState(product));
     setFormErrors({});
+    setUnitDraft(EMPTY_UNIT_DRAFT);
+    setUnitErrors({});
+    loadUnits(product.id);
+  };
+
+  // Đơn vị quy đổi (thùng, lốc...) bán theo giá và mã vạch riêng; tồn kho vẫn tính theo đơn vị cơ bản.
+  const handleAddUnit = async () => {
+    const barcodeValue = unitDraft.barcode.trim();
+    try {
+      await invoke("save_product_unit", {
+        payload: {
+          productId: Number(formData.id),
+          name: unitDraft.name,
+          factor: Number(unitDraft.factor),
+          price: Number(unitDraft.price),
+          barcode: barcodeValue || null,
+        },
+      });
+      setUnitDraft(EMPTY_UNIT_DRAFT);
+      setUnitErrors({});
+      await loadUnits(formData.id);
+    } catch (error) {
+      console.error("Không thể lưu đơn vị bán:", error);
+      if (error?.field) {
+        setUnitErrors({ [error.field]: translateError(error) });
+      }
+    }
+  };
+
+  const handleDeleteUnit = async (unit) => {
+    try {
+      await invoke("delete_product_unit", { payload: { id: unit.id } });
+      setUnits((prev) => prev.filter((item) => item.id !== unit.id));
+    } catch (error) {
+      console.error("Không thể xoá đơn vị bán:", error);
+    }
   };
 
   const handleFormChange = (field, value) => {
@@ -165,6 +373,10 @@ const ProductManagement = ({ onBack }) => {
     if (Number.isNaN(priceValue) || priceValue <= 0) {
       errors.price = "Giá bán phải lớn hơn 0.";
     }
+    const costValue = Number(formData.costPrice);
+    if (formData.costPrice !== "" && (Number.isNaN(costValue) || costValue < 0)) {
+      errors.costPrice = "Giá vốn không được âm.";
+    }
     const orderValue = Number(formData.displayOrder);
     if (Number.isNaN(orderValue) || orderValue < 1) {
       errors.displayOrder = "Thứ tự hiển thị phải từ 1 trở lên.";
@@ -184,7 +396,10 @@ const ProductManagement = ({ onBack }) => {
     setIsSubmitting(true);
     try {
       if (isEditing) {
-        const payload = buildProductPayload(formData, { includeId: true });
+        const payload = buildProductPayload(formData, {
+          includeId: true,
+          changedBy: currentCashier ?? null,
+        });
         const updated = await invoke("update_product", { payload });
         const normalized = mapDatabaseProduct(updated);
         setProducts((prev) =>
@@ -200,6 +415,9 @@ const ProductManagement = ({ onBack }) => {
       }
     } catch (error) {
       console.error("Không thể lưu sản phẩm:", error);
+      if (["name", "price", "barcode", "categoryId", "costPrice"].includes(error?.field)) {
+        setFormErrors({ [error.field]: translateError(error) });
+      }
     } finally {
       setIsSubmitting(false);
     }
@@ -208,7 +426,10 @@ const ProductManagement = ({ onBack }) => {
   // Persist a product to SQLite and keep local/UI state in sync.
   const syncProductToDatabase = async (nextProduct, fallbackProduct) => {
     try {
-      const payload = buildProductPayload(nextProduct, { includeId: true });
+      const payload = buildProductPayload(nextProduct, {
+        includeId: true,
+        changedBy: currentCashier ?? null,
+      });
       const saved = await invoke("update_product", { payload });
       const normalized = mapDatabaseProduct(saved);
       setProducts((prev) =>
@@ -268,12 +489,72 @@ const ProductManagement = ({ onBack }) => {
           <button className="ghost-btn" onClick={onBack}>
             ← Quay lại POS
           </button>
+          <button className="ghost-btn" onClick={previewImport}>
+            Nhập file
+          </button>
+          <button className="ghost-btn" onClick={exportCatalog}>
+            Xuất file
+          </button>
+          <button className="ghost-btn" onClick={toggleArchivedView}>
+            {showArchived ? "Ẩn lưu trữ" : "Đã lưu trữ"}
+          </button>
           <button className="primary-btn" onClick={resetForm}>
             + Thêm sản phẩm
           </button>
         </div>
       </header>
 
+      {importPreview && (
+        <section className="import-preview">
+          <p>
+            {importPreview.report.totalRows} dòng: {importPreview.report.created} thêm mới,{" "}
+            {importPreview.report.updated} cập nhật, {importPreview.report.errors.length} lỗi.
+          </p>
+          {importPreview.report.errors.length > 0 && (
+            <ul>
+              {importPreview.report.errors.map((error) => (
+                <li key={`${error.row}-${error.code}`}>
+                  Dòng {error.row}
+                  {error.field ? ` (${error.field})` : ""}: {translateError(error)}
+                </li>
+              ))}
+            </ul>
+          )}
+          <div className="header-actions">
+            <button className="ghost-btn" onClick={() => setImportPreview(null)}>
+              Huỷ
+            </button>
+            <button
+              className="primary-btn"
+              onClick={confirmImport}
+              disabled={importPreview.report.errors.length > 0}
+            >
+              Xác nhận nhập
+            </button>
+          </div>
+        </section>
+      )}
+
+      {showArchived && (
+        <section className="import-preview">
+          <p>{archivedProducts.length} sản phẩm đã lưu trữ.</p>
+          <ul>
+            {archivedProducts.map((product) => (
+              <li key={product.id}>
+                {product.name} · {formatCurrency(product.price)}
+                {product.barcode ? ` · ${product.barcode}` : ""}{" "}
+                <button className="link-btn" onClick={() => restoreProduct(product)}>
+                  Khôi phục
+                </button>
+                <button className="link-btn" onClick={() => deleteProduct(product)}>
+                  Xoá hẳn
+                </button>
+              </li>
+            ))}
+          </ul>
+        </section>
+      )}
+
       <div className="admin-toolbar">
         <div className="search-box">
           <input
@@ -314,6 +595,7 @@ const ProductManagement = ({ onBack }) => {
                   <th>Tên sản phẩm</th>
                   <th>Giá bán</th>
                   <th>Barcode</th>
+                  <th>Danh mục</th>
                   <th>Trạng thái</th>
                   <th>Hiển thị nhanh</th>
                   <th>Hành động</th>
@@ -333,6 +615,7 @@ const ProductManagement = ({ onBack }) => {
                     </td>
                     <td>{formatCurrency(product.price)}</td>
                     <td>{product.barcode || "—"}</td>
+                    <td>{categoryNames.get(product.categoryId) || "—"}</td>
                     <td>
                       <span className={`status-pill ${product.visible ? "status-on" : "status-off"}`}>
                         {product.visible ? "Hiển" : "Ẩn"}
@@ -353,13 +636,16 @@ const ProductManagement = ({ onBack }) => {
                         >
                           Ghim lên POS
                         </button>
+                        <button className="ghost-btn" onClick={() => archiveProduct(product)}>
+                          Lưu trữ
+                        </button>
                       </div>
                     </td>
                   </tr>
                 ))}
                 {!filteredProducts.length && (
                   <tr>
-                    <td colSpan={6} className="empty-state">
+                    <td colSpan={7} className="empty-state">
                       Không có sản phẩm nào phù hợp với từ khoá tìm kiếm.
                     </td>
                   </tr>
@@ -397,6 +683,28 @@ const ProductManagement = ({ onBack }) => {
               />
               {formErrors.price && <p className="error-text">{formErrors.price}</p>}
             </div>
+            <div className="form-field">
+              <label htmlFor="costPrice">Giá vốn (VND, tuỳ chọn)</label>
+              <input
+                id="costPrice"
+                type="number"
+                min="0"
+                step="1"
+                value={formData.costPrice}
+                onChange={(event) => handleFormChange("costPrice", event.target.value)}
+                placeholder="Tự cập nhật khi nhập hàng"
+              />
+              {formErrors.costPrice && <p className="error-text">{formErrors.costPrice}</p>}
+            </div>
+            <div className="form-field">
+              <label htmlFor="unitName">Đơn vị cơ bản (tuỳ chọn)</label>
+              <input
+                id="unitName"
+                value={formData.unitName}
+                onChange={(event) => handleFormChange("unitName", event.target.value)}
+                placeholder="Ví dụ: lon, chai, kg"
+              />
+            </div>
             <div className="form-field">
               <label htmlFor="barcode">Barcode (tuỳ chọn)</label>
               <input
@@ -406,6 +714,39 @@ const ProductManagement = ({ onBack }) => {
                 onChange={(event) => handleFormChange("barcode", event.target.value)}
                 placeholder="Nhập hoặc quét mã"
               />
+              {formErrors.barcode && <p className="error-text">{formErrors.barcode}</p>}
+            </div>
+            <div className="checkbox-field">
+              <label>
+                <input
+                  type="checkbox"
+                  checked={Boolean(formData.internalBarcode)}
+                  onChange={(event) => handleFormChange("internalBarcode", event.target.checked)}
+                />
+                Mã nội bộ (không kiểm tra số kiểm tra)
+              </label>
+            </div>
+            <div className="form-field">
+              <label htmlFor="categoryId">Danh mục</label>
+              <select
+                id="categoryId"
+                value={formData.categoryId ?? ""}
+                onChange={(event) =>
+                  handleFormChange(
+                    "categoryId",
+                    event.target.value ? Number(event.target.value) : null,
+                  )
+                }
+              >
+                <option value="">Chưa phân loại</option>
+                {categories.map((category) => (
+                  <option key={category.id} value={category.id}>
+                    {category.parentId != null ? "— " : ""}
+                    {category.name}
+                  </option>
+                ))}
+              </select>
+              {formErrors.categoryId && <p className="error-text">{formErrors.categoryId}</p>}
             </div>
             <div className="form-field">
               <label htmlFor="displayOrder">Thứ tự hiển thị</label>
@@ -441,6 +782,73 @@ const ProductManagement = ({ onBack }) => {
               )}
             </div>
           </form>
+
+          {isEditing && (
+            <section className="product-units">
+              <h3>Đơn vị quy đổi</h3>
+              {units.length > 0 && (
+                <ul>
+                  {units.map((unit) => (
+                    <li key={unit.id}>
+                      {unit.name} = {unit.factor} {formData.unitName || "đơn vị cơ bản"} ·{" "}
+                      {formatCurrency(unit.price)}
+                      {unit.barcode && ` · ${unit.barcode}`}
+                      <button
+                        type="button"
+                        className="ghost-btn"
+                        onClick={() => handleDeleteUnit(unit)}
+                      >
+                        Xoá
+                      </button>
+                    </li>
+                  ))}
+                </ul>
+              )}
+              <div className="form-field">
+                <input
+                  value={unitDraft.name}
+                  onChange={(event) =>
+                    setUnitDraft((prev) => ({ ...prev, name: event.target.value }))
+                  }
+                  placeholder="Tên đơn vị, ví dụ: thùng"
+                />
+                {unitErrors.name && <p className="error-text">{unitErrors.name}</p>}
+                <input
+                  type="number"
+                  min="0"
+                  step="any"
+                  value={unitDraft.factor}
+                  onChange={(event) =>
+                    setUnitDraft((prev) => ({ ...prev, factor: event.target.value }))
+                  }
+                  placeholder="Số đơn vị cơ bản, ví dụ: 24"
+                />
+                {unitErrors.factor && <p className="error-text">{unitErrors.factor}</p>}
+                <input
+                  type="number"
+                  min="0"
+                  step="1"
+                  value={unitDraft.price}
+                  onChange={(event) =>
+                    setUnitDraft((prev) => ({ ...prev, price: event.target.value }))
+                  }
+                  placeholder="Giá bán theo đơn vị này"
+                />
+                {unitErrors.price && <p className="error-text">{unitErrors.price}</p>}
+                <input
+                  value={unitDraft.barcode}
+                  onChange={(event) =>
+                    setUnitDraft((prev) => ({ ...prev, barcode: event.target.value }))
+                  }
+                  placeholder="Barcode riêng (tuỳ chọn)"
+                />
+                {unitErrors.barcode && <p className="error-text">{unitErrors.barcode}</p>}
+              </div>
+              <button type="button" className="ghost-btn" onClick={handleAddUnit}>
+                Thêm đơn vị
+              </button>
+            </section>
+          )}
         </aside>
       </div>
     </div>
@@ -1,5 +1,8 @@
 import { invoke } from "@tauri-apps/api/core";
-import { useEffect, useMemo, useState } from "react";
+import { save } from "@tauri-apps/plugin-dialog";
+import { useEffect, useState } from "react";
+import usePaymentPages from "../hooks/usePaymentPages";
+import { translateError } from "../utils/errorMessages";
 
 const formatCurrency = (value) => `${Number(value || 0).toLocaleString("vi-VN")}đ`;
 const formatTime = (value) => {
@@ -8,25 +11,69 @@ const formatTime = (value) => {
   return time.slice(0, 5);
 };
 
-const getToday = () => new Date().toISOString().slice(0, 10);
+const getToday = () => new Date().toLocaleDateString("sv-SE");
+
+const EMPTY_SUMMARY = {
+  count: 0,
+  revenue: 0,
+  refunds: 0,
+  discounts: 0,
+  paid: 0,
+  change: 0,
+  average: 0,
+};
 
 const RevenueReport = ({ onBack }) => {
+  const [today, setToday] = useState(getToday);
   const [selectedDate, setSelectedDate] = useState(getToday);
-  const [receipts, setReceipts] = useState([]);
+  // Bảng chi tiết tải theo trang; các con số tổng hợp lấy từ revenue_report.
+  const {
+    items: receipts,
+    totalCount: receiptCount,
+    hasMore,
+    isLoading: isLoadingReceipts,
+    error: receiptsError,
+    loadMore,
+  } = usePaymentPages(selectedDate, { includeVoided: false });
+  const [report, setReport] = useState(null);
+  const [margins, setMargins] = useState(null);
   const [isLoading, setIsLoading] = useState(true);
+  const [reportError, setReportError] = useState("");
+
+  useEffect(() => {
+    // Ngày làm việc do backend tính theo múi giờ và giờ chốt ca của cửa hàng.
+    invoke("current_business_date")
+      .then((businessDate) => {
+        if (!businessDate) return;
+        setToday(businessDate);
+        setSelectedDate(businessDate);
+      })
+      .catch((error) => console.error("Không thể lấy ngày làm việc:", error));
+  }, []);
 
   useEffect(() => {
     let mounted = true;
+    setIsLoading(true);
+    setReportError("");
     (async () => {
       try {
-        const rows = await invoke("list_payments");
+        const [summary, marginReport] = await Promise.all([
+          invoke("revenue_report", {
+            payload: { dateFrom: selectedDate, dateTo: selectedDate, groupBy: "day" },
+          }),
+          invoke("margin_report", {
+            payload: { dateFrom: selectedDate, dateTo: selectedDate },
+          }),
+        ]);
         if (!mounted) return;
-        if (Array.isArray(rows)) {
-          setReceipts(rows);
-        }
+        setReport(summary ?? null);
+        setMargins(marginReport ?? null);
       } catch (error) {
         console.error("Không thể tải dữ liệu bán hàng:", error);
-        setReceipts([]);
+        if (!mounted) return;
+        setReportError(translateError(error));
+        setReport(null);
+        setMargins(null);
       } finally {
         if (mounted) {
           setIsLoading(false);
@@ -36,120 +83,35 @@ const RevenueReport = ({ onBack }) => {
     return () => {
       mounted = false;
     };
-  }, []);
+  }, [selectedDate]);
 
-  const dailyReceipts = useMemo(() => {
-    if (!selectedDate) return [];
-    return receipts
-      .filter((receipt) => receipt.createdAt?.slice(0, 10) === selectedDate)
-      .sort((a, b) => {
-        const aTime = a.createdAt ?? "";
-        const bTime = b.createdAt ?? "";
-        return aTime.localeCompare(bTime);
-      });
-  }, [receipts, selectedDate]);
+  const aggregate = report?.summary ?? EMPTY_SUMMARY;
+  const cashierSummary = report?.cashiers ?? [];
+  const categorySummary = report?.categories ?? [];
+  const productMargins = margins?.products ?? [];
+  const hasSales = aggregate.count > 0;
 
-  const aggregate = useMemo(() => {
-    if (!dailyReceipts.length) {
-      return {
-        count: 0,
-        revenue: 0,
-        discounts: 0,
-        paid: 0,
-        change: 0,
-        avg: 0,
-        max: null,
-        min: null,
-      };
+  const exportReport = async (format) => {
+    if (!hasSales) return;
+    const extension = format === "xlsx" ? "xlsx" : "csv";
+    setReportError("");
+    try {
+      const path = await save({
+        defaultPath: `bao-cao-${selectedDate}.${extension}`,
+        filters: [{ name: extension.toUpperCase(), extensions: [extension] }],
+      });
+      if (!path) return;
+      await invoke("export_sales", {
+        payload: { dateFrom: selectedDate, dateTo: selectedDate, format, path },
+      });
+    } catch (error) {
+      console.error("Không thể xuất báo cáo:", error);
+      setReportError(translateError(error));
     }
-    const initial = {
-      count: 0,
-      revenue: 0,
-      discounts: 0,
-      paid: 0,
-      change: 0,
-      max: dailyReceipts[0],
-      min: dailyReceipts[0],
-    };
-    const result = dailyReceipts.reduce((acc, receipt) => {
-      const total = Number(receipt.total) || 0;
-      const discount = Number(receipt.discount) || 0;
-      const paidCash = Number(receipt.paidCash) || 0;
-      const changeDue = Number(receipt.changeDue) || 0;
-      acc.count += 1;
-      acc.revenue += total;
-      acc.discounts += discount;
-      acc.paid += paidCash;
-      acc.change += changeDue;
-      if (!acc.max || total > (Number(acc.max.total) || 0)) {
-        acc.max = receipt;
-      }
-      if (!acc.min || total < (Number(acc.min.total) || 0)) {
-        acc.min = receipt;
-      }
-      return acc;
-    }, initial);
-    result.avg = result.count ? Math.round(result.revenue / result.count) : 0;
-    return result;
-  }, [dailyReceipts]);
-
-  const cashierSummary = useMemo(() => {
-    const groups = new Map();
-    dailyReceipts.forEach((receipt) => {
-      const name = receipt.cashierName || "Không rõ";
-      const total = Number(receipt.total) || 0;
-      const discount = Number(receipt.discount) || 0;
-      const current = groups.get(name) ?? { count: 0, revenue: 0, discounts: 0 };
-      current.count += 1;
-      current.revenue += total;
-      current.discounts += discount;
-      groups.set(name, current);
-    });
-    return Array.from(groups.entries()).map(([name, stats]) => ({
-      cashierName: name,
-      ...stats,
-    }));
-  }, [dailyReceipts]);
-
-  const exportReport = () => {
-    if (!dailyReceipts.length) return;
-    const rows = [
-      ["STT", "Số hoá đơn", "Thời gian", "Thu ngân", "Tổng tiền", "Giảm giá", "Tiền khách đưa", "Tiền thừa"],
-      ...dailyReceipts.map((receipt, index) => [
-        index + 1,
-        receipt.invoiceNumber,
-        formatTime(receipt.createdAt),
-        receipt.cashierName,
-        Number(receipt.total) || 0,
-        Number(receipt.discount) || 0,
-        Number(receipt.paidCash) || 0,
-        Number(receipt.changeDue) || 0,
-      ]),
-    ];
-    const csvContent = rows
-      .map((line) =>
-        line
-          .map((value) => {
-            const stringValue = `${value ?? ""}`.replace(/"/g, '""');
-            return `"${stringValue}"`;
-          })
-          .join(","),
-      )
-      .join("\n");
-    const blob = new Blob([`\uFEFF${csvContent}`], { type: "text/csv;charset=utf-8;" });
-    const url = URL.createObjectURL(blob);
-    const link = document.createElement("a");
-    link.href = url;
-    link.download = `bao-cao-${selectedDate}.csv`;
-    document.body.appendChild(link);
-    link.click();
-    document.body.removeChild(link);
-    URL.revokeObjectURL(url);
   };
 
   const handleDateChange = (event) => {
     const value = event.target.value;
-    const today = getToday();
     if (value > today) return;
     setSelectedDate(value);
   };
@@ -166,8 +128,11 @@ const RevenueReport = ({ onBack }) => {
           <button className="ghost-btn" onClick={onBack}>
             ← Quay lại POS
           </button>
-          <button className="primary-btn" onClick={exportReport} disabled={!dailyReceipts.length}>
-            Xuất file
+          <button className="ghost-btn" onClick={() => exportReport("csv")} disabled={!hasSales}>
+            Xuất CSV
+          </button>
+          <button className="primary-btn" onClick={() => exportReport("xlsx")} disabled={!hasSales}>
+            Xuất Excel
           </button>
         </div>
       </header>
@@ -175,15 +140,19 @@ const RevenueReport = ({ onBack }) => {
       <div className="report-toolbar">
         <label>
           Chọn ngày
-          <input type="date" value={selectedDate} max={getToday()} onChange={handleDateChange} />
+          <input type="date" value={selectedDate} max={today} onChange={handleDateChange} />
         </label>
       </div>
 
-      {!dailyReceipts.length && !isLoading ? (
+      {(reportError || receiptsError) && (
+        <p className="error-text">{reportError || receiptsError}</p>
+      )}
+
+      {!hasSales && !isLoading ? (
         <div className="empty-state">Không có giao dịch trong ngày này.</div>
       ) : null}
 
-      {dailyReceipts.length > 0 && (
+      {hasSales && (
         <>
           <section className="report-summary">
             <div className="summary-card primary">
@@ -198,6 +167,10 @@ const RevenueReport = ({ onBack }) => {
               <span>Tổng giảm giá</span>
               <strong>{formatCurrency(aggregate.discounts)}</strong>
             </div>
+            <div className="summary-card">
+              <span>Hoàn trả</span>
+              <strong>{formatCurrency(-aggregate.refunds)}</strong>
+            </div>
             <div className="summary-card">
               <span>Tiền khách trả</span>
               <strong>{formatCurrency(aggregate.paid)}</strong>
@@ -208,7 +181,7 @@ const RevenueReport = ({ onBack }) => {
             </div>
             <div className="summary-card">
               <span>Hoá đơn trung bình</span>
-              <strong>{formatCurrency(aggregate.avg)}</strong>
+              <strong>{formatCurrency(aggregate.average)}</strong>
             </div>
           </section>
 
@@ -229,9 +202,10 @@ const RevenueReport = ({ onBack }) => {
                   </tr>
                 </thead>
                 <tbody>
-                  {dailyReceipts.map((receipt, index) => (
+                  {receipts.map((receipt, index) => (
                     <tr key={receipt.id}>
-                      <td>{index + 1}</td>
+                      {/* Hoá đơn mới nhất đứng đầu, STT vẫn đánh theo thứ tự bán */}
+                      <td>{receiptCount - index}</td>
                       <td>{receipt.invoiceNumber}</td>
                       <td>{formatTime(receipt.createdAt)}</td>
                       <td>{receipt.cashierName}</td>
@@ -244,6 +218,11 @@ const RevenueReport = ({ onBack }) => {
                 </tbody>
               </table>
             </div>
+            {hasMore && (
+              <button className="ghost-btn" onClick={loadMore} disabled={isLoadingReceipts}>
+                {isLoadingReceipts ? "Đang tải..." : `Tải thêm (${receipts.length}/${receiptCount})`}
+              </button>
+            )}
           </section>
 
           {cashierSummary.length > 0 && (
@@ -261,8 +240,8 @@ const RevenueReport = ({ onBack }) => {
                   </thead>
                   <tbody>
                     {cashierSummary.map((entry) => (
-                      <tr key={entry.cashierName}>
-                        <td>{entry.cashierName}</td>
+                      <tr key={entry.key}>
+                        <td>{entry.key || "Không rõ"}</td>
                         <td>{entry.count}</td>
                         <td>{formatCurrency(entry.revenue)}</td>
                         <td>{formatCurrency(entry.discounts)}</td>
@@ -273,6 +252,72 @@ const RevenueReport = ({ onBack }) => {
               </div>
             </section>
           )}
+
+          {categorySummary.length > 0 && (
+            <section className="report-cashier">
+              <h2>Doanh thu theo danh mục</h2>
+              <div className="table-wrapper">
+                <table>
+                  <thead>
+                    <tr>
+                      <th>Danh mục</th>
+                      <th>Số lượng</th>
+                      <th>Doanh thu</th>
+                      <th>Hoàn trả</th>
+                    </tr>
+                  </thead>
+                  <tbody>
+                    {categorySummary.map((entry) => (
+                      <tr key={entry.categoryId ?? "none"}>
+                        <td>{entry.name || "Chưa phân loại"}</td>
+                        <td>{Number(entry.quantity).toLocaleString("vi-VN")}</td>
+                        <td>{formatCurrency(entry.revenue)}</td>
+                        <td>{formatCurrency(entry.refunds)}</td>
+                      </tr>
+                    ))}
+                  </tbody>
+                </table>
+              </div>
+            </section>
+          )}
+
+          {productMargins.length > 0 && (
+            <section className="report-cashier">
+              <h2>Lãi gộp theo sản phẩm</h2>
+              <p>
+                Tổng lãi gộp: {formatCurrency(margins.summary.grossProfit)}
+                {margins.summary.marginPercent != null && ` (${margins.summary.marginPercent}%)`}
+              </p>
+              <div className="table-wrapper">
+                <table>
+                  <thead>
+                    <tr>
+                      <th>Sản phẩm</th>
+                      <th>Doanh thu</th>
+                      <th>Giá vốn</th>
+                      <th>Lãi gộp</th>
+                      <th>Tỷ suất</th>
+                    </tr>
+                  </thead>
+                  <tbody>
+                    {productMargins.map((entry) => (
+                      <tr key={entry.key ?? "none"}>
+                        <td>
+                          {entry.name || "Hàng không mã"}
+                          {/* Dòng bán khi chưa có giá vốn được tính giá vốn bằng 0 */}
+                          {entry.uncostedLines > 0 && " *"}
+                        </td>
+                        <td>{formatCurrency(entry.revenue)}</td>
+                        <td>{formatCurrency(entry.cost)}</td>
+                        <td>{formatCurrency(entry.grossProfit)}</td>
+                        <td>{entry.marginPercent != null ? `${entry.marginPercent}%` : "—"}</td>
+                      </tr>
+                    ))}
+                  </tbody>
+                </table>
+              </div>
+            </section>
+          )}
         </>
       )}
     </div>
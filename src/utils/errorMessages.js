@@ -0,0 +1,95 @@
+// Lỗi từ backend có dạng { code, field, messageKey, message }; message là tiếng Anh
+// nên màn hình tra bảng này theo messageKey/code, chỉ dùng message khi chưa có bản dịch.
+const ERROR_MESSAGES = {
+  // Sản phẩm
+  product_name_required: "Tên sản phẩm không được để trống.",
+  product_price_invalid: "Giá bán không được âm.",
+  product_not_found: "Không tìm thấy sản phẩm.",
+  product_cost_invalid: "Giá vốn không được âm.",
+  product_barcode_exists: "Mã vạch đã được dùng cho sản phẩm khác.",
+  unit_barcode_exists: "Mã vạch đã được dùng cho một đơn vị bán.",
+  barcode_check_digit_invalid:
+    "Số kiểm tra của mã vạch không đúng; đánh dấu là mã nội bộ nếu muốn giữ mã này.",
+  category_not_found: "Không tìm thấy danh mục.",
+
+  // Đơn vị bán
+  unit_name_required: "Tên đơn vị không được để trống.",
+  unit_name_duplicated: "Tên đơn vị trùng với đơn vị cơ bản hoặc một đơn vị khác của sản phẩm.",
+  unit_factor_invalid: "Hệ số quy đổi phải lớn hơn 0.",
+  unit_price_invalid: "Giá bán không được âm.",
+  unit_product_mismatch: "Không thể chuyển đơn vị sang sản phẩm khác.",
+  product_unit_not_found: "Không tìm thấy đơn vị bán.",
+
+  // Nhập / xuất file
+  file_path_required: "Chưa chọn file.",
+  import_file_empty: "File không có dòng dữ liệu nào.",
+  import_file_unreadable: "Không đọc được file.",
+  import_column_missing: (error) => `File cần có cột "${error.field}".`,
+  import_price_invalid: "Giá phải là số nguyên từ 0 trở lên.",
+  import_display_order_invalid: "Thứ tự hiển thị phải là số nguyên.",
+  import_flag_invalid: "Dùng 1/0, true/false hoặc có/không.",
+  import_barcode_required: "Cập nhật theo mã vạch cần mã vạch ở mọi dòng.",
+  import_barcode_duplicated: "Mã vạch bị trùng với một dòng khác trong file.",
+  product_archived: "Mã vạch thuộc một sản phẩm đã lưu trữ; khôi phục sản phẩm trước khi nhập.",
+  export_failed: "Không ghi được file.",
+
+  // Bán hàng và thanh toán
+  payment_figure_mismatch: "Giá hoặc số tiền đã thay đổi, vui lòng kiểm tra lại giỏ hàng.",
+  payment_items_empty: "Giỏ hàng chưa có sản phẩm nào.",
+  item_quantity_invalid: "Số lượng phải lớn hơn 0.",
+  item_name_required: "Tên món không được để trống.",
+  base_unit_price_negative: "Đơn giá không được âm.",
+  edited_unit_price_negative: "Giá sửa tay không được âm.",
+  line_discount_negative: "Giảm giá không được âm.",
+  line_discount_exceeds_subtotal: "Giảm giá không được vượt quá thành tiền của dòng.",
+  item_product_not_found: "Sản phẩm trong giỏ không còn tồn tại.",
+  item_product_archived: "Sản phẩm trong giỏ đã được lưu trữ.",
+  item_unit_invalid: "Đơn vị bán không thuộc sản phẩm này.",
+  cashier_name_required: "Chưa chọn thu ngân.",
+  paid_cash_insufficient: "Tiền khách đưa chưa đủ.",
+  idempotency_key_invalid: "Mã chống trùng hoá đơn không hợp lệ.",
+  idempotency_key_reused: "Hoá đơn này đã được lưu với số tiền khác.",
+  stock_insufficient: "Không đủ hàng trong kho để bán.",
+  embedded_value_invalid: "Tem cân không có khối lượng hoặc giá tiền.",
+
+  // Huỷ hoá đơn và hoàn trả
+  payment_not_found: "Không tìm thấy hoá đơn.",
+  payment_already_voided: "Hoá đơn đã được huỷ trước đó.",
+  payment_voided: "Không thể hoàn trả hoá đơn đã huỷ.",
+  voided_by_required: "Cần ghi rõ người huỷ hoá đơn.",
+  void_reason_required: "Cần nhập lý do huỷ hoá đơn.",
+  refund_lines_empty: "Chọn ít nhất một dòng để hoàn trả.",
+  refund_line_duplicated: "Mỗi dòng chỉ được hoàn trả một lần trong một phiếu.",
+  refund_quantity_invalid: "Số lượng hoàn trả phải lớn hơn 0.",
+  refund_line_not_in_payment: "Dòng này không thuộc hoá đơn.",
+  refund_quantity_exceeds_sold: "Số lượng hoàn trả vượt quá số còn có thể hoàn.",
+
+  // Số hoá đơn, ngày làm việc và tra cứu
+  invoice_format_code_invalid: "Các phần của số hoá đơn phải ngắn và không có khoảng trắng.",
+  invoice_format_counter_width_invalid: "Độ dài bộ đếm không hợp lệ.",
+  invoice_format_reset_without_date: "Phần ngày phải chứa kỳ mà bộ đếm được đặt lại.",
+  invoice_format_date_pattern_invalid: "Phần ngày dùng mã định dạng không được hỗ trợ.",
+  store_timezone_invalid: "Múi giờ phải là tên IANA, ví dụ Asia/Ho_Chi_Minh.",
+  business_day_cutover_invalid: "Giờ chốt ngày phải từ 0 đến 23.",
+  search_limit_invalid: "Số dòng mỗi trang không hợp lệ.",
+  search_date_invalid: "Ngày phải có dạng YYYY-MM-DD.",
+  search_date_range_invalid: "Ngày kết thúc không được trước ngày bắt đầu.",
+  search_amount_range_invalid: "Số tiền tối đa không được nhỏ hơn số tiền tối thiểu.",
+  report_date_range_required: "Báo cáo cần có ngày bắt đầu và ngày kết thúc.",
+
+  // Chung
+  database_unavailable: "Không mở được cơ sở dữ liệu.",
+  database_locked: "Cơ sở dữ liệu đang bận, vui lòng thử lại.",
+  database_error: "Lỗi cơ sở dữ liệu.",
+  constraint_violation: "Dữ liệu vi phạm ràng buộc.",
+};
+
+export const translateError = (error) => {
+  if (!error) return "";
+  const code = error.messageKey?.replace(/^errors\./, "") ?? error.code;
+  const entry = ERROR_MESSAGES[code] ?? ERROR_MESSAGES[error.code];
+  if (typeof entry === "function") return entry(error);
+  return entry ?? error.message ?? String(error);
+};
+
+export default ERROR_MESSAGES;